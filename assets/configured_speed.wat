@@ -0,0 +1,12 @@
+(module
+  (import "config" "get" (func $config (param f64 i32 i32) (result f64)))
+  (memory (export "memory") 1)
+  (data (i32.const 0) "speed")
+
+  ;; The configured speed, or 1 if none is configured.
+  (func (export "speed") (param $entity f64) (result f64)
+    (local $speed f64)
+    (local.set $speed (call $config (local.get $entity) (i32.const 0) (i32.const 5)))
+    (if (result f64) (f64.ne (local.get $speed) (local.get $speed))
+      (then (f64.const 1))
+      (else (local.get $speed)))))
@@ -0,0 +1,8 @@
+(module
+  (memory 1)
+  (type $grow_t (func (param i32) (result i32)))
+  ;; Grows memory by the given number of pages, returning the old size or -1 on failure.
+  (func $grow_f (type $grow_t) (param $pages i32) (result i32)
+    (memory.grow (local.get $pages)))
+  (export "memory" (memory 0))
+  (export "main" (func $grow_f)))
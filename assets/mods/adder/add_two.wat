@@ -0,0 +1,7 @@
+(module
+  (type $add_two_t (func (param i32) (result i32)))
+  (func $add_two_f (type $add_two_t) (param $value i32) (result i32)
+    local.get $value
+    i32.const 2
+    i32.add)
+  (export "main" (func $add_two_f)))
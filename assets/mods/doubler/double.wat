@@ -0,0 +1,7 @@
+(module
+  (type $double_t (func (param i32) (result i32)))
+  (func $double_f (type $double_t) (param $value i32) (result i32)
+    local.get $value
+    i32.const 2
+    i32.mul)
+  (export "main" (func $double_f)))
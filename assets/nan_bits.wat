@@ -0,0 +1,5 @@
+(module
+  ;; The bits of 0/0, which are the same on every machine in deterministic mode.
+  (func (export "nan_bits") (result i32)
+    (i32.reinterpret_f32
+      (f32.div (f32.const 0) (f32.const 0)))))
@@ -0,0 +1,7 @@
+(module
+  (func $play_sound (import "audio" "play_sound") (param i32))
+  (type $main_t (func (param i32) (result i32)))
+  (func $main_f (type $main_t) (param $value i32) (result i32)
+    (call $play_sound (local.get $value))
+    local.get $value)
+  (export "main" (func $main_f)))
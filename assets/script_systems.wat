@@ -0,0 +1,30 @@
+(module
+  (import "systems" "add_system" (func $add_system (param i32 i32 i32 i32) (result i32)))
+  (import "systems" "add_run_condition" (func $add_run_condition (param i32 i32 i32) (result i32)))
+  (import "env" "log_count" (func $log_count (param i32 i32)))
+  (memory 1)
+  (data (i32.const 0) "on_frame")
+  (data (i32.const 16) "every_other_frame")
+  (data (i32.const 48) "tick")
+  (data (i32.const 64) "fixed")
+  (global $frames (mut i32) (i32.const 0))
+  (global $ticks (mut i32) (i32.const 0))
+  ;; Run on_frame in the default phase, but only every other frame. Run tick in the "fixed" phase.
+  (func $register_f
+    (drop (call $add_run_condition
+      (call $add_system (i32.const 0) (i32.const 8) (i32.const 0) (i32.const 0))
+      (i32.const 16) (i32.const 17)))
+    (drop (call $add_system (i32.const 48) (i32.const 4) (i32.const 64) (i32.const 5))))
+  (func $every_other_frame_f (result i32)
+    (global.set $frames (i32.add (global.get $frames) (i32.const 1)))
+    (i32.rem_u (global.get $frames) (i32.const 2)))
+  (func $on_frame_f
+    (call $log_count (i32.const 0) (global.get $frames)))
+  (func $tick_f
+    (global.set $ticks (i32.add (global.get $ticks) (i32.const 1)))
+    (call $log_count (i32.const 1) (global.get $ticks)))
+  (export "memory" (memory 0))
+  (export "register" (func $register_f))
+  (export "every_other_frame" (func $every_other_frame_f))
+  (export "on_frame" (func $on_frame_f))
+  (export "tick" (func $tick_f)))
@@ -0,0 +1,15 @@
+(module
+  (import "events" "event_type_id" (func $event_type_id (param i32 i32) (result i32)))
+  (import "events" "send_event" (func $send_event (param i32 i32 i32) (result i32)))
+  (memory 1)
+  (data (i32.const 0) "score")
+  (data (i32.const 16) "(points: 10)")
+  (type $main_t (func (result i32)))
+  ;; Sends a score event, returning the result of send_event.
+  (func $main_f (type $main_t) (result i32)
+    (call $send_event
+      (call $event_type_id (i32.const 0) (i32.const 5))
+      (i32.const 16)
+      (i32.const 12)))
+  (export "memory" (memory 0))
+  (export "main" (func $main_f)))
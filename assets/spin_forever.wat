@@ -0,0 +1,12 @@
+(module
+  (type $spin_t (func (param i32) (result i32)))
+  ;; Counts up until the counter reaches the given value, which for negative values is never.
+  (func $spin_f (type $spin_t) (param $until i32) (result i32)
+    (local $count i32)
+    (block $done
+      (loop $spin
+        (local.set $count (i32.add (local.get $count) (i32.const 1)))
+        (br_if $done (i32.eq (local.get $count) (local.get $until)))
+        (br $spin)))
+    (local.get $count))
+  (export "main" (func $spin_f)))
@@ -0,0 +1,37 @@
+(module
+  (import "query" "component_id" (func $component_id (param i32 i32) (result i32)))
+  (import "query" "query" (func $query (param i32 i32) (result i32)))
+  (import "query" "query_next" (func $query_next (param i32 i32 i32) (result i32)))
+  (import "query" "query_close" (func $query_close (param i32) (result i32)))
+  (memory 1)
+  (data (i32.const 0) "health")
+  (type $main_t (func (result i32)))
+  ;; Sums the health of every entity with health. Rows are an 8 byte entity id, then a 4 byte i32.
+  ;; Reads rows in batches of up to 16, at offset 256.
+  (func $main_f (type $main_t) (result i32)
+    (local $handle i32)
+    (local $rows i32)
+    (local $row i32)
+    (local $total i32)
+    (i32.store (i32.const 32) (call $component_id (i32.const 0) (i32.const 6)))
+    (local.set $handle (call $query (i32.const 32) (i32.const 1)))
+    (if (i32.lt_s (local.get $handle) (i32.const 0))
+      (then (return (i32.const -1))))
+    (block $done
+      (loop $batch
+        (local.set $rows (call $query_next (local.get $handle) (i32.const 256) (i32.const 192)))
+        (br_if $done (i32.le_s (local.get $rows) (i32.const 0)))
+        (local.set $row (i32.const 0))
+        (block $batch_done
+          (loop $each
+            (br_if $batch_done (i32.ge_s (local.get $row) (local.get $rows)))
+            (local.set $total
+              (i32.add (local.get $total)
+                (i32.load (i32.add (i32.const 264) (i32.mul (local.get $row) (i32.const 12))))))
+            (local.set $row (i32.add (local.get $row) (i32.const 1)))
+            (br $each)))
+        (br $batch)))
+    (drop (call $query_close (local.get $handle)))
+    (local.get $total))
+  (export "memory" (memory 0))
+  (export "main" (func $main_f)))
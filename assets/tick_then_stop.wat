@@ -0,0 +1,18 @@
+(module
+  (import "timers" "set_timeout" (func $set_timeout (param f64 i32 i32) (result i32)))
+  (import "timers" "set_interval" (func $set_interval (param f64 i32 i32) (result i32)))
+  (import "timers" "clear_timer" (func $clear_timer (param i32) (result i32)))
+  (import "env" "announce" (func $announce (param f64 i32)))
+
+  (global $ticking (mut i32) (i32.const -1))
+
+  ;; Tick every half second, and stop after three seconds.
+  (func (export "start") (param $entity f64)
+    (global.set $ticking
+      (call $set_interval (local.get $entity) (i32.const 500) (i32.const 1)))
+    (drop (call $set_timeout (local.get $entity) (i32.const 3000) (i32.const 2))))
+
+  (func (export "on_timer") (param $entity f64) (param $callback i32)
+    (call $announce (local.get $entity) (local.get $callback))
+    (if (i32.eq (local.get $callback) (i32.const 2))
+      (then (drop (call $clear_timer (global.get $ticking)))))))
@@ -0,0 +1,59 @@
+;; Announces three steps, waiting a second between each. A real script would be built from another
+;; language and transformed with `wasm-opt --asyncify`; this one implements the same protocol by hand,
+;; saving only the current step to the coroutine stack.
+(module
+  (import "coroutine" "wait_seconds" (func $wait_seconds (param f32)))
+  (import "env" "announce" (func $announce (param f64 i32)))
+  (memory (export "memory") 1)
+
+  ;; 0: running normally, 1: unwinding, 2: rewinding
+  (global $state (mut i32) (i32.const 0))
+  (global $data (mut i32) (i32.const 0))
+  (global $next_stack (mut i32) (i32.const 1024))
+
+  (func (export "asyncify_start_unwind") (param $data i32)
+    (global.set $state (i32.const 1))
+    (global.set $data (local.get $data)))
+  (func (export "asyncify_stop_unwind")
+    (global.set $state (i32.const 0)))
+  (func (export "asyncify_start_rewind") (param $data i32)
+    (global.set $state (i32.const 2))
+    (global.set $data (local.get $data)))
+  (func (export "asyncify_stop_rewind")
+    (global.set $state (i32.const 0)))
+  (func (export "asyncify_get_state") (result i32)
+    (global.get $state))
+
+  ;; Each buffer is 64 bytes: the saved stack's current end, the buffer's end, then the stack.
+  (func (export "coroutine_stack_alloc") (result i32)
+    (local $ptr i32)
+    (local.set $ptr (global.get $next_stack))
+    (i32.store (local.get $ptr) (i32.add (local.get $ptr) (i32.const 8)))
+    (i32.store offset=4 (local.get $ptr) (i32.add (local.get $ptr) (i32.const 64)))
+    (global.set $next_stack (i32.add (local.get $ptr) (i32.const 64)))
+    (local.get $ptr))
+
+  (func (export "run") (param $entity f64)
+    (local $step i32)
+    ;; When rewinding, pop the step we were on.
+    (if (i32.eq (global.get $state) (i32.const 2))
+      (then
+        (i32.store (global.get $data)
+          (i32.sub (i32.load (global.get $data)) (i32.const 4)))
+        (local.set $step (i32.load (i32.load (global.get $data))))))
+    (block $done
+      (loop $steps
+        (br_if $done (i32.ge_u (local.get $step) (i32.const 3)))
+        (if (i32.ne (global.get $state) (i32.const 2))
+          (then (call $announce (local.get $entity) (local.get $step))))
+        (call $wait_seconds (f32.const 1))
+        ;; When unwinding, push the step we are on and return to the host.
+        (if (i32.eq (global.get $state) (i32.const 1))
+          (then
+            (i32.store (i32.load (global.get $data)) (local.get $step))
+            (i32.store (global.get $data)
+              (i32.add (i32.load (global.get $data)) (i32.const 4)))
+            (return)))
+        (local.set $step (i32.add (local.get $step) (i32.const 1)))
+        (br $steps)))
+    (call $announce (local.get $entity) (i32.const 3))))
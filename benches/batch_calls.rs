@@ -0,0 +1,111 @@
+use std::time::Duration;
+
+use bevy::{asset::AssetPlugin, ecs::system::SystemState, prelude::*};
+use bevy_wasm_scripting::*;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use wasmer::{imports, Module};
+
+const ON_UPDATE: &str = r#"
+(module
+  (func $on_update (param f64 f32) (result f32)
+    (f32.add (f32.demote_f64 (local.get 0)) (local.get 1)))
+  (func $tick (result i32)
+    (i32.const 1))
+  (export "on_update" (func $on_update))
+  (export "tick" (func $tick)))
+"#;
+
+fn setup() -> (App, Handle<WasmScript>) {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_plugin(AssetPlugin::default())
+        .add_plugin(WasmPlugin);
+    let world = &mut app.world;
+    let module = Module::new(&world.resource::<WasmerStore>().0, ON_UPDATE).unwrap();
+    let handle = world
+        .resource_mut::<Assets<WasmScript>>()
+        .add(WasmScript::Compiled(module));
+    world.resource_scope(|world, mut assets: Mut<Assets<WasmScript>>| {
+        let mut wasmer_store = world.resource_mut::<WasmerStore>();
+        let script = assets.get_mut(&handle).unwrap();
+        assert!(script.instantiate_if_compiled(&mut wasmer_store, &imports! {}));
+    });
+    (app, handle)
+}
+
+fn batch_calls(c: &mut Criterion) {
+    bench_on_update(c, "on_update", None);
+    bench_on_update(c, "on_update_with_timeout", Some(Duration::from_secs(1)));
+}
+
+fn bench_on_update(c: &mut Criterion, name: &str, timeout: Option<Duration>) {
+    let (mut app, handle) = setup();
+    app.world
+        .resource_mut::<WasmWatchdog>()
+        .set_timeout(timeout);
+    let mut state = SystemState::<WasmScriptEnv>::new(&mut app.world);
+    let mut group = c.benchmark_group(name);
+    for entity_count in [100u32, 1_000, 10_000] {
+        let entities: Vec<Entity> = (0..entity_count).map(Entity::from_raw).collect();
+        group.bench_with_input(
+            BenchmarkId::new("call_if_instantiated_2", entity_count),
+            &entities,
+            |b, entities| {
+                b.iter(|| {
+                    let mut script_env = state.get_mut(&mut app.world);
+                    for entity in entities {
+                        black_box(script_env.call_if_instantiated_2::<f64, f32, f32>(
+                            &handle,
+                            "on_update",
+                            EntityId::from_entity(*entity),
+                            0.016,
+                        ))
+                        .unwrap();
+                    }
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("call_batch_if_instantiated_2", entity_count),
+            &entities,
+            |b, entities| {
+                b.iter(|| {
+                    let mut script_env = state.get_mut(&mut app.world);
+                    black_box(
+                        script_env
+                            .call_batch_if_instantiated_2::<_, f64, f32, f32>(
+                                &handle,
+                                "on_update",
+                                entities.iter().map(|entity| {
+                                    (*entity, (EntityId::from_entity(*entity), 0.016))
+                                }),
+                            )
+                            .unwrap(),
+                    );
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("call_batch_if_instantiated_0", entity_count),
+            &entities,
+            |b, entities| {
+                b.iter(|| {
+                    let mut script_env = state.get_mut(&mut app.world);
+                    black_box(
+                        script_env
+                            .call_batch_if_instantiated_0::<_, i32>(
+                                &handle,
+                                "tick",
+                                entities.iter().map(|entity| (*entity, ())),
+                            )
+                            .unwrap(),
+                    );
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, batch_calls);
+criterion_main!(benches);
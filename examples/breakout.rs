@@ -85,6 +85,18 @@ fn main() {
         // NEW - Update balls, using their script.
         .add_system(ball_on_update_script)
         .add_wasm_script_component::<BallScript>()
+        // NEW - Let ball scripts react to collisions, with an exported `on_collision(me, other)`.
+        .add_wasm_event_handler::<BallScript, CollisionEvent, _>(
+            "on_collision",
+            |event| match event {
+                CollisionEvent::BallWall(ball, other) | CollisionEvent::BallBrick(ball, other) => {
+                    vec![(
+                        *ball,
+                        (EntityId::from_entity(*ball), EntityId::from_entity(*other)),
+                    )]
+                }
+            },
+        )
         .run();
 }
 
@@ -0,0 +1,62 @@
+use bevy::{prelude::*, DefaultPlugins};
+use bevy_wasm_scripting::*;
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        // Must be inserted before the WasmPlugin, which creates the engine.
+        .insert_resource(WasmDeterminism::enabled())
+        .add_plugin(WasmPlugin)
+        .add_wasm_script_resource::<DeterministicScripts>()
+        .add_startup_system(add_script_resource)
+        .add_system(call_script)
+        .add_system(report_rejected_scripts)
+        .run();
+}
+
+#[derive(Resource)]
+struct DeterministicScripts {
+    nan_bits: Handle<WasmScript>,
+    // Imports `timers`, so is rejected in deterministic mode.
+    tick_then_stop: Handle<WasmScript>,
+}
+
+impl WasmScriptResource for DeterministicScripts {
+    type ImportQueriedComponents = ();
+    type ImportResources = ();
+
+    fn get_handle(&self) -> Option<&Handle<WasmScript>> {
+        Some(&self.nan_bits)
+    }
+}
+
+fn add_script_resource(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(DeterministicScripts {
+        nan_bits: asset_server.load("nan_bits.wat"),
+        tick_then_stop: asset_server.load("tick_then_stop.wat"),
+    });
+}
+
+fn call_script(
+    scripts: Res<DeterministicScripts>,
+    mut script_env: WasmScriptResourceEnv<DeterministicScripts>,
+) {
+    if let Ok(bits) = script_env.call_if_instantiated_0::<i32>(&scripts.nan_bits, "nan_bits") {
+        println!("0/0 has the bits {:#010x}", bits);
+    }
+}
+
+fn report_rejected_scripts(
+    scripts: Res<DeterministicScripts>,
+    mut script_errors: EventReader<WasmScriptError>,
+) {
+    for error in script_errors.iter() {
+        if let WasmScriptError::NonDeterministic { script, problems } = error {
+            if *script == scripts.tick_then_stop {
+                for problem in problems {
+                    println!("tick_then_stop was rejected: it {}", problem);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use bevy::{prelude::*, DefaultPlugins};
+use bevy_wasm_scripting::*;
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugin(WasmPlugin)
+        .add_wasm_script_component::<AdderScript>()
+        .add_startup_system(spawn_inline_scripts)
+        .add_system(call_script_on_entity)
+        .run();
+}
+
+#[derive(Component)]
+struct AdderScript {
+    handle: Handle<WasmScript>,
+    accumulator: i32,
+}
+
+impl WasmScriptComponent for AdderScript {
+    type ImportQueriedComponents = ();
+    type ImportResources = ();
+    fn get_wasm_script_handle(&self) -> &Handle<WasmScript> {
+        &self.handle
+    }
+}
+
+// A script generated at runtime, which adds `step` each call.
+fn add_step_wat(step: i32) -> String {
+    format!(
+        r#"(module
+  (func (export "main") (param $value i32) (result i32)
+    local.get $value
+    i32.const {}
+    i32.add))"#,
+        step
+    )
+}
+
+fn spawn_inline_scripts(mut commands: Commands, mut scripts: ResMut<Assets<WasmScript>>) {
+    // Embedded in the binary, rather than loaded from the asset folder.
+    let embedded = WasmScript::from_wat_str("add_one", include_str!("../assets/add_one.wat"))
+        .expect("add_one.wat is valid");
+    commands.spawn(AdderScript {
+        handle: scripts.add(embedded),
+        accumulator: 0,
+    });
+    let generated =
+        WasmScript::from_wat_str("add_ten", &add_step_wat(10)).expect("Generated wat is valid");
+    commands.spawn(AdderScript {
+        handle: scripts.add(generated),
+        accumulator: 0,
+    });
+}
+
+fn call_script_on_entity(
+    mut scripted_entities: Query<&mut AdderScript>,
+    mut script_env: WasmScriptComponentEnv<AdderScript>,
+) {
+    for mut scripted_entity in scripted_entities.iter_mut() {
+        if let Ok(new_val) = script_env.call_if_instantiated_1(
+            &scripted_entity.handle,
+            "main",
+            scripted_entity.accumulator,
+        ) {
+            scripted_entity.accumulator = new_val;
+        }
+        println!("Accumulated value: {}", scripted_entity.accumulator);
+    }
+}
@@ -0,0 +1,73 @@
+use bevy::{prelude::*, DefaultPlugins};
+use bevy_wasm_scripting::*;
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugin(WasmPlugin)
+        .add_wasm_script_component::<GreedyScript>()
+        .add_startup_system(spawn_script_entity)
+        .add_system(call_script_on_entity)
+        .add_system(report_memory_errors)
+        .run();
+}
+
+#[derive(Component)]
+struct GreedyScript {
+    handle: Handle<WasmScript>,
+}
+
+impl WasmScriptComponent for GreedyScript {
+    type ImportQueriedComponents = ();
+    type ImportResources = ();
+
+    fn get_wasm_script_handle(&self) -> &Handle<WasmScript> {
+        &self.handle
+    }
+
+    // 4 pages of 64KiB each.
+    fn memory_limit() -> Option<u32> {
+        Some(4)
+    }
+}
+
+fn spawn_script_entity(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    memory_limits: Res<WasmMemoryLimits>,
+) {
+    // No script may use more than 16MiB, whatever its type.
+    memory_limits.set_default_limit(Some(256));
+    commands.spawn(GreedyScript {
+        handle: asset_server.load("grow_memory.wat"),
+    });
+}
+
+fn call_script_on_entity(
+    scripted_entities: Query<&GreedyScript>,
+    mut script_env: WasmScriptComponentEnv<GreedyScript>,
+) {
+    for scripted_entity in scripted_entities.iter() {
+        if let Ok(old_size) =
+            script_env.call_if_instantiated_1::<i32, i32>(&scripted_entity.handle, "main", 1)
+        {
+            println!("Grew memory from {} pages", old_size);
+        }
+    }
+}
+
+fn report_memory_errors(mut script_errors: EventReader<WasmScriptError>) {
+    for error in script_errors.iter() {
+        if let WasmScriptError::MemoryLimitExceeded {
+            script,
+            limit,
+            requested,
+        } = error
+        {
+            println!(
+                "{} wanted {} pages, but is limited to {}",
+                script, requested, limit
+            );
+        }
+    }
+}
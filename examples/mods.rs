@@ -0,0 +1,77 @@
+use bevy::{prelude::*, DefaultPlugins};
+use bevy_wasm_scripting::*;
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugin(WasmPlugin)
+        // Scans assets/mods for .mod.ron files.
+        .add_plugin(WasmModsPlugin::default())
+        .add_wasm_script_component::<ModScript>()
+        .add_system(spawn_mod_scripts)
+        .add_system(call_mod_scripts)
+        .run();
+}
+
+#[derive(Component)]
+struct ModScript {
+    handle: Handle<WasmScript>,
+    order: usize,
+}
+
+impl WasmScriptComponent for ModScript {
+    type ImportQueriedComponents = ();
+    type ImportResources = ();
+
+    fn get_wasm_script_handle(&self) -> &Handle<WasmScript> {
+        &self.handle
+    }
+
+    // Mods get no capabilities beyond what they request in their manifests, and we grant none.
+    fn capabilities() -> ScriptCapabilities {
+        ScriptCapabilities::none()
+    }
+}
+
+fn spawn_mod_scripts(
+    mut commands: Commands,
+    loaded_mods: Res<LoadedMods>,
+    script_manifests: Res<Assets<WasmScriptManifest>>,
+) {
+    if !loaded_mods.is_changed() || !loaded_mods.resolved {
+        return;
+    }
+    for failed in loaded_mods.failed.iter() {
+        println!("Mod at {:?} failed: {:?}", failed.path, failed.reason);
+    }
+    for (order, loaded) in loaded_mods.active.iter().enumerate() {
+        println!("Loading mod {} {}", loaded.name, loaded.version);
+        for script in loaded.scripts.iter() {
+            if let Some(manifest) = script_manifests.get(script) {
+                commands.spawn(ModScript {
+                    handle: manifest.script.clone(),
+                    order,
+                });
+            }
+        }
+    }
+}
+
+// Run every mod's script on the value, in load order.
+fn call_mod_scripts(
+    scripted_entities: Query<&ModScript>,
+    mut script_env: WasmScriptComponentEnv<ModScript>,
+) {
+    let mut scripts: Vec<&ModScript> = scripted_entities.iter().collect();
+    if scripts.is_empty() {
+        return;
+    }
+    scripts.sort_by_key(|script| script.order);
+    let mut value = 1;
+    for script in scripts {
+        if let Ok(new_value) = script_env.call_if_instantiated_1(&script.handle, "main", value) {
+            value = new_value;
+        }
+    }
+    println!("Modded value: {}", value);
+}
@@ -0,0 +1,36 @@
+use bevy::{prelude::*, DefaultPlugins};
+use bevy_wasm_scripting::*;
+use wasmer::Value;
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        // Registers `WasmScripts`, so entities may have any number of scripts.
+        .add_plugin(WasmPlugin)
+        .add_startup_system(spawn_script_entity)
+        .add_system(call_each_script)
+        .run();
+}
+
+fn spawn_script_entity(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn(WasmScripts::new([
+        asset_server.load("add_one.wat"),
+        asset_server.load("multiply_two.wat"),
+    ]));
+}
+
+fn call_each_script(
+    scripted_entities: Query<&WasmScripts>,
+    mut script_env: WasmScriptComponentEnv<WasmScripts>,
+) {
+    for scripts in scripted_entities.iter() {
+        // Each script's `main` is called in order, so add_one runs before multiply_two.
+        for (handle, result) in scripts.call_each(&mut script_env, "main", (10,)) {
+            if let Ok(rets) = result {
+                if let [Value::I32(value)] = &*rets {
+                    println!("Script {:?} returned {}", handle.id(), value);
+                }
+            }
+        }
+    }
+}
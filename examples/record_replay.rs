@@ -0,0 +1,78 @@
+use bevy::{prelude::*, DefaultPlugins};
+use bevy_wasm_scripting::*;
+
+const RECORDING: &str = "add_one.rec";
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .insert_resource(WasmCallRecorder::default())
+        .add_plugin(WasmPlugin)
+        .add_wasm_script_resource::<AddOneScript>()
+        .add_startup_system(start_recording)
+        .add_system(call_script)
+        .add_system(stop_and_replay)
+        .run();
+}
+
+#[derive(Resource)]
+struct AddOneScript {
+    handle: Handle<WasmScript>,
+}
+
+impl WasmScriptResource for AddOneScript {
+    type ImportQueriedComponents = ();
+    type ImportResources = ();
+
+    fn get_handle(&self) -> Option<&Handle<WasmScript>> {
+        Some(&self.handle)
+    }
+}
+
+fn start_recording(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    recorder: Res<WasmCallRecorder>,
+) {
+    commands.insert_resource(AddOneScript {
+        handle: asset_server.load("add_one.wat"),
+    });
+    // Started before the script is instantiated, since only scripts instantiated while recording
+    // have their calls and host imports recorded.
+    if let Err(err) = recorder.start(RECORDING) {
+        println!("Could not record: {}", err);
+    }
+}
+
+fn call_script(
+    script: Res<AddOneScript>,
+    time: Res<Time>,
+    mut script_env: WasmScriptResourceEnv<AddOneScript>,
+) {
+    let _ = script_env.call_if_instantiated_1::<i32, i32>(
+        &script.handle,
+        "add_one",
+        time.elapsed_seconds() as i32,
+    );
+}
+
+// Press R to stop recording, and replay the recording against the script, outside of the game.
+fn stop_and_replay(keyboard_input: Res<Input<KeyCode>>, recorder: Res<WasmCallRecorder>) {
+    if !keyboard_input.just_pressed(KeyCode::R) || !recorder.is_recording() {
+        return;
+    }
+    let report = recorder
+        .stop()
+        .map_err(anyhow::Error::new)
+        .and_then(|_| WasmReplay::load(RECORDING, "add_one"))
+        .and_then(|replay| replay.run(include_bytes!("../assets/add_one.wat")));
+    match report {
+        Ok(report) => {
+            println!("Replayed {} calls", report.calls);
+            for mismatch in report.mismatches.iter() {
+                println!("Mismatch: {}", mismatch);
+            }
+        }
+        Err(err) => println!("Could not replay: {}", err),
+    }
+}
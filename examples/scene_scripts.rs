@@ -0,0 +1,34 @@
+use bevy::{prelude::*, DefaultPlugins};
+use bevy_wasm_scripting::*;
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        // Registers `WasmScriptPath`, so that scenes can spawn scripted entities.
+        .add_plugin(WasmPlugin)
+        .add_startup_system(load_scene)
+        .add_system(call_scene_scripts)
+        .run();
+}
+
+fn load_scene(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn(DynamicSceneBundle {
+        scene: asset_server.load("scenes/scripted.scn.ron"),
+        ..default()
+    });
+}
+
+fn call_scene_scripts(
+    scripted_entities: Query<(Entity, &WasmScriptPath)>,
+    mut script_env: WasmScriptComponentEnv<WasmScriptPath>,
+) {
+    for (entity, script) in scripted_entities.iter() {
+        if let Ok(speed) = script_env.call_if_instantiated_1::<EntityId, f64>(
+            script.handle(),
+            "speed",
+            EntityId::from_entity(entity),
+        ) {
+            println!("{:?} runs {} at speed {}", entity, script.path, speed);
+        }
+    }
+}
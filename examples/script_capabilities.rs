@@ -0,0 +1,82 @@
+use bevy::{prelude::*, DefaultPlugins};
+use bevy_wasm_scripting::*;
+use wasmer::{imports, Function};
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugin(WasmPlugin)
+        // Any import in the "audio" namespace requires the "audio" capability.
+        .add_wasm_capability("audio", &["audio"])
+        .add_wasm_script_component::<ModScript>()
+        .add_startup_system(spawn_script_entity)
+        .add_system(call_script_on_entity)
+        .add_system(report_denied_calls)
+        .run();
+}
+
+#[derive(Component)]
+struct ModScript {
+    handle: Handle<WasmScript>,
+}
+
+impl WasmScriptComponent for ModScript {
+    type ImportQueriedComponents = ();
+    type ImportResources = ();
+
+    fn get_wasm_script_handle(&self) -> &Handle<WasmScript> {
+        &self.handle
+    }
+
+    // Mod scripts may spawn things, but may not play sounds.
+    fn capabilities() -> ScriptCapabilities {
+        ScriptCapabilities::only(["spawn"])
+    }
+
+    fn get_imports_from_world(
+        wasmer_store: &mut WasmerStore,
+        _world: &WorldPointer,
+    ) -> wasmer::Imports {
+        imports! {
+            "audio" => {
+                "play_sound" => Function::new_typed(&mut wasmer_store.0, |sound: i32| println!("Playing sound {}", sound)),
+            }
+        }
+    }
+}
+
+fn spawn_script_entity(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn(ModScript {
+        handle: asset_server.load("play_sound.wat"),
+    });
+}
+
+fn call_script_on_entity(
+    scripted_entities: Query<&ModScript>,
+    mut script_env: WasmScriptComponentEnv<ModScript>,
+) {
+    for scripted_entity in scripted_entities.iter() {
+        if let Err(err) =
+            script_env.call_if_instantiated_1::<i32, i32>(&scripted_entity.handle, "main", 1)
+        {
+            println!("Script failed: {}", err);
+        }
+    }
+}
+
+fn report_denied_calls(mut script_errors: EventReader<WasmScriptError>) {
+    for error in script_errors.iter() {
+        if let WasmScriptError::CapabilityDenied {
+            script,
+            capability,
+            function,
+            ..
+        } = error
+        {
+            println!(
+                "{} tried to call {} without {}",
+                script, function, capability
+            );
+        }
+    }
+}
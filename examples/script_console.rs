@@ -0,0 +1,60 @@
+use std::{
+    io::BufRead,
+    sync::{
+        mpsc::{channel, Receiver},
+        Mutex,
+    },
+};
+
+use bevy::{prelude::*, DefaultPlugins};
+use bevy_wasm_scripting::*;
+
+// Type commands such as `scripts`, `exports add_one` or `call 0 add_one 41` into the terminal.
+fn main() {
+    let (sender, receiver) = channel();
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lock().lines().map_while(Result::ok) {
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugin(WasmPlugin)
+        .add_plugin(WasmConsolePlugin)
+        .add_wasm_script_component::<AdderScript>()
+        .insert_resource(TerminalInput(Mutex::new(receiver)))
+        .add_startup_system(spawn_script_entity)
+        .add_system(forward_terminal_input)
+        .run();
+}
+
+#[derive(Component)]
+struct AdderScript(Handle<WasmScript>);
+
+impl WasmScriptComponent for AdderScript {
+    type ImportQueriedComponents = ();
+    type ImportResources = ();
+    fn get_wasm_script_handle(&self) -> &Handle<WasmScript> {
+        &self.0
+    }
+}
+
+#[derive(Resource)]
+struct TerminalInput(Mutex<Receiver<String>>);
+
+fn spawn_script_entity(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn(AdderScript(asset_server.load("add_one.wat")));
+    commands.spawn(AdderScript(asset_server.load("multiply_two.wat")));
+}
+
+// The console already logs its output, so it is taken and dropped here rather than piling up.
+fn forward_terminal_input(input: Res<TerminalInput>, mut console: ResMut<WasmConsole>) {
+    if let Ok(receiver) = input.0.lock() {
+        for command in receiver.try_iter() {
+            console.run(command);
+        }
+    }
+    console.take_output();
+}
@@ -0,0 +1,74 @@
+use bevy::{prelude::*, utils::HashSet, DefaultPlugins};
+use bevy_wasm_scripting::*;
+use wasmer::{imports, Function};
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugin(WasmPlugin)
+        .add_wasm_script_component::<AnnouncerScript>()
+        .add_startup_system(spawn_script_entity)
+        .add_system(start_coroutines)
+        .run();
+}
+
+#[derive(Component)]
+struct AnnouncerScript {
+    handle: Handle<WasmScript>,
+}
+
+fn announce(entity_id: EntityId, step: i32) {
+    println!("{:?} reached step {}", entity_id.to_entity(), step);
+}
+
+impl WasmScriptComponent for AnnouncerScript {
+    type ImportQueriedComponents = ();
+    type ImportResources = ();
+
+    fn get_wasm_script_handle(&self) -> &Handle<WasmScript> {
+        &self.handle
+    }
+
+    fn get_imports_from_world(
+        wasmer_store: &mut WasmerStore,
+        _world: &WorldPointer,
+    ) -> wasmer::Imports {
+        imports! {
+            "env" => {
+                "announce" => Function::new_typed(&mut wasmer_store.0, announce),
+            }
+        }
+    }
+}
+
+fn spawn_script_entity(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn(AnnouncerScript {
+        handle: asset_server.load("wait_and_announce.wat"),
+    });
+}
+
+fn start_coroutines(
+    scripted_entities: Query<(Entity, &AnnouncerScript)>,
+    mut coroutines: ResMut<WasmCoroutines>,
+    mut started: Local<HashSet<Entity>>,
+    mut script_env: WasmScriptComponentEnv<AnnouncerScript>,
+) {
+    for (entity, script) in scripted_entities.iter() {
+        if started.contains(&entity) {
+            continue;
+        }
+        // Fails until the script is instantiated. Once started, it resumes itself every second.
+        if coroutines
+            .start(
+                &mut script_env,
+                entity,
+                &script.handle,
+                "run",
+                (EntityId::from_entity(entity),),
+            )
+            .is_ok()
+        {
+            started.insert(entity);
+        }
+    }
+}
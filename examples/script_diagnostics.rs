@@ -0,0 +1,40 @@
+use bevy::{diagnostic::LogDiagnosticsPlugin, prelude::*, DefaultPlugins};
+use bevy_wasm_scripting::*;
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugin(WasmPlugin)
+        .add_plugin(WasmDiagnosticsPlugin)
+        .add_plugin(LogDiagnosticsPlugin::default())
+        .add_wasm_script_component::<AdderScript>()
+        .add_startup_system(spawn_scripted_entities)
+        .add_system(call_scripts)
+        .run();
+}
+
+#[derive(Component)]
+struct AdderScript(Handle<WasmScript>);
+
+impl WasmScriptComponent for AdderScript {
+    type ImportQueriedComponents = ();
+    type ImportResources = ();
+
+    fn get_wasm_script_handle(&self) -> &Handle<WasmScript> {
+        &self.0
+    }
+}
+
+fn spawn_scripted_entities(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let handle = asset_server.load("add_one.wat");
+    for _ in 0..10 {
+        commands.spawn(AdderScript(handle.clone()));
+    }
+}
+
+// The wasm_script_calls and wasm_script_time diagnostics are logged along with the others.
+fn call_scripts(scripts: Query<&AdderScript>, mut script_env: WasmScriptComponentEnv<AdderScript>) {
+    for script in scripts.iter() {
+        let _ = script_env.call_if_instantiated_1::<i32, i32>(&script.0, "add_one", 1);
+    }
+}
@@ -0,0 +1,62 @@
+use bevy::{prelude::*, DefaultPlugins};
+use bevy_wasm_scripting::*;
+use serde::Deserialize;
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugin(WasmPlugin)
+        // Scripts may now send ScoreEvents, as RON, by the name "score".
+        .add_wasm_script_event::<ScoreEvent>("score")
+        .add_wasm_script_component::<ScoringScript>()
+        .add_startup_system(spawn_script_entity)
+        .add_system(call_script_on_entity)
+        .add_system(read_score_events)
+        .run();
+}
+
+#[derive(Debug, Deserialize)]
+struct ScoreEvent {
+    points: u32,
+}
+
+#[derive(Component)]
+struct ScoringScript {
+    handle: Handle<WasmScript>,
+}
+
+impl WasmScriptComponent for ScoringScript {
+    type ImportQueriedComponents = ();
+    type ImportResources = ();
+
+    fn get_wasm_script_handle(&self) -> &Handle<WasmScript> {
+        &self.handle
+    }
+}
+
+fn spawn_script_entity(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn(ScoringScript {
+        handle: asset_server.load("send_score.wat"),
+    });
+}
+
+fn call_script_on_entity(
+    scripted_entities: Query<&ScoringScript>,
+    mut script_env: WasmScriptComponentEnv<ScoringScript>,
+) {
+    for scripted_entity in scripted_entities.iter() {
+        if let Ok(status) =
+            script_env.call_if_instantiated_0::<i32>(&scripted_entity.handle, "main")
+        {
+            if status != 0 {
+                println!("Script could not send its event: {}", status);
+            }
+        }
+    }
+}
+
+fn read_score_events(mut score_events: EventReader<ScoreEvent>) {
+    for event in score_events.iter() {
+        println!("Scored {} points", event.points);
+    }
+}
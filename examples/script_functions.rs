@@ -0,0 +1,50 @@
+use bevy::{prelude::*, DefaultPlugins};
+use bevy_wasm_scripting::*;
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugin(WasmPlugin)
+        .add_wasm_script_component::<AdderScript>()
+        .add_startup_system(spawn_script_entity)
+        .add_system(call_script_on_entity)
+        .run();
+}
+
+#[derive(Component)]
+struct AdderScript {
+    handle: Handle<WasmScript>,
+    // Resolved on the first call, and again whenever the script is reloaded
+    main: ScriptFunction<i32, i32>,
+    accumulator: i32,
+}
+
+impl WasmScriptComponent for AdderScript {
+    type ImportQueriedComponents = ();
+    type ImportResources = ();
+    fn get_wasm_script_handle(&self) -> &Handle<WasmScript> {
+        &self.handle
+    }
+}
+
+fn spawn_script_entity(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let handle = asset_server.load("add_one.wat");
+    commands.spawn(AdderScript {
+        main: ScriptFunction::new(handle.clone(), "main"),
+        handle,
+        accumulator: 0,
+    });
+}
+
+fn call_script_on_entity(
+    mut scripted_entities: Query<&mut AdderScript>,
+    mut script_env: WasmScriptComponentEnv<AdderScript>,
+) {
+    for mut scripted_entity in scripted_entities.iter_mut() {
+        let accumulator = scripted_entity.accumulator;
+        if let Ok(new_val) = scripted_entity.main.call_1(&mut script_env, accumulator) {
+            scripted_entity.accumulator = new_val;
+        }
+        println!("Accumulated value: {}", scripted_entity.accumulator);
+    }
+}
@@ -0,0 +1,75 @@
+use bevy::{prelude::*, DefaultPlugins};
+use bevy_wasm_scripting::*;
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugin(WasmPlugin)
+        .add_wasm_script_component::<AdderScript>()
+        .add_startup_system(spawn_script_entity)
+        .add_system(call_script_on_entity)
+        .add_system(describe_manifest)
+        .add_system(report_script_errors)
+        .run();
+}
+
+#[derive(Resource)]
+struct AdderManifest(Handle<WasmScriptManifest>);
+
+#[derive(Component)]
+struct AdderScript {
+    handle: Handle<WasmScript>,
+    accumulator: i32,
+}
+
+impl WasmScriptComponent for AdderScript {
+    type ImportQueriedComponents = ();
+    type ImportResources = ();
+    fn get_wasm_script_handle(&self) -> &Handle<WasmScript> {
+        &self.handle
+    }
+}
+
+fn spawn_script_entity(mut commands: Commands, asset_server: Res<AssetServer>) {
+    // Loading the manifest loads the script it references, too.
+    commands.insert_resource(AdderManifest(asset_server.load("add_one.script.ron")));
+    commands.spawn(AdderScript {
+        // This is the same handle as the manifest's `script`.
+        handle: asset_server.load("add_one.wat"),
+        accumulator: 0,
+    });
+}
+
+fn call_script_on_entity(
+    mut scripted_entities: Query<&mut AdderScript>,
+    mut script_env: WasmScriptComponentEnv<AdderScript>,
+) {
+    for mut scripted_entity in scripted_entities.iter_mut() {
+        if let Ok(new_val) = script_env.call_if_instantiated_1(
+            &scripted_entity.handle,
+            "main",
+            scripted_entity.accumulator,
+        ) {
+            scripted_entity.accumulator = new_val;
+        }
+        println!("Accumulated value: {}", scripted_entity.accumulator);
+    }
+}
+
+fn describe_manifest(
+    manifest: Res<AdderManifest>,
+    manifests: Res<Assets<WasmScriptManifest>>,
+    mut described: Local<bool>,
+) {
+    if let (false, Some(manifest)) = (*described, manifests.get(&manifest.0)) {
+        println!("Loaded manifest for {} {}", manifest.name, manifest.version);
+        *described = true;
+    }
+}
+
+// If the script doesn't match its manifest, it won't be instantiated and we'll hear about it here.
+fn report_script_errors(mut script_errors: EventReader<WasmScriptError>) {
+    for error in script_errors.iter() {
+        println!("Script error: {:?}", error);
+    }
+}
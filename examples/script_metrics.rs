@@ -0,0 +1,59 @@
+use bevy::{prelude::*, time::common_conditions::on_timer, DefaultPlugins};
+use bevy_wasm_scripting::*;
+use std::time::Duration;
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugin(WasmPlugin)
+        .add_wasm_script_resource::<ProfiledScripts>()
+        .add_startup_system(add_script_resource)
+        .add_system(call_scripts)
+        .add_system(print_metrics.run_if(on_timer(Duration::from_secs(1))))
+        .run();
+}
+
+#[derive(Resource)]
+struct ProfiledScripts {
+    add_one: Handle<WasmScript>,
+    multiply_two: Handle<WasmScript>,
+}
+
+impl WasmScriptResource for ProfiledScripts {
+    type ImportQueriedComponents = ();
+    type ImportResources = ();
+
+    fn get_handle(&self) -> Option<&Handle<WasmScript>> {
+        Some(&self.add_one)
+    }
+}
+
+fn add_script_resource(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(ProfiledScripts {
+        add_one: asset_server.load("add_one.wat"),
+        multiply_two: asset_server.load("multiply_two.wat"),
+    });
+}
+
+fn call_scripts(scripts: Res<ProfiledScripts>, mut script_env: WasmScriptEnv) {
+    for value in 0..100 {
+        let _ = script_env.call_if_instantiated_1::<i32, i32>(&scripts.add_one, "add_one", value);
+    }
+    let _ = script_env.call_if_instantiated_1::<i32, i32>(&scripts.multiply_two, "multiply_two", 2);
+}
+
+// Prints the time spent in each script over the last second, most expensive first.
+fn print_metrics(metrics: Res<WasmScriptMetrics>) {
+    for (script, metrics) in metrics.by_total_time() {
+        println!(
+            "{}: {} calls, {:?} total, {:?} average, {:?} max, {} fuel",
+            script,
+            metrics.calls,
+            metrics.total,
+            metrics.average(),
+            metrics.max,
+            metrics.fuel
+        );
+    }
+    metrics.reset();
+}
@@ -0,0 +1,50 @@
+use bevy::{prelude::*, DefaultPlugins};
+use bevy_wasm_scripting::*;
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugin(WasmPlugin)
+        // Scripts may query Health by the name "health", as a little-endian i32.
+        .add_wasm_query_component("health", |health: &Health| health.0.to_le_bytes())
+        .add_wasm_script_resource::<HealthTotalScript>()
+        .add_startup_system(setup)
+        .add_system(call_script)
+        .run();
+}
+
+#[derive(Component)]
+struct Health(i32);
+
+#[derive(Resource)]
+struct HealthTotalScript {
+    handle: Handle<WasmScript>,
+}
+
+impl WasmScriptResource for HealthTotalScript {
+    // Scripts may only query what is listed here.
+    type ImportQueriedComponents = &'static Health;
+    type ImportResources = ();
+
+    fn get_handle(&self) -> Option<&Handle<WasmScript>> {
+        Some(&self.handle)
+    }
+}
+
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    for health in [10, 20, 30] {
+        commands.spawn(Health(health));
+    }
+    commands.insert_resource(HealthTotalScript {
+        handle: asset_server.load("sum_health.wat"),
+    });
+}
+
+fn call_script(
+    script: Res<HealthTotalScript>,
+    mut script_env: WasmScriptResourceEnv<HealthTotalScript>,
+) {
+    if let Ok(total) = script_env.call_if_instantiated_0::<i32>(&script.handle, "main") {
+        println!("Total health: {}", total);
+    }
+}
@@ -0,0 +1,76 @@
+use bevy::{prelude::*, DefaultPlugins};
+use bevy_wasm_scripting::*;
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugin(WasmPlugin)
+        .add_wasm_script_resource::<CounterScript>()
+        .init_resource::<Frame>()
+        .add_startup_system(add_script_resource)
+        .add_system(advance_frame)
+        .add_system(roll_back.before(advance_frame))
+        .run();
+}
+
+#[derive(Resource)]
+struct CounterScript {
+    handle: Handle<WasmScript>,
+}
+
+impl WasmScriptResource for CounterScript {
+    type ImportQueriedComponents = ();
+    type ImportResources = ();
+
+    fn get_handle(&self) -> Option<&Handle<WasmScript>> {
+        Some(&self.handle)
+    }
+}
+
+/** The frame being simulated, as a rollback library would track it. */
+#[derive(Resource, Default)]
+struct Frame(i32);
+
+fn add_script_resource(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(CounterScript {
+        handle: asset_server.load("counter.wat"),
+    });
+}
+
+// Each frame is saved before it is simulated, so that it can be re-simulated after a rollback.
+fn advance_frame(
+    script: Res<CounterScript>,
+    mut frame: ResMut<Frame>,
+    mut rollback: ResMut<WasmRollback>,
+    mut script_env: WasmScriptEnv,
+    host: ScriptHostState,
+) {
+    if let Err(err) = rollback.save_frame(frame.0, &mut script_env, &host) {
+        println!("Could not save frame {}: {}", frame.0, err);
+        return;
+    }
+    if let Ok(count) = script_env.call_if_instantiated_0::<i32>(&script.handle, "increment") {
+        println!("Frame {}: count {}", frame.0, count);
+        frame.0 += 1;
+    }
+}
+
+// Press R to roll back to the oldest saved frame.
+fn roll_back(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut frame: ResMut<Frame>,
+    rollback: Res<WasmRollback>,
+    mut script_env: WasmScriptEnv,
+    host: ScriptHostState,
+) {
+    if !keyboard_input.just_pressed(KeyCode::R) {
+        return;
+    }
+    let Some(oldest) = rollback.frames().next() else {
+        return;
+    };
+    match rollback.load_frame(oldest, &mut script_env, &host) {
+        Ok(()) => frame.0 = oldest,
+        Err(err) => println!("Could not roll back: {}", err),
+    }
+}
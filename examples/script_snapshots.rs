@@ -0,0 +1,71 @@
+use bevy::{prelude::*, DefaultPlugins};
+use bevy_wasm_scripting::*;
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugin(WasmPlugin)
+        .add_wasm_script_resource::<CounterScript>()
+        .init_resource::<SavedGame>()
+        .add_startup_system(add_script_resource)
+        .add_system(count_up)
+        .add_system(save_and_load)
+        .run();
+}
+
+#[derive(Resource)]
+struct CounterScript {
+    handle: Handle<WasmScript>,
+}
+
+impl WasmScriptResource for CounterScript {
+    type ImportQueriedComponents = ();
+    type ImportResources = ();
+
+    fn get_handle(&self) -> Option<&Handle<WasmScript>> {
+        Some(&self.handle)
+    }
+}
+
+/** A stand-in for a save file. */
+#[derive(Resource, Default)]
+struct SavedGame(Option<String>);
+
+fn add_script_resource(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(CounterScript {
+        handle: asset_server.load("counter.wat"),
+    });
+}
+
+fn count_up(script: Res<CounterScript>, mut script_env: WasmScriptResourceEnv<CounterScript>) {
+    if let Ok(count) = script_env.call_if_instantiated_0::<i32>(&script.handle, "increment") {
+        println!("Count: {}", count);
+    }
+}
+
+// Press S to save the script's state, and L to load it again.
+fn save_and_load(
+    keyboard_input: Res<Input<KeyCode>>,
+    script: Res<CounterScript>,
+    mut saved_game: ResMut<SavedGame>,
+    mut script_env: WasmScriptResourceEnv<CounterScript>,
+) {
+    if keyboard_input.just_pressed(KeyCode::S) {
+        match ScriptSnapshot::take(&mut script_env, &script.handle) {
+            Ok(snapshot) => saved_game.0 = ron::to_string(&snapshot).ok(),
+            Err(err) => println!("Could not save: {}", err),
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::L) {
+        let Some(snapshot) = saved_game
+            .0
+            .as_ref()
+            .and_then(|saved| ron::from_str::<ScriptSnapshot>(saved).ok())
+        else {
+            return;
+        };
+        if let Err(err) = snapshot.restore(&mut script_env, &script.handle) {
+            println!("Could not load: {}", err);
+        }
+    }
+}
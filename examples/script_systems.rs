@@ -0,0 +1,57 @@
+use bevy::{prelude::*, DefaultPlugins};
+use bevy_wasm_scripting::*;
+use wasmer::{imports, Function};
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins.set(AssetPlugin {
+            watch_for_changes: true,
+            ..Default::default()
+        }))
+        .add_plugin(WasmPlugin)
+        .insert_resource(FixedTime::new_from_secs(0.5))
+        // Script systems in the "fixed" phase run every FixedUpdate.
+        .add_system(wasm_system_phase("fixed").in_schedule(CoreSchedule::FixedUpdate))
+        .add_wasm_script_component::<SystemsScript>()
+        .add_startup_system(spawn_script_entity)
+        .run();
+}
+
+#[derive(Component)]
+struct SystemsScript {
+    handle: Handle<WasmScript>,
+}
+
+impl WasmScriptComponent for SystemsScript {
+    type ImportQueriedComponents = ();
+    type ImportResources = ();
+
+    fn get_wasm_script_handle(&self) -> &Handle<WasmScript> {
+        &self.handle
+    }
+
+    fn get_imports_from_world(
+        wasmer_store: &mut WasmerStore,
+        _world: &WorldPointer,
+    ) -> wasmer::Imports {
+        imports! {
+            "env" => {
+                "log_count" => Function::new_typed(&mut wasmer_store.0, log_count),
+            }
+        }
+    }
+}
+
+fn log_count(system: i32, count: i32) {
+    match system {
+        0 => println!("on_frame, after {} frames", count),
+        _ => println!("tick {}", count),
+    }
+}
+
+// The script registers its own systems, once it has been instantiated. Try editing it while running!
+fn spawn_script_entity(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn(SystemsScript {
+        handle: asset_server.load("script_systems.wat"),
+    });
+}
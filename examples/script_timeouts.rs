@@ -0,0 +1,56 @@
+use std::time::Duration;
+
+use bevy::{prelude::*, DefaultPlugins};
+use bevy_wasm_scripting::*;
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugin(WasmPlugin)
+        // Give each script call 5ms before it is interrupted.
+        .insert_resource(WasmWatchdog::with_timeout(Duration::from_millis(5)))
+        .add_wasm_script_component::<SpinningScript>()
+        .add_startup_system(spawn_script_entity)
+        .add_system(call_script_on_entity)
+        .run();
+}
+
+#[derive(Component)]
+struct SpinningScript {
+    handle: Handle<WasmScript>,
+}
+
+impl WasmScriptComponent for SpinningScript {
+    type ImportQueriedComponents = ();
+    type ImportResources = ();
+
+    fn get_wasm_script_handle(&self) -> &Handle<WasmScript> {
+        &self.handle
+    }
+}
+
+fn spawn_script_entity(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn(SpinningScript {
+        handle: asset_server.load("spin_forever.wat"),
+    });
+}
+
+fn call_script_on_entity(
+    scripted_entities: Query<&SpinningScript>,
+    mut script_env: WasmScriptComponentEnv<SpinningScript>,
+) {
+    for scripted_entity in scripted_entities.iter() {
+        match script_env.call_if_instantiated_1::<i32, i32>(&scripted_entity.handle, "main", 1000) {
+            Ok(count) => println!("Counted to {}", count),
+            Err(err) => println!("{}", err),
+        }
+        // Never finishes on its own.
+        match script_env.call_if_instantiated_1::<i32, i32>(&scripted_entity.handle, "main", -1) {
+            Ok(count) => println!("Counted to {}", count),
+            Err(err) => match err.downcast_ref::<ScriptTimeout>() {
+                Some(timeout) => println!("Stopped after {:?}", timeout.timeout),
+                None => println!("{}", err),
+            },
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use bevy::{prelude::*, utils::HashSet, DefaultPlugins};
+use bevy_wasm_scripting::*;
+use wasmer::{imports, Function};
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugin(WasmPlugin)
+        // Keep ticking into the new script when tick_then_stop.wat is edited.
+        .insert_resource(WasmScriptTimers::with_reload_policy(
+            TimerReloadPolicy::Keep,
+        ))
+        .add_wasm_script_component::<TickingScript>()
+        .add_startup_system(spawn_script_entity)
+        .add_system(start_scripts)
+        .run();
+}
+
+#[derive(Component)]
+struct TickingScript {
+    handle: Handle<WasmScript>,
+}
+
+fn announce(entity_id: EntityId, callback_id: i32) {
+    match callback_id {
+        1 => println!("{:?} ticked", entity_id.to_entity()),
+        _ => println!("{:?} stopped ticking", entity_id.to_entity()),
+    }
+}
+
+impl WasmScriptComponent for TickingScript {
+    type ImportQueriedComponents = ();
+    type ImportResources = ();
+
+    fn get_wasm_script_handle(&self) -> &Handle<WasmScript> {
+        &self.handle
+    }
+
+    fn get_imports_from_world(
+        wasmer_store: &mut WasmerStore,
+        _world: &WorldPointer,
+    ) -> wasmer::Imports {
+        imports! {
+            "env" => {
+                "announce" => Function::new_typed(&mut wasmer_store.0, announce),
+            }
+        }
+    }
+}
+
+fn spawn_script_entity(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn(TickingScript {
+        handle: asset_server.load("tick_then_stop.wat"),
+    });
+}
+
+fn start_scripts(
+    scripted_entities: Query<(Entity, &TickingScript)>,
+    mut started: Local<HashSet<Entity>>,
+    mut script_env: WasmScriptComponentEnv<TickingScript>,
+) {
+    for (entity, script) in scripted_entities.iter() {
+        if started.contains(&entity) {
+            continue;
+        }
+        // Fails until the script is instantiated. From then on, the script's timers call it.
+        if script_env
+            .call_if_instantiated_1::<EntityId, ()>(
+                &script.handle,
+                "start",
+                EntityId::from_entity(entity),
+            )
+            .is_ok()
+        {
+            started.insert(entity);
+        }
+    }
+}
@@ -2,13 +2,17 @@ use std::fmt::Debug;
 
 use bevy::{
     asset::{AssetLoader, LoadContext, LoadedAsset},
-    prelude::{AssetEvent, Assets, EventReader, Res, ResMut},
+    prelude::{AssetEvent, Assets, EventReader, EventWriter, Handle, Res, ResMut, World},
     reflect::TypeUuid,
-    utils::BoxedFuture,
+    utils::{BoxedFuture, HashSet},
 };
 use wasmer::{wat2wasm, Imports, Instance, Module};
 
-use crate::WasmerStore;
+use crate::{
+    functions::bump_generation, script_systems::schedule_registration,
+    snapshots::restore_pending_snapshot, WasmDeterminism, WasmScriptError, WasmScriptManifest,
+    WasmerStore,
+};
 
 /**
 A WasmScript assets represented a single, eventually-instantiated WASM script. All WasmScript assets
@@ -22,7 +26,12 @@ add a component of that type, with the asset handle associated as per `get_wasm_
 * For resources with one associated script, implement WasmScriptResource and register that resource
 with `add_wasm_script_resource`.
 * For other resource-based scripts, add a system to your app, using `instantiate_resource_script`.
-* Call `instantiate_if_compiled` on the WasmScript directly. This will not work with hot reloading.
+* Call `instantiate_if_compiled` on the WasmScript directly. This will not work with hot reloading,
+  does not check the script's manifests, and the script can only be called through `WasmScriptEnv`.
+
+Scripts may also be described by a `WasmScriptManifest`, loaded from a `.script.ron` file. Scripts
+are checked against their manifests after compilation, or when a manifest loads after its script, and
+are not instantiated if they do not match.
 
 Hot reloading is enabled for component and resource-based scripts, though there will be 1-2 frames in
 which the asset is in the `Loaded` or `Compiled` state and will not run. `call_if_instantiated` will
@@ -42,12 +51,38 @@ unsafe impl Send for WasmScript {}
 unsafe impl Sync for WasmScript {}
 
 impl WasmScript {
+    /**
+    A script from its `.wasm` bytes, such as one generated at runtime or received over the network.
+    Once added to the `Assets<WasmScript>`, it is compiled and instantiated like a loaded asset:
+    ```ignore
+    let handle = scripts.add(WasmScript::from_bytes("generated", wasm));
+    commands.spawn(MyScript(handle));
+    ```
+    */
+    pub fn from_bytes(name: &str, wasm: impl Into<Vec<u8>>) -> Self {
+        WasmScript::Loaded(name.to_string(), wasm.into())
+    }
+
+    /** A script from its `.wat` source, such as one embedded with `include_str!`. */
+    pub fn from_wat_str(name: &str, wat: &str) -> Result<Self, anyhow::Error> {
+        Ok(Self::from_bytes(name, wat2wasm(wat.as_bytes())?))
+    }
+
+    /**
+    Instantiate the script directly into the `WasmerStore`, with the given imports. Scripts
+    instantiated this way can only be called through `WasmScriptEnv`, as described by
+    `WasmScriptStores`. Script types registered with `add_wasm_script_component` or
+    `add_wasm_script_resource` instantiate their scripts themselves.
+    */
     pub fn instantiate_if_compiled(
         &mut self,
         wasmer_store: &mut WasmerStore,
         imports: &Imports,
     ) -> bool {
         if let WasmScript::Compiled(module) = self {
+            let _span =
+                bevy::log::info_span!("wasm_instantiate", script = module.name().unwrap_or(""))
+                    .entered();
             if let Ok(instance) = Instance::new(&mut wasmer_store.0, module, imports) {
                 *self = WasmScript::Instantiated(module.name().unwrap_or("").to_string(), instance);
                 true
@@ -67,6 +102,26 @@ impl WasmScript {
     }
 }
 
+/**
+Mark a compiled script as modified, so the script types holding it retry instantiating it. Used when a
+script which was held back, such as by a mismatched manifest, may now be instantiated.
+*/
+pub(crate) fn retry_instantiation(scripts: &mut Assets<WasmScript>, handle: &Handle<WasmScript>) {
+    if let Some(WasmScript::Compiled(module)) = scripts.get(handle) {
+        let module = module.clone();
+        let _ = scripts.set(handle.clone(), WasmScript::Compiled(module));
+    }
+}
+
+/**
+Called whenever a script asset is instantiated, or re-instantiated after hot reloading.
+*/
+pub(crate) fn script_instantiated(world: &mut World, handle: Handle<WasmScript>) {
+    bump_generation(world, &handle);
+    restore_pending_snapshot(world, &handle);
+    schedule_registration(world, handle);
+}
+
 pub struct WasmAssetLoader;
 
 impl AssetLoader for WasmAssetLoader {
@@ -118,23 +173,117 @@ fn get_module_name(load_context: &mut LoadContext) -> String {
     name
 }
 
+/**
+Check a module against every loaded manifest which describes it, logging and sending each mismatch.
+*/
+fn check_manifests(
+    name: &str,
+    handle: &Handle<WasmScript>,
+    module: &Module,
+    manifests: &Assets<WasmScriptManifest>,
+    script_errors: &mut EventWriter<WasmScriptError>,
+) -> bool {
+    let mut valid = true;
+    for (_, manifest) in manifests
+        .iter()
+        .filter(|(_, manifest)| manifest.script == *handle)
+    {
+        let mismatches = manifest.validate(module);
+        if !mismatches.is_empty() {
+            valid = false;
+            for mismatch in mismatches.iter() {
+                bevy::log::error!(
+                    "{} does not match manifest {}: {}",
+                    name,
+                    manifest.name,
+                    mismatch
+                );
+            }
+            script_errors.send(WasmScriptError::ManifestMismatch {
+                script: handle.clone(),
+                manifest: manifest.name.clone(),
+                mismatches,
+            });
+        }
+    }
+    valid
+}
+
 pub(crate) fn compile_wasm_scripts(
     mut ev_asset_loaded: EventReader<AssetEvent<WasmScript>>,
+    mut ev_manifest_loaded: EventReader<AssetEvent<WasmScriptManifest>>,
     mut wasm_assets: ResMut<Assets<WasmScript>>,
+    manifests: Res<Assets<WasmScriptManifest>>,
+    determinism: Res<WasmDeterminism>,
     wasm_store: Res<WasmerStore>,
+    mut script_errors: EventWriter<WasmScriptError>,
 ) {
+    let mut to_compile = HashSet::new();
     for asset in ev_asset_loaded.iter() {
         if let AssetEvent::Created { handle } | AssetEvent::Modified { handle } = asset {
-            if let Some(WasmScript::Loaded(name, wasm_script)) = wasm_assets.get(&handle) {
-                match Module::new(&wasm_store.0, wasm_script) {
-                    Ok(mut module) => {
-                        module.set_name(name);
-                        wasm_assets.set_untracked(handle, WasmScript::Compiled(module));
+            to_compile.insert(handle.clone());
+        }
+    }
+    // A script which failed validation is left loaded, so a fixed manifest can retry it.
+    let mut to_validate = HashSet::new();
+    for manifest in ev_manifest_loaded.iter() {
+        if let AssetEvent::Created { handle } | AssetEvent::Modified { handle } = manifest {
+            if let Some(manifest) = manifests.get(handle) {
+                to_compile.insert(manifest.script.clone());
+                to_validate.insert(manifest.script.clone());
+            }
+        }
+    }
+    // Scripts may finish compiling before their manifest loads. Those are checked as soon as it
+    // does, and an instantiated script which does not match is dropped back to its compiled module.
+    // Instantiation refuses compiled modules which do not match, so a compiled module which matches
+    // again is marked modified for its script types to instantiate it.
+    for handle in to_validate {
+        let (name, module, instantiated) = match wasm_assets.get(&handle) {
+            Some(WasmScript::Compiled(module)) => (module.name().unwrap_or(""), module, false),
+            Some(WasmScript::Instantiated(name, instance)) => {
+                (name.as_str(), instance.module(), true)
+            }
+            _ => continue,
+        };
+        let valid = check_manifests(name, &handle, module, &manifests, &mut script_errors);
+        if !valid && instantiated {
+            let module = module.clone();
+            wasm_assets.set_untracked(handle, WasmScript::Compiled(module));
+        } else if valid && !instantiated {
+            retry_instantiation(&mut wasm_assets, &handle);
+        }
+    }
+    for handle in to_compile {
+        if let Some(WasmScript::Loaded(name, wasm_script)) = wasm_assets.get(&handle) {
+            let name = name.clone();
+            let compiled = {
+                let _span = bevy::log::info_span!("wasm_compile", script = name.as_str()).entered();
+                Module::new(&wasm_store.0, wasm_script)
+            };
+            match compiled {
+                Ok(mut module) => {
+                    module.set_name(&name);
+                    let mut valid =
+                        check_manifests(&name, &handle, &module, &manifests, &mut script_errors);
+                    let problems = determinism.validate(&module);
+                    if !problems.is_empty() {
+                        valid = false;
+                        for problem in problems.iter() {
+                            bevy::log::error!("{} is not deterministic: {}", name, problem);
+                        }
+                        script_errors.send(WasmScriptError::NonDeterministic {
+                            script: handle.clone(),
+                            problems,
+                        });
                     }
-                    Err(err) => {
-                        bevy::log::warn!("Could not compile {}: {}", name, err);
+                    if valid {
+                        wasm_assets.set_untracked(handle, WasmScript::Compiled(module));
                     }
                 }
+                Err(err) => {
+                    bevy::log::warn!("Could not compile {}: {}", name, err);
+                }
             }
         }
     }
@@ -1,3 +1,5 @@
+use std::any::TypeId;
+
 use bevy::{
     ecs::{
         query::ReadOnlyWorldQuery,
@@ -5,9 +7,21 @@ use bevy::{
     },
     prelude::*,
 };
-use wasmer::{FromToNativeWasmType, NativeWasmTypeInto, WasmTypeList};
+use wasmer::{FromToNativeWasmType, NativeWasmTypeInto, Value, WasmTypeList};
 
-use crate::{resources::WasmScriptResource, WasmScript, WasmScriptComponent, WasmerStore};
+use crate::{
+    commands::ScriptCommandQueue,
+    functions::{ScriptCallContext, ScriptGenerations},
+    metrics::WasmScriptMetrics,
+    queries::CallerQueryAccess,
+    recording::{
+        begin_call, finish_call, finish_value_call, record_args, record_value_args,
+        script_recorder, WasmCallRecorder,
+    },
+    resources::WasmScriptResource,
+    stores::{exported_function, instantiated, ScriptStoreAccess, WasmScriptStores},
+    WasmDeterminism, WasmScript, WasmScriptComponent, WasmWatchdog, WasmerStore,
+};
 
 /**
 The `WasmScriptComponentEnv` is the primary entry point for running scripts associated with components
@@ -18,6 +32,8 @@ It is a `SystemParam` with two generic types:
 * Second, an optional `ReadOnlyWorldQuery` which should consist of a tuple of `Without` query
 elements. This should be filled with any components which are referenced by the system directly.
 
+Systems using it run in parallel with other script systems, as described by `WasmScriptStores`.
+
 Within a system, the `call_if_instantiated` method can be used to execute an exported function.
 */
 #[derive(SystemParam)]
@@ -27,10 +43,18 @@ pub struct WasmScriptComponentEnv<
     WS: WasmScriptComponent,
     Without: ReadOnlyWorldQuery + 'static = (),
 > {
-    wasmer_store: ResMut<'w, WasmerStore>,
+    stores: Res<'w, WasmScriptStores>,
+    watchdog: Res<'w, WasmWatchdog>,
+    determinism: Option<Res<'w, WasmDeterminism>>,
+    generations: Res<'w, ScriptGenerations>,
+    metrics: Res<'w, WasmScriptMetrics>,
+    recorder: Option<Res<'w, WasmCallRecorder>>,
     assets: Res<'w, Assets<WasmScript>>,
     _used_components_query:
         Query<'w, 's, <WS as WasmScriptComponent>::ImportQueriedComponents, Without>,
+    // Written by the script's imports, through `WorldPointer::commands`.
+    _commands: Option<ResMut<'w, ScriptCommandQueue<WS>>>,
+    entities: Query<'w, 's, Entity, Without>,
     pub resources: StaticSystemParam<'w, 's, <WS as WasmScriptComponent>::ImportResources>,
 }
 
@@ -51,10 +75,18 @@ pub struct WasmScriptResourceEnv<
     WS: WasmScriptResource,
     Without: ReadOnlyWorldQuery + 'static = (),
 > {
-    wasmer_store: ResMut<'w, WasmerStore>,
+    stores: Res<'w, WasmScriptStores>,
+    watchdog: Res<'w, WasmWatchdog>,
+    determinism: Option<Res<'w, WasmDeterminism>>,
+    generations: Res<'w, ScriptGenerations>,
+    metrics: Res<'w, WasmScriptMetrics>,
+    recorder: Option<Res<'w, WasmCallRecorder>>,
     assets: Res<'w, Assets<WasmScript>>,
     _used_components_query:
         Query<'w, 's, <WS as WasmScriptResource>::ImportQueriedComponents, Without>,
+    // Written by the script's imports, through `WorldPointer::commands`.
+    _commands: Option<ResMut<'w, ScriptCommandQueue<WS>>>,
+    entities: Query<'w, 's, Entity, Without>,
     pub resources: StaticSystemParam<'w, 's, <WS as WasmScriptResource>::ImportResources>,
 }
 
@@ -66,15 +98,58 @@ in one system, resource-based scripts).
 SAFETY:
 Unlike `WasmScriptComponentEnv`, there are no automatic references to any components or references.
 Instead, any associated components or references should be added a system parameters manually.
+Scripts whose imports touch the world should only be called through it from exclusive systems, with
+a `SystemState`, as the systems of this crate do.
+
+It can also call scripts instantiated directly into the `WasmerStore`, so systems using it do not run
+in parallel with each other. See `WasmScriptStores`. Since it holds no component access, scripts it
+calls may not use the `query` imports. See `ScriptQueryRegistry`.
 
 Within a system, the `call_if_instantiated` method can be used to execute an exported function.
 */
 #[derive(SystemParam)]
 pub struct WasmScriptEnv<'w, 's> {
     wasmer_store: ResMut<'w, WasmerStore>,
+    stores: Res<'w, WasmScriptStores>,
+    watchdog: Res<'w, WasmWatchdog>,
+    determinism: Option<Res<'w, WasmDeterminism>>,
+    generations: Res<'w, ScriptGenerations>,
+    metrics: Res<'w, WasmScriptMetrics>,
+    recorder: Option<Res<'w, WasmCallRecorder>>,
     assets: StaticSystemParam<'w, 's, Res<'static, Assets<WasmScript>>>,
 }
 
+/** The results of a batch call, each alongside the key it was called for. */
+pub type BatchResults<Key, Rets> = Vec<(Key, Result<Rets, anyhow::Error>)>;
+
+/**
+Arguments for a script function whose signature is only known at runtime, such as an event handler.
+Implemented for tuples of up to five values which convert into a wasm `Value`, like `EntityId`, `i32`
+or `f32`.
+*/
+pub trait IntoScriptArgs {
+    fn into_script_args(self) -> Vec<Value>;
+}
+
+macro_rules! impl_into_script_args {
+    ($( $x:ident ),*) => {
+        impl<$($x: Into<Value>,)*> IntoScriptArgs for ($($x,)*) {
+            #[allow(non_snake_case, clippy::unused_unit)]
+            fn into_script_args(self) -> Vec<Value> {
+                let ($($x,)*) = self;
+                vec![$($x.into(),)*]
+            }
+        }
+    };
+}
+
+impl_into_script_args!();
+impl_into_script_args!(S0);
+impl_into_script_args!(S0, S1);
+impl_into_script_args!(S0, S1, S2);
+impl_into_script_args!(S0, S1, S2, S3);
+impl_into_script_args!(S0, S1, S2, S3, S4);
+
 pub trait GeneralWasmScriptEnv {
     /**
     This will call the associated script's named function, with the provided arguments.
@@ -82,7 +157,8 @@ pub trait GeneralWasmScriptEnv {
     If the associated script is not loaded or not fully instantiated, an error will be
     returned.
 
-    Errors from the executed script function may also be returned.
+    Errors from the executed script function may also be returned. If the call runs longer than
+    the `WasmWatchdog` timeout, it is interrupted and a `ScriptTimeout` error is returned.
     */
     fn call_if_instantiated_0<Rets: WasmTypeList>(
         &mut self,
@@ -171,6 +247,111 @@ pub trait GeneralWasmScriptEnv {
         S2::Native: NativeWasmTypeInto + FromToNativeWasmType,
         S3::Native: NativeWasmTypeInto + FromToNativeWasmType,
         S4::Native: NativeWasmTypeInto + FromToNativeWasmType;
+    /**
+    Call the associated script's named function once for each item of `calls`, such as once per
+    entity. The export is looked up and type-checked, and the recorder, metrics and watchdog are set
+    up, only once, so this is much cheaper than calling `call_if_instantiated_N` in a loop. Each
+    result is returned alongside its key.
+
+    If the script is not instantiated, or the function is not exported with the right signature, an
+    error is returned instead. Each call is separately guarded by the `WasmWatchdog`, and counted by
+    the `WasmScriptMetrics`.
+    */
+    fn call_batch_if_instantiated_0<Key, Rets: WasmTypeList>(
+        &mut self,
+        handle: &Handle<WasmScript>,
+        function_name: &str,
+        calls: impl IntoIterator<Item = (Key, ())>,
+    ) -> Result<BatchResults<Key, Rets>, anyhow::Error>;
+    fn call_batch_if_instantiated_1<Key, S0: FromToNativeWasmType, Rets: WasmTypeList>(
+        &mut self,
+        handle: &Handle<WasmScript>,
+        function_name: &str,
+        calls: impl IntoIterator<Item = (Key, (S0,))>,
+    ) -> Result<BatchResults<Key, Rets>, anyhow::Error>
+    where
+        S0::Native: NativeWasmTypeInto + FromToNativeWasmType;
+    fn call_batch_if_instantiated_2<
+        Key,
+        S0: FromToNativeWasmType,
+        S1: FromToNativeWasmType,
+        Rets: WasmTypeList,
+    >(
+        &mut self,
+        handle: &Handle<WasmScript>,
+        function_name: &str,
+        calls: impl IntoIterator<Item = (Key, (S0, S1))>,
+    ) -> Result<BatchResults<Key, Rets>, anyhow::Error>
+    where
+        S0::Native: NativeWasmTypeInto + FromToNativeWasmType,
+        S1::Native: NativeWasmTypeInto + FromToNativeWasmType;
+    fn call_batch_if_instantiated_3<
+        Key,
+        S0: FromToNativeWasmType,
+        S1: FromToNativeWasmType,
+        S2: FromToNativeWasmType,
+        Rets: WasmTypeList,
+    >(
+        &mut self,
+        handle: &Handle<WasmScript>,
+        function_name: &str,
+        calls: impl IntoIterator<Item = (Key, (S0, S1, S2))>,
+    ) -> Result<BatchResults<Key, Rets>, anyhow::Error>
+    where
+        S0::Native: NativeWasmTypeInto + FromToNativeWasmType,
+        S1::Native: NativeWasmTypeInto + FromToNativeWasmType,
+        S2::Native: NativeWasmTypeInto + FromToNativeWasmType;
+    fn call_batch_if_instantiated_4<
+        Key,
+        S0: FromToNativeWasmType,
+        S1: FromToNativeWasmType,
+        S2: FromToNativeWasmType,
+        S3: FromToNativeWasmType,
+        Rets: WasmTypeList,
+    >(
+        &mut self,
+        handle: &Handle<WasmScript>,
+        function_name: &str,
+        calls: impl IntoIterator<Item = (Key, (S0, S1, S2, S3))>,
+    ) -> Result<BatchResults<Key, Rets>, anyhow::Error>
+    where
+        S0::Native: NativeWasmTypeInto + FromToNativeWasmType,
+        S1::Native: NativeWasmTypeInto + FromToNativeWasmType,
+        S2::Native: NativeWasmTypeInto + FromToNativeWasmType,
+        S3::Native: NativeWasmTypeInto + FromToNativeWasmType;
+    fn call_batch_if_instantiated_5<
+        Key,
+        S0: FromToNativeWasmType,
+        S1: FromToNativeWasmType,
+        S2: FromToNativeWasmType,
+        S3: FromToNativeWasmType,
+        S4: FromToNativeWasmType,
+        Rets: WasmTypeList,
+    >(
+        &mut self,
+        handle: &Handle<WasmScript>,
+        function_name: &str,
+        calls: impl IntoIterator<Item = (Key, (S0, S1, S2, S3, S4))>,
+    ) -> Result<BatchResults<Key, Rets>, anyhow::Error>
+    where
+        S0::Native: NativeWasmTypeInto + FromToNativeWasmType,
+        S1::Native: NativeWasmTypeInto + FromToNativeWasmType,
+        S2::Native: NativeWasmTypeInto + FromToNativeWasmType,
+        S3::Native: NativeWasmTypeInto + FromToNativeWasmType,
+        S4::Native: NativeWasmTypeInto + FromToNativeWasmType;
+    /** Used by `ScriptFunction` to make calls through this environment. */
+    #[doc(hidden)]
+    fn call_context(&mut self) -> ScriptCallContext<'_>;
+    /**
+    Like the `call_if_instantiated_N` methods, but with arguments and results as `Value`s, for when
+    the signature is only known at runtime.
+    */
+    fn call_if_instantiated_with_values(
+        &mut self,
+        handle: &Handle<WasmScript>,
+        function_name: &str,
+        args: &[Value],
+    ) -> Result<Box<[Value]>, anyhow::Error>;
 }
 
 macro_rules! impl_calls {
@@ -182,29 +363,141 @@ macro_rules! impl_calls {
             function_name: &str,
             $( $x: $x, )*
         ) -> Result<Rets, anyhow::Error> where $($x::Native: FromToNativeWasmType + NativeWasmTypeInto,)* {
-            self.assets
-                .get(handle)
-                .ok_or(anyhow::Error::msg("Asset not loaded"))
-                .and_then(|script| {
-                    if let WasmScript::Instantiated(_, instance) = script {
-                        match instance
-                                .exports
-                                .get_function(function_name)
-                                .map_err(anyhow::Error::new)
-                                .and_then(|export| export.typed::<( $(<$x as FromToNativeWasmType>::Native),* ), Rets>(&mut self.wasmer_store.0).map_err(anyhow::Error::new)) {
-                            Ok(exported) => exported
-                                .call(&mut self.wasmer_store.0, $($x.to_native(),)*)
-                                .map_err(anyhow::Error::new),
-                            Err(err) => Err(anyhow::Error::msg(format!(
-                                "{} is not exported correctly: {}",
-                                function_name,
-                                err
-                            )))
-                        }
-                    } else {
-                        Err(anyhow::Error::msg("Script not instantiated yet."))
-                    }
+            let mut context = self.call_context();
+            let instance = instantiated(context.assets, handle)?;
+            let mut store = context.stores.lock(handle)?;
+            let exported = exported_function(instance, &store, function_name)
+                .and_then(|export| export.typed::<( $(<$x as FromToNativeWasmType>::Native),* ), Rets>(&*store).map_err(anyhow::Error::new))
+                .map_err(|err| anyhow::Error::msg(format!(
+                    "{} is not exported correctly: {}",
+                    function_name,
+                    err
+                )))?;
+            let recording = begin_call(context.recorder, context.assets, handle, function_name, &mut store, instance);
+            let ($($x),*) = record_args(&mut store, &recording, ($($x.to_native()),*));
+            let result = context.metrics.measure(&mut store, instance, function_name, |store| {
+                context.watchdog.guard(
+                    context.determinism,
+                    store,
+                    instance,
+                    function_name,
+                    |store| exported.call(store, $($x,)*),
+                )
+            });
+            finish_call(&mut store, recording, result)
+        }
+    };
+}
+
+macro_rules! impl_batch_calls {
+    ($call_name:ident $( $x:ident ),* ) => {
+        #[allow(non_snake_case, unused_parens)]
+        fn $call_name<Key, $($x: FromToNativeWasmType,)* Rets: WasmTypeList>(
+            &mut self,
+            handle: &Handle<WasmScript>,
+            function_name: &str,
+            calls: impl IntoIterator<Item = (Key, ($($x,)*))>,
+        ) -> Result<BatchResults<Key, Rets>, anyhow::Error> where $($x::Native: FromToNativeWasmType + NativeWasmTypeInto,)* {
+            let mut context = self.call_context();
+            let instance = instantiated(context.assets, handle)?;
+            let mut store = context.stores.lock(handle)?;
+            let exported = exported_function(instance, &store, function_name)
+                .and_then(|export| export.typed::<( $(<$x as FromToNativeWasmType>::Native),* ), Rets>(&*store).map_err(anyhow::Error::new))
+                .map_err(|err| anyhow::Error::msg(format!(
+                    "{} is not exported correctly: {}",
+                    function_name,
+                    err
+                )))?;
+            let recorder = script_recorder(context.recorder, context.assets, handle, &mut store, instance);
+            let mut measured = context.metrics.batch(&mut store, instance, function_name);
+            let guard = context.watchdog.batch(context.determinism, &mut store, instance);
+            let results = calls
+                .into_iter()
+                .map(|(key, ($($x,)*))| {
+                    let recording = recorder.as_ref().map(|recorder| recorder.begin(function_name));
+                    let ($($x),*) = record_args(&mut store, &recording, ($($x.to_native()),*));
+                    let result = measured.measure(&mut store, |store| {
+                        guard.guard(store, function_name, |store| exported.call(store, $($x,)*))
+                    });
+                    (key, finish_call(&mut store, recording, result))
                 })
+                .collect();
+            drop(guard);
+            measured.finish(&mut store);
+            Ok(results)
+        }
+    };
+}
+
+macro_rules! impl_call_context {
+    (wasmer_store) => {
+        fn call_context(&mut self) -> ScriptCallContext<'_> {
+            ScriptCallContext {
+                stores: ScriptStoreAccess::new(&self.stores, Some(&mut self.wasmer_store), None),
+                assets: &self.assets,
+                watchdog: &self.watchdog,
+                determinism: self.determinism.as_deref(),
+                generations: &self.generations,
+                metrics: &self.metrics,
+                recorder: self.recorder.as_deref(),
+            }
+        }
+    };
+    ($script:ident) => {
+        fn call_context(&mut self) -> ScriptCallContext<'_> {
+            ScriptCallContext {
+                stores: ScriptStoreAccess::new(
+                    &self.stores,
+                    None,
+                    Some(CallerQueryAccess {
+                        group: TypeId::of::<$script>(),
+                        entities: &self.entities,
+                    }),
+                ),
+                assets: &self.assets,
+                watchdog: &self.watchdog,
+                determinism: self.determinism.as_deref(),
+                generations: &self.generations,
+                metrics: &self.metrics,
+                recorder: self.recorder.as_deref(),
+            }
+        }
+    };
+}
+
+macro_rules! impl_value_calls {
+    () => {
+        fn call_if_instantiated_with_values(
+            &mut self,
+            handle: &Handle<WasmScript>,
+            function_name: &str,
+            args: &[Value],
+        ) -> Result<Box<[Value]>, anyhow::Error> {
+            let mut context = self.call_context();
+            let instance = instantiated(context.assets, handle)?;
+            let mut store = context.stores.lock(handle)?;
+            let function = exported_function(instance, &store, function_name)?;
+            let recording = begin_call(
+                context.recorder,
+                context.assets,
+                handle,
+                function_name,
+                &mut store,
+                instance,
+            );
+            record_value_args(&recording, args);
+            let result = context
+                .metrics
+                .measure(&mut store, instance, function_name, |store| {
+                    context.watchdog.guard(
+                        context.determinism,
+                        store,
+                        instance,
+                        function_name,
+                        |store| function.call(store, args),
+                    )
+                });
+            finish_value_call(recording, result)
         }
     };
 }
@@ -218,6 +511,14 @@ impl<'w, 's, WS: WasmScriptComponent, Without: ReadOnlyWorldQuery> GeneralWasmSc
     impl_calls!(call_if_instantiated_3 S0, S1, S2);
     impl_calls!(call_if_instantiated_4 S0, S1, S2, S3);
     impl_calls!(call_if_instantiated_5 S0, S1, S2, S3, S4);
+    impl_batch_calls!(call_batch_if_instantiated_0);
+    impl_batch_calls!(call_batch_if_instantiated_1 S0);
+    impl_batch_calls!(call_batch_if_instantiated_2 S0, S1);
+    impl_batch_calls!(call_batch_if_instantiated_3 S0, S1, S2);
+    impl_batch_calls!(call_batch_if_instantiated_4 S0, S1, S2, S3);
+    impl_batch_calls!(call_batch_if_instantiated_5 S0, S1, S2, S3, S4);
+    impl_value_calls!();
+    impl_call_context!(WS);
 }
 
 impl<'w, 's, WS: WasmScriptResource, Without: ReadOnlyWorldQuery> GeneralWasmScriptEnv
@@ -229,6 +530,14 @@ impl<'w, 's, WS: WasmScriptResource, Without: ReadOnlyWorldQuery> GeneralWasmScr
     impl_calls!(call_if_instantiated_3 S0, S1, S2);
     impl_calls!(call_if_instantiated_4 S0, S1, S2, S3);
     impl_calls!(call_if_instantiated_5 S0, S1, S2, S3, S4);
+    impl_batch_calls!(call_batch_if_instantiated_0);
+    impl_batch_calls!(call_batch_if_instantiated_1 S0);
+    impl_batch_calls!(call_batch_if_instantiated_2 S0, S1);
+    impl_batch_calls!(call_batch_if_instantiated_3 S0, S1, S2);
+    impl_batch_calls!(call_batch_if_instantiated_4 S0, S1, S2, S3);
+    impl_batch_calls!(call_batch_if_instantiated_5 S0, S1, S2, S3, S4);
+    impl_value_calls!();
+    impl_call_context!(WS);
 }
 
 impl<'w, 's> GeneralWasmScriptEnv for WasmScriptEnv<'w, 's> {
@@ -238,4 +547,12 @@ impl<'w, 's> GeneralWasmScriptEnv for WasmScriptEnv<'w, 's> {
     impl_calls!(call_if_instantiated_3 S0, S1, S2);
     impl_calls!(call_if_instantiated_4 S0, S1, S2, S3);
     impl_calls!(call_if_instantiated_5 S0, S1, S2, S3, S4);
+    impl_batch_calls!(call_batch_if_instantiated_0);
+    impl_batch_calls!(call_batch_if_instantiated_1 S0);
+    impl_batch_calls!(call_batch_if_instantiated_2 S0, S1);
+    impl_batch_calls!(call_batch_if_instantiated_3 S0, S1, S2);
+    impl_batch_calls!(call_batch_if_instantiated_4 S0, S1, S2, S3);
+    impl_batch_calls!(call_batch_if_instantiated_5 S0, S1, S2, S3, S4);
+    impl_value_calls!();
+    impl_call_context!(wasmer_store);
 }
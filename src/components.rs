@@ -1,3 +1,5 @@
+use std::any::TypeId;
+
 use anyhow::anyhow;
 use bevy::{
     ecs::{
@@ -10,7 +12,19 @@ use bevy::{
 };
 use wasmer::{imports, Imports, Instance, Module};
 
-use crate::{world_pointer::WorldPointer, WasmScript, WasmerStore};
+use crate::{
+    assets::script_instantiated,
+    host::HostImports,
+    limits::smallest_limit,
+    manifest::{manifests_for, matches_manifests},
+    mods::mod_allows,
+    permissions::{granted_capabilities, restrict_imports},
+    queries::query_access_for,
+    recording::record_imports,
+    stores::{assign_group_store, group_store},
+    world_pointer::WorldPointer,
+    ScriptCapabilities, WasmMemoryLimits, WasmScript, WasmerStore,
+};
 
 /** The WasmScriptComponent represents the configuration point for component-based scripts.
 A WasmScriptComponent should have an associated handle, which is returned by `get_wasm_script_handle`.
@@ -19,12 +33,24 @@ Each WasmScriptComponent can define its own set of imports, by defining `get_imp
 When defining imports which reference the provided `WorldPointer`, you should include a list of
 queried components in `ImportQueriedComponents`, and any referenced resources in `ImportedResources`.
 
-SAFETY: If your system uses the components or resources, care should be taken to avoid safety issues
-related to concurrent access to those components.
+SAFETY: Systems calling scripts of different types through their `WasmScriptComponentEnv` run in
+parallel, as described by `WasmScriptStores`, so imports must only use the components and resources
+listed here, which that env holds, and `WorldPointer::commands` for this type. Imports touching
+anything else race with other systems. `WasmScriptEnv` holds none of these, so calls through it are
+only sound from exclusive systems, as the systems of this crate are. If your system uses the
+components or resources, care should be taken to avoid safety issues related to concurrent access to
+those components.
 
 If you are not defining imports or not using the provided `WorldPointer`, both `ImportResources` and
 `ImportQueriedComponents` can be set to `()`.
 
+Every script also receives the host imports provided by this crate, such as the `events` namespace
+described by `ScriptEventRegistry`. Imports defined by the script type take precedence. Scripts may
+only query the components listed in `ImportQueriedComponents`, as described by `ScriptQueryRegistry`.
+
+Import namespaces gated by a capability are only available to scripts whose type grants it through
+`capabilities`. Gated functions which are not granted are replaced with denying stubs at instantiation.
+
 `WasmScriptComponent` types should be registered with the App, using `add_wasm_script_component`. This
 will ensure that the script assets are instantiated and usable. Instantiation happens only when the
 asset is loaded or reloaded.
@@ -40,13 +66,57 @@ pub trait WasmScriptComponent: Component {
 
     fn get_wasm_script_handle(&self) -> &Handle<WasmScript>;
 
+    /**
+    Every script handle of this component, each instantiated independently, in the order they receive
+    calls such as event handlers. Only the handle from `get_wasm_script_handle`, unless overridden as
+    by `WasmScripts`.
+    */
+    fn get_wasm_script_handles(&self) -> &[Handle<WasmScript>] {
+        std::slice::from_ref(self.get_wasm_script_handle())
+    }
+
+    /**
+    The capabilities scripts of this type may be granted, none by default. Manifests may narrow
+    these further.
+    */
+    fn capabilities() -> ScriptCapabilities {
+        ScriptCapabilities::none()
+    }
+
+    /** The most memory, in wasm pages, scripts of this type may use. See `WasmMemoryLimits`. */
+    fn memory_limit() -> Option<u32> {
+        None
+    }
+
     fn instantiate(
         world_pointer: &WorldPointer,
         wasmer_store: &mut WasmerStore,
         module: &Module,
+        capabilities: &ScriptCapabilities,
     ) -> Result<Instance, anyhow::Error> {
+        let host_imports = HostImports::new(
+            world_pointer,
+            &mut wasmer_store.0,
+            module.name().unwrap_or(""),
+            query_access_for::<Self>(world_pointer.read()),
+            TypeId::of::<Self>(),
+        );
         let imports = Self::get_imports_from_world(wasmer_store, world_pointer);
+        let imports = restrict_imports(
+            world_pointer.read(),
+            &mut wasmer_store.0,
+            module,
+            capabilities,
+            host_imports.with(imports),
+        );
+        let imports = record_imports(
+            world_pointer.read(),
+            &mut wasmer_store.0,
+            module.name().unwrap_or(""),
+            imports,
+        );
         let instance = Instance::new(&mut wasmer_store.0, module, &imports)?;
+        host_imports.bind(&mut wasmer_store.0, &instance);
         Ok(instance)
     }
 }
@@ -72,7 +142,7 @@ fn get_modified_script_assets<S: WasmScriptComponent>(
         let mut s_handles = HashSet::new();
         {
             for component in scripts_on_entities.iter(&world) {
-                s_handles.insert(component.get_wasm_script_handle());
+                s_handles.extend(component.get_wasm_script_handles());
             }
         }
         result.retain(|updated_script_handle| s_handles.contains(updated_script_handle));
@@ -87,7 +157,7 @@ fn get_added_script_assets<S: WasmScriptComponent>(world: &mut World) -> Vec<Han
             .query_filtered::<&S, Or<(Changed<S>, Added<S>)>>()
             .iter(world)
         {
-            s_handles.insert(changed.get_wasm_script_handle().clone());
+            s_handles.extend(changed.get_wasm_script_handles().iter().cloned());
         }
     }
     s_handles.drain().collect::<Vec<Handle<WasmScript>>>()
@@ -100,15 +170,33 @@ fn instantiate_if_compiled<S: WasmScriptComponent>(
     // SAFETY: Probably not safe?
     // Need to figure out how world access actually works and how long we can keep a WorldPointer around...
     unsafe {
+        if !matches_manifests(world, &wasm_script_handle) || !mod_allows(world, &wasm_script_handle)
+        {
+            return None;
+        }
         let world_pointer = WorldPointer::new(world).clone();
-        let mut state =
-            SystemState::<(ResMut<Assets<WasmScript>>, ResMut<WasmerStore>)>::new(world);
-        let (mut wasm_assets, mut wasmer_store) = state.get_mut(world);
+        let capabilities = granted_capabilities(world, &wasm_script_handle, &S::capabilities());
+        let memory_limit = smallest_limit(
+            manifests_for(world, &wasm_script_handle)
+                .iter()
+                .map(|manifest| manifest.memory_limit)
+                .chain([S::memory_limit()]),
+        );
+        let memory_limits = world.get_resource::<WasmMemoryLimits>().cloned();
+        let store = group_store(world, TypeId::of::<S>());
+        let mut state = SystemState::<ResMut<Assets<WasmScript>>>::new(world);
+        let mut wasm_assets = state.get_mut(world);
         let wasm_script = wasm_assets.get_mut(&wasm_script_handle)?;
         let name = wasm_script.name();
         if let WasmScript::Compiled(module) = wasm_script {
             bevy::log::warn!("Received compiled module {}...", name);
-            match S::instantiate(&world_pointer, &mut wasmer_store, module) {
+            let Ok(mut wasmer_store) = store.lock() else {
+                bevy::log::error!("Could not instantiate {}: script store poisoned", name);
+                return None;
+            };
+            let _scope = memory_limits.map(|limits| limits.instantiating(&name, memory_limit));
+            let _span = bevy::log::info_span!("wasm_instantiate", script = name.as_str()).entered();
+            match S::instantiate(&world_pointer, &mut wasmer_store, module, &capabilities) {
                 Ok(instance) => {
                     bevy::log::warn!("Instantiated module {}...", name);
                     Some((module.name().unwrap_or("").to_string(), instance))
@@ -130,7 +218,12 @@ pub fn instantiate_wasm_component_scripts<S: WasmScriptComponent>(world: &mut Wo
             world
                 .get_resource_mut::<Assets<WasmScript>>()
                 .unwrap()
-                .set_untracked(script_asset, WasmScript::Instantiated(name, instance));
+                .set_untracked(
+                    script_asset.clone(),
+                    WasmScript::Instantiated(name, instance),
+                );
+            assign_group_store(world, &script_asset, TypeId::of::<S>());
+            script_instantiated(world, script_asset);
         }
     }
     for script_asset in get_added_script_assets::<S>(world) {
@@ -138,7 +231,12 @@ pub fn instantiate_wasm_component_scripts<S: WasmScriptComponent>(world: &mut Wo
             world
                 .get_resource_mut::<Assets<WasmScript>>()
                 .unwrap()
-                .set_untracked(script_asset, WasmScript::Instantiated(name, instance));
+                .set_untracked(
+                    script_asset.clone(),
+                    WasmScript::Instantiated(name, instance),
+                );
+            assign_group_store(world, &script_asset, TypeId::of::<S>());
+            script_instantiated(world, script_asset);
         }
     }
 }
@@ -0,0 +1,286 @@
+use std::{any::TypeId, collections::VecDeque};
+
+use bevy::{ecs::system::SystemState, prelude::*, utils::HashMap};
+use wasmer::{ExternType, FunctionType, Type, Value};
+
+use crate::{GeneralWasmScriptEnv, WasmScript, WasmScriptComponent, WasmScriptEnv};
+
+const HELP: [&str; 4] = [
+    "scripts: list every script and its state",
+    "exports <script>: list the exports of a compiled script",
+    "call <entity|script> <export> [args...]: call an export of an entity's scripts, or of a script",
+    "help: show this message",
+];
+
+type EntityScripts = fn(&World, Entity) -> Vec<Handle<WasmScript>>;
+
+/** How to find the scripts of an entity, for each registered script component type. */
+#[derive(Resource, Default)]
+pub(crate) struct ScriptEntityLookup(HashMap<TypeId, EntityScripts>);
+
+fn entity_scripts<S: WasmScriptComponent>(
+    world: &World,
+    entity: Entity,
+) -> Vec<Handle<WasmScript>> {
+    world
+        .get::<S>(entity)
+        .map(|component| component.get_wasm_script_handles().to_vec())
+        .unwrap_or_default()
+}
+
+pub(crate) fn register_console_lookup<S: WasmScriptComponent>(world: &mut World) {
+    world
+        .get_resource_or_insert_with(ScriptEntityLookup::default)
+        .0
+        .insert(TypeId::of::<S>(), entity_scripts::<S>);
+}
+
+/**
+The `WasmConsole` takes text commands for poking at scripts while the game runs, such as from an
+in-game console or a debug socket. Each command is run on the next update, and its output is logged
+and kept until taken with `take_output`:
+```ignore
+console.run("scripts");
+console.run("exports add_one");
+console.run("call 3v0 add_one 41");
+```
+
+Entities are given as their index, optionally with their generation as they are printed, such as
+`3v0`. Calling an entity calls each of its scripts which has the export, from every registered
+script component type. Anything else, or an entity with no scripts, is taken as the name of a
+script, for resource scripts. Only numeric arguments can be passed.
+
+Commands are only run while the `WasmConsolePlugin` is added.
+*/
+#[derive(Resource, Debug, Default)]
+pub struct WasmConsole {
+    pending: VecDeque<String>,
+    output: Vec<String>,
+}
+
+impl WasmConsole {
+    pub fn run(&mut self, command: impl Into<String>) {
+        self.pending.push_back(command.into());
+    }
+
+    /** Every line of output since it was last taken. */
+    pub fn take_output(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.output)
+    }
+}
+
+/**
+The `WasmConsolePlugin` runs the commands sent to the `WasmConsole`. This plugin should be added after
+the `WasmPlugin`.
+*/
+#[derive(Default)]
+pub struct WasmConsolePlugin;
+
+impl Plugin for WasmConsolePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WasmConsole>()
+            .add_system(run_console_commands);
+    }
+}
+
+fn run_console_commands(world: &mut World) {
+    let commands: Vec<_> = world
+        .resource_mut::<WasmConsole>()
+        .pending
+        .drain(..)
+        .collect();
+    for command in commands {
+        let mut output = vec![format!("> {}", command)];
+        match run_command(world, &command) {
+            Ok(lines) => output.extend(lines),
+            Err(err) => output.push(format!("error: {}", err)),
+        }
+        for line in output.iter() {
+            bevy::log::info!("{}", line);
+        }
+        world.resource_mut::<WasmConsole>().output.extend(output);
+    }
+}
+
+fn run_command(world: &mut World, command: &str) -> Result<Vec<String>, anyhow::Error> {
+    let words: Vec<_> = command.split_whitespace().collect();
+    match words.as_slice() {
+        ["help"] | [] => Ok(HELP.iter().map(|line| line.to_string()).collect()),
+        ["scripts"] => Ok(list_scripts(world)),
+        ["exports", script] => list_exports(world, script),
+        ["call", target, export, args @ ..] => call_export(world, target, export, args),
+        _ => Err(anyhow::Error::msg(format!(
+            "Unknown command {}. Try help.",
+            command
+        ))),
+    }
+}
+
+fn list_scripts(world: &World) -> Vec<String> {
+    let mut scripts: Vec<_> = world
+        .resource::<Assets<WasmScript>>()
+        .iter()
+        .map(|(id, script)| {
+            let state = match script {
+                WasmScript::Loaded(..) => "loaded",
+                WasmScript::Compiled(_) => "compiled",
+                WasmScript::Instantiated(..) => "instantiated",
+            };
+            format!("{} ({:?}): {}", script.name(), id, state)
+        })
+        .collect();
+    scripts.sort();
+    scripts
+}
+
+/** The exports of a compiled script, with their types. */
+fn exports(script: &WasmScript) -> Vec<(String, ExternType)> {
+    let module = match script {
+        WasmScript::Loaded(..) => return Vec::new(),
+        WasmScript::Compiled(module) => module,
+        WasmScript::Instantiated(_, instance) => instance.module(),
+    };
+    module
+        .exports()
+        .map(|export| (export.name().to_string(), export.ty().clone()))
+        .collect()
+}
+
+fn scripts_named(world: &World, name: &str) -> Vec<Handle<WasmScript>> {
+    world
+        .resource::<Assets<WasmScript>>()
+        .iter()
+        .filter(|(_, script)| script.name() == name)
+        .map(|(id, _)| Handle::weak(id))
+        .collect()
+}
+
+fn list_exports(world: &World, name: &str) -> Result<Vec<String>, anyhow::Error> {
+    let assets = world.resource::<Assets<WasmScript>>();
+    let script = scripts_named(world, name)
+        .first()
+        .and_then(|handle| assets.get(handle))
+        .ok_or_else(|| anyhow::Error::msg(format!("No script is named {}.", name)))?;
+    if let WasmScript::Loaded(..) = script {
+        return Err(anyhow::Error::msg(format!("{} is not compiled yet.", name)));
+    }
+    Ok(exports(script)
+        .into_iter()
+        .map(|(export, ty)| match ty {
+            ExternType::Function(ty) => format!("{}: {}", export, ty),
+            ExternType::Global(ty) => format!("{}: global {}", export, ty),
+            ExternType::Memory(_) => format!("{}: memory", export),
+            ExternType::Table(_) => format!("{}: table", export),
+        })
+        .collect())
+}
+
+/** The index and, if given, the generation of an entity written as `3` or `3v0`. */
+fn parse_entity_id(target: &str) -> Option<(u32, Option<u32>)> {
+    match target.split_once('v') {
+        Some((index, generation)) => Some((index.parse().ok()?, Some(generation.parse().ok()?))),
+        None => Some((target.parse().ok()?, None)),
+    }
+}
+
+/** The live entity `target` names. Freed indices and other generations name no entity. */
+fn parse_entity(world: &World, target: &str) -> Option<Entity> {
+    let (index, generation) = parse_entity_id(target)?;
+    let entity = world.entities().resolve_from_id(index)?;
+    if generation.is_some_and(|generation| generation != entity.generation()) {
+        return None;
+    }
+    world.get_entity(entity).map(|entity| entity.id())
+}
+
+fn scripts_of_entity(world: &World, entity: Entity) -> Vec<Handle<WasmScript>> {
+    world
+        .get_resource::<ScriptEntityLookup>()
+        .map(|lookup| {
+            lookup
+                .0
+                .values()
+                .flat_map(|scripts| scripts(world, entity))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn parse_args(ty: &FunctionType, args: &[&str]) -> Result<Vec<Value>, anyhow::Error> {
+    if ty.params().len() != args.len() {
+        return Err(anyhow::Error::msg(format!(
+            "Expected {} arguments, for {}.",
+            ty.params().len(),
+            ty
+        )));
+    }
+    ty.params()
+        .iter()
+        .zip(args)
+        .map(|(param, arg)| {
+            let value = match param {
+                Type::I32 => arg.parse().ok().map(Value::I32),
+                Type::I64 => arg.parse().ok().map(Value::I64),
+                Type::F32 => arg.parse().ok().map(Value::F32),
+                Type::F64 => arg.parse().ok().map(Value::F64),
+                _ => None,
+            };
+            value.ok_or_else(|| anyhow::Error::msg(format!("{} is not a valid {}.", arg, param)))
+        })
+        .collect()
+}
+
+fn call_export(
+    world: &mut World,
+    target: &str,
+    export: &str,
+    args: &[&str],
+) -> Result<Vec<String>, anyhow::Error> {
+    // Targets which look like entities may still be script names, such as for resource scripts.
+    let entity = parse_entity(world, target);
+    let handles = entity
+        .map(|entity| scripts_of_entity(world, entity))
+        .filter(|handles| !handles.is_empty())
+        .unwrap_or_else(|| scripts_named(world, target));
+    if handles.is_empty() && entity.is_none() && parse_entity_id(target).is_some() {
+        return Err(anyhow::Error::msg(format!(
+            "No entity or script is named {}.",
+            target
+        )));
+    }
+    // Only the scripts which export the function, with its type.
+    let assets = world.resource::<Assets<WasmScript>>();
+    let calls: Vec<_> = handles
+        .into_iter()
+        .filter_map(|handle| {
+            let script = assets.get(&handle)?;
+            exports(script)
+                .into_iter()
+                .find_map(|(name, ty)| match ty {
+                    ExternType::Function(ty) if name == export => Some(ty),
+                    _ => None,
+                })
+                .map(|ty| (handle, script.name(), ty))
+        })
+        .collect();
+    if calls.is_empty() {
+        return Err(anyhow::Error::msg(format!(
+            "{} has no script which exports {}.",
+            target, export
+        )));
+    }
+    let mut state = SystemState::<WasmScriptEnv>::new(world);
+    let mut script_env = state.get_mut(world);
+    let mut output = Vec::new();
+    for (handle, name, ty) in calls {
+        let line = match parse_args(&ty, args)
+            .and_then(|args| script_env.call_if_instantiated_with_values(&handle, export, &args))
+        {
+            Ok(results) => format!("{}.{} returned {:?}", name, export, results),
+            Err(err) => format!("{}.{} failed: {}", name, export, err),
+        };
+        output.push(line);
+    }
+    state.apply(world);
+    Ok(output)
+}
@@ -0,0 +1,434 @@
+use std::cell::RefCell;
+
+use bevy::{ecs::system::SystemState, prelude::*};
+use wasmer::{
+    imports, Function, FunctionEnv, FunctionEnvMut, Imports, Instance, Store, TypedFunction, Value,
+};
+
+use crate::{
+    calls::IntoScriptArgs,
+    functions::ScriptCallContext,
+    host::{read_guest_bytes, HostEnv},
+    recording::{begin_call, finish_call, finish_value_call, record_args, record_value_args},
+    stores::{exported_function, instantiated},
+    GeneralWasmScriptEnv, WasmScript, WasmScriptEnv,
+};
+
+/** The export which allocates the buffer a suspended call's stack is saved to. */
+const STACK_ALLOC_EXPORT: &str = "coroutine_stack_alloc";
+/** The optional export which releases a buffer from `coroutine_stack_alloc`. */
+const STACK_FREE_EXPORT: &str = "coroutine_stack_free";
+
+// The states returned by `asyncify_get_state`.
+const REWINDING: i32 = 2;
+const UNWINDING: i32 = 1;
+
+/** What a suspended coroutine is waiting for. */
+#[derive(Debug, Clone, PartialEq)]
+pub enum CoroutineWait {
+    /** Resume once this many more seconds have passed. */
+    Seconds(f32),
+    /** Resume after this many more frames. */
+    Frames(u32),
+    /** Resume once the named export, which takes no arguments and returns an `i32`, returns non-zero. */
+    Until(String),
+}
+
+/** The exports added to a module by Binaryen's asyncify transform. */
+#[derive(Clone)]
+struct Asyncify {
+    start_unwind: TypedFunction<i32, ()>,
+    stop_unwind: TypedFunction<(), ()>,
+    start_rewind: TypedFunction<i32, ()>,
+    stop_rewind: TypedFunction<(), ()>,
+    get_state: TypedFunction<(), i32>,
+}
+
+impl Asyncify {
+    fn new(instance: &Instance, store: &Store) -> Result<Self, anyhow::Error> {
+        let exports = &instance.exports;
+        Ok(Self {
+            start_unwind: exports.get_typed_function(store, "asyncify_start_unwind")?,
+            stop_unwind: exports.get_typed_function(store, "asyncify_stop_unwind")?,
+            start_rewind: exports.get_typed_function(store, "asyncify_start_rewind")?,
+            stop_rewind: exports.get_typed_function(store, "asyncify_stop_rewind")?,
+            get_state: exports.get_typed_function(store, "asyncify_get_state")?,
+        })
+    }
+}
+
+/** The coroutine call running on this thread, which the `coroutine` imports suspend. */
+struct ActiveCoroutine {
+    data: i32,
+    asyncify: Asyncify,
+    wait: Option<CoroutineWait>,
+}
+
+thread_local! {
+    static ACTIVE: RefCell<Option<ActiveCoroutine>> = const { RefCell::new(None) };
+}
+
+struct SuspendedCoroutine {
+    entity: Entity,
+    script: Handle<WasmScript>,
+    export: String,
+    args: Vec<Value>,
+    data: i32,
+    generation: u64,
+    wait: CoroutineWait,
+}
+
+/**
+`WasmCoroutines` tracks script calls which have suspended themselves to wait, for each entity. A
+coroutine is started with `start`, and is resumed by the `WasmPlugin` from where it left off, on the
+first frame its wait is over.
+
+Coroutine scripts must be transformed with Binaryen's asyncify pass
+(`wasm-opt --asyncify --pass-arg=asyncify-imports@coroutine.wait_seconds,...`), and must export
+`coroutine_stack_alloc() -> i32`, which returns a buffer for the saved stack, laid out as asyncify
+expects: the address where the saved stack starts, then the address where the buffer ends. Each
+suspended call gets its own buffer, which is passed to `coroutine_stack_free(ptr: i32)` when the call
+finishes, if that is exported.
+
+Scripts suspend through the `coroutine` import namespace:
+* `wait_seconds(seconds: f32)`
+* `wait_frames(frames: i32)`
+* `wait_until(export_ptr: i32, export_len: i32)` waits until the named export, which takes no
+  arguments and returns an `i32`, returns non-zero. It is checked once per frame.
+
+Waiting outside of a coroutine does nothing. Coroutines are dropped when their entity is despawned,
+freeing their stacks, or when their script is reloaded, whose new instance doesn't own them.
+*/
+#[derive(Resource, Default)]
+pub struct WasmCoroutines {
+    suspended: Vec<SuspendedCoroutine>,
+}
+
+impl WasmCoroutines {
+    /**
+    Call `export` on the script as a coroutine for `entity`. Returns whether the call suspended
+    itself, in which case it is resumed on a later frame.
+    */
+    pub fn start(
+        &mut self,
+        script_env: &mut impl GeneralWasmScriptEnv,
+        entity: Entity,
+        handle: &Handle<WasmScript>,
+        export: &str,
+        args: impl IntoScriptArgs,
+    ) -> Result<bool, anyhow::Error> {
+        let args = args.into_script_args();
+        let mut context = script_env.call_context();
+        let data = allocate_stack(&mut context, handle)?;
+        match run(&mut context, handle, export, &args, data, false) {
+            Ok(Some(wait)) => {
+                self.suspended.push(SuspendedCoroutine {
+                    entity,
+                    script: handle.clone(),
+                    export: export.to_string(),
+                    args,
+                    data,
+                    generation: context.generations.generation(handle),
+                    wait,
+                });
+                Ok(true)
+            }
+            Ok(None) => {
+                free_stack(&mut context, handle, data);
+                Ok(false)
+            }
+            Err(err) => {
+                free_stack(&mut context, handle, data);
+                Err(err)
+            }
+        }
+    }
+
+    /** Whether `entity` has any suspended coroutines. */
+    pub fn is_suspended(&self, entity: Entity) -> bool {
+        self.suspended
+            .iter()
+            .any(|coroutine| coroutine.entity == entity)
+    }
+
+    /** Whether no coroutine is suspended, for any entity. */
+    pub fn is_empty(&self) -> bool {
+        self.suspended.is_empty()
+    }
+
+    /** What each of the coroutines suspended for `entity` is waiting for, by export. */
+    pub fn waits(&self, entity: Entity) -> impl Iterator<Item = (&str, &CoroutineWait)> {
+        self.suspended
+            .iter()
+            .filter(move |coroutine| coroutine.entity == entity)
+            .map(|coroutine| (coroutine.export.as_str(), &coroutine.wait))
+    }
+
+    /**
+    Drop every coroutine suspended for `entity`, without resuming them, and free their stacks through
+    `script_env`.
+    */
+    pub fn cancel(&mut self, script_env: &mut impl GeneralWasmScriptEnv, entity: Entity) {
+        let (cancelled, suspended) = std::mem::take(&mut self.suspended)
+            .into_iter()
+            .partition(|coroutine| coroutine.entity == entity);
+        self.suspended = suspended;
+        let mut context = script_env.call_context();
+        for coroutine in cancelled {
+            release(&mut context, &coroutine);
+        }
+    }
+}
+
+fn allocate_stack(
+    context: &mut ScriptCallContext,
+    handle: &Handle<WasmScript>,
+) -> Result<i32, anyhow::Error> {
+    let instance = instantiated(context.assets, handle)?;
+    let mut store = context.stores.lock(handle)?;
+    let alloc = exported_function(instance, &store, STACK_ALLOC_EXPORT)?
+        .typed::<(), i32>(&*store)
+        .map_err(anyhow::Error::new)?;
+    let recording = begin_call(
+        context.recorder,
+        context.assets,
+        handle,
+        STACK_ALLOC_EXPORT,
+        &mut store,
+        instance,
+    );
+    let result = alloc.call(&mut *store).map_err(anyhow::Error::new);
+    finish_call(&mut store, recording, result)
+}
+
+fn free_stack(context: &mut ScriptCallContext, handle: &Handle<WasmScript>, data: i32) {
+    let Ok(instance) = instantiated(context.assets, handle) else {
+        return;
+    };
+    let Ok(mut store) = context.stores.lock(handle) else {
+        return;
+    };
+    if let Ok(free) = exported_function(instance, &store, STACK_FREE_EXPORT)
+        .and_then(|export| export.typed::<i32, ()>(&*store).map_err(anyhow::Error::new))
+    {
+        let recording = begin_call(
+            context.recorder,
+            context.assets,
+            handle,
+            STACK_FREE_EXPORT,
+            &mut store,
+            instance,
+        );
+        let data = record_args(&mut store, &recording, data);
+        let result = free.call(&mut *store, data).map_err(anyhow::Error::new);
+        if let Err(err) = finish_call(&mut store, recording, result) {
+            bevy::log::error!("Failed to free a coroutine stack: {}", err);
+        }
+    }
+}
+
+/**
+Free the stack of a coroutine which won't be resumed, unless its script was reloaded since it was
+suspended, in which case the stack belonged to the old instance.
+*/
+fn release(context: &mut ScriptCallContext, coroutine: &SuspendedCoroutine) {
+    if context.generations.generation(&coroutine.script) == coroutine.generation {
+        free_stack(context, &coroutine.script, coroutine.data);
+    }
+}
+
+/**
+Call `export`, rewinding to where it was suspended first if `rewind` is set. Returns what the call
+is waiting for, if it suspended itself again.
+*/
+fn run(
+    context: &mut ScriptCallContext,
+    handle: &Handle<WasmScript>,
+    export: &str,
+    args: &[Value],
+    data: i32,
+    rewind: bool,
+) -> Result<Option<CoroutineWait>, anyhow::Error> {
+    let instance = instantiated(context.assets, handle)?;
+    let mut store = context.stores.lock(handle)?;
+    let asyncify = Asyncify::new(instance, &store)?;
+    let function = exported_function(instance, &store, export)?;
+    if rewind {
+        asyncify.start_rewind.call(&mut *store, data)?;
+    }
+    ACTIVE.with(|active| {
+        *active.borrow_mut() = Some(ActiveCoroutine {
+            data,
+            asyncify: asyncify.clone(),
+            wait: None,
+        })
+    });
+    let recording = begin_call(
+        context.recorder,
+        context.assets,
+        handle,
+        export,
+        &mut store,
+        instance,
+    );
+    record_value_args(&recording, args);
+    let result = context
+        .metrics
+        .measure(&mut store, instance, export, |store| {
+            context
+                .watchdog
+                .guard(context.determinism, store, instance, export, |store| {
+                    function.call(store, args)
+                })
+        });
+    let result = finish_value_call(recording, result);
+    let active = ACTIVE.with(|active| active.borrow_mut().take());
+    // Leave the instance out of asyncify's unwinding or rewinding states, even if the call failed, so
+    // that later calls run normally.
+    let state = asyncify.get_state.call(&mut *store)?;
+    match state {
+        UNWINDING => asyncify.stop_unwind.call(&mut *store)?,
+        // The call failed before it was rewound to where it was suspended.
+        REWINDING => asyncify.stop_rewind.call(&mut *store)?,
+        _ => {}
+    }
+    let suspended = state == UNWINDING;
+    result?;
+    Ok(if suspended {
+        active.and_then(|active| active.wait)
+    } else {
+        None
+    })
+}
+
+fn suspend(mut env: FunctionEnvMut<HostEnv>, wait: CoroutineWait) {
+    let active = ACTIVE.with(|active| {
+        active
+            .borrow()
+            .as_ref()
+            .map(|active| (active.data, active.asyncify.clone()))
+    });
+    let Some((data, asyncify)) = active else {
+        bevy::log::warn!("{} waited outside of a coroutine", env.data().script);
+        return;
+    };
+    let store = &mut env;
+    let result = match asyncify.get_state.call(store) {
+        // Resuming: the call has been rewound to here, and continues.
+        Ok(REWINDING) => asyncify.stop_rewind.call(store),
+        Ok(_) => {
+            ACTIVE.with(|active| {
+                if let Some(active) = active.borrow_mut().as_mut() {
+                    active.wait = Some(wait);
+                }
+            });
+            asyncify.start_unwind.call(store, data)
+        }
+        Err(err) => Err(err),
+    };
+    if let Err(err) = result {
+        bevy::log::error!("Failed to suspend a coroutine: {}", err);
+    }
+}
+
+fn wait_seconds(env: FunctionEnvMut<HostEnv>, seconds: f32) {
+    suspend(env, CoroutineWait::Seconds(seconds));
+}
+
+fn wait_frames(env: FunctionEnvMut<HostEnv>, frames: i32) {
+    suspend(env, CoroutineWait::Frames(frames.max(0) as u32));
+}
+
+fn wait_until(env: FunctionEnvMut<HostEnv>, export_ptr: i32, export_len: i32) {
+    match read_guest_bytes(&env, export_ptr, export_len)
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+    {
+        Some(export) => suspend(env, CoroutineWait::Until(export)),
+        None => bevy::log::error!("{} waited on an unreadable export", env.data().script),
+    }
+}
+
+pub(crate) fn script_coroutine_imports(store: &mut Store, env: &FunctionEnv<HostEnv>) -> Imports {
+    imports! {
+        "coroutine" => {
+            "wait_seconds" => Function::new_typed_with_env(store, env, wait_seconds),
+            "wait_frames" => Function::new_typed_with_env(store, env, wait_frames),
+            "wait_until" => Function::new_typed_with_env(store, env, wait_until),
+        }
+    }
+}
+
+type ResumeState = SystemState<(
+    ResMut<'static, WasmCoroutines>,
+    Res<'static, Time>,
+    Query<'static, 'static, Entity>,
+    WasmScriptEnv<'static, 'static>,
+)>;
+
+// Exclusive, like every crate system calling scripts through `WasmScriptEnv`. See `WasmScriptStores`.
+pub(crate) fn resume_wasm_coroutines(world: &mut World, state: &mut ResumeState) {
+    let (mut coroutines, time, entities, mut script_env) = state.get_mut(world);
+    let delta = time.delta_seconds();
+    let suspended = std::mem::take(&mut coroutines.suspended);
+    for mut coroutine in suspended {
+        if !entities.contains(coroutine.entity) {
+            release(&mut script_env.call_context(), &coroutine);
+            continue;
+        }
+        let ready = match &mut coroutine.wait {
+            CoroutineWait::Seconds(seconds) => {
+                *seconds -= delta;
+                *seconds <= 0.0
+            }
+            CoroutineWait::Frames(frames) => {
+                *frames = frames.saturating_sub(1);
+                *frames == 0
+            }
+            CoroutineWait::Until(condition) => {
+                match script_env.call_if_instantiated_0::<i32>(&coroutine.script, condition) {
+                    Ok(result) => result != 0,
+                    Err(err) => {
+                        bevy::log::error!("Failed to check {}: {}", condition, err);
+                        false
+                    }
+                }
+            }
+        };
+        if !ready {
+            coroutines.suspended.push(coroutine);
+            continue;
+        }
+        let mut context = script_env.call_context();
+        if context.generations.generation(&coroutine.script) != coroutine.generation {
+            bevy::log::warn!(
+                "Dropped coroutine {} for {:?}, as its script was reloaded",
+                coroutine.export,
+                coroutine.entity
+            );
+            continue;
+        }
+        match run(
+            &mut context,
+            &coroutine.script,
+            &coroutine.export,
+            &coroutine.args,
+            coroutine.data,
+            true,
+        ) {
+            Ok(Some(wait)) => {
+                coroutine.wait = wait;
+                coroutines.suspended.push(coroutine);
+            }
+            Ok(None) => free_stack(&mut context, &coroutine.script, coroutine.data),
+            Err(err) => {
+                bevy::log::error!(
+                    "Failed to resume coroutine {} for {:?}: {}",
+                    coroutine.export,
+                    coroutine.entity,
+                    err
+                );
+                free_stack(&mut context, &coroutine.script, coroutine.data);
+            }
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use std::fmt::Display;
+
+use bevy::prelude::*;
+#[cfg(feature = "non-js")]
+use wasmer::{Cranelift, EngineBuilder, Features};
+use wasmer::{ExternType, Module};
+
+/** Host imports which read the time between frames, which differs between machines. */
+const FRAME_TIME_IMPORTS: [(&str, Option<&str>); 2] =
+    [("coroutine", Some("wait_seconds")), ("timers", None)];
+
+/** WASI imports which read the wall clock or unseeded randomness. */
+const WASI_NAMESPACES: [&str; 2] = ["wasi_snapshot_preview1", "wasi_unstable"];
+const WASI_IMPORTS: [&str; 3] = ["clock_time_get", "clock_res_get", "random_get"];
+
+/** Why a script was rejected in deterministic mode. */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NonDeterminism {
+    /** The script imports a function which is forbidden in deterministic mode. */
+    ForbiddenImport { namespace: String, name: String },
+    /** The script imports or exports a shared memory, for use by several threads. */
+    SharedMemory { name: String },
+}
+
+impl Display for NonDeterminism {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ForbiddenImport { namespace, name } => {
+                write!(
+                    f,
+                    "imports {}.{}, which is not deterministic",
+                    namespace, name
+                )
+            }
+            Self::SharedMemory { name } => write!(f, "memory {} is shared between threads", name),
+        }
+    }
+}
+
+/**
+`WasmDeterminism` configures deterministic mode, in which every script computes the same results on
+every machine, for lockstep multiplayer and replays. It must be inserted before the `WasmPlugin` is
+added, since it configures the engine the `WasmerStore` is created with:
+```ignore
+app.insert_resource(WasmDeterminism::enabled())
+    .add_plugin(WasmPlugin);
+```
+
+In deterministic mode:
+* Every NaN produced by a float operation is canonicalized, so its bits don't depend on the CPU.
+* The threads and relaxed SIMD proposals are disabled, so scripts using them fail to compile.
+* `compile_wasm_scripts` rejects scripts which import a forbidden function, sending a
+  `WasmScriptError::NonDeterministic` event. By default these are the WASI clock and randomness
+  imports, and the host imports which wait on the time between frames: `coroutine.wait_seconds` and
+  the `timers` namespace. Use `forbid_import` and `allow_import` to change them.
+* The `WasmWatchdog` doesn't time out calls, since whether a call times out depends on the machine.
+
+The engine settings only apply with the `non-js` feature. Imports are checked with either feature.
+*/
+#[derive(Resource, Debug, Clone, Default)]
+pub struct WasmDeterminism {
+    enabled: bool,
+    // Imports by namespace and name. No name forbids the whole namespace.
+    forbidden_imports: Vec<(String, Option<String>)>,
+}
+
+impl WasmDeterminism {
+    /** Deterministic mode, forbidding the default imports. */
+    pub fn enabled() -> Self {
+        let wasi = WASI_NAMESPACES.iter().flat_map(|namespace| {
+            WASI_IMPORTS
+                .iter()
+                .map(move |name| (namespace.to_string(), Some(name.to_string())))
+        });
+        let frame_time = FRAME_TIME_IMPORTS
+            .iter()
+            .map(|(namespace, name)| (namespace.to_string(), name.map(str::to_string)));
+        Self {
+            enabled: true,
+            forbidden_imports: wasi.chain(frame_time).collect(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /** Forbid the import `namespace.name`, or the whole namespace if `name` is `None`. */
+    pub fn forbid_import(mut self, namespace: &str, name: Option<&str>) -> Self {
+        self.forbidden_imports
+            .push((namespace.to_string(), name.map(str::to_string)));
+        self
+    }
+
+    /** Allow an import forbidden with the same `namespace` and `name`, such as one of the defaults. */
+    pub fn allow_import(mut self, namespace: &str, name: Option<&str>) -> Self {
+        self.forbidden_imports.retain(|forbidden| {
+            (forbidden.0.as_str(), forbidden.1.as_deref()) != (namespace, name)
+        });
+        self
+    }
+
+    fn forbids(&self, namespace: &str, name: &str) -> bool {
+        self.forbidden_imports
+            .iter()
+            .any(|(forbidden, forbidden_name)| {
+                forbidden == namespace && forbidden_name.iter().all(|forbidden| forbidden == name)
+            })
+    }
+
+    /** Everything about `module` which is not allowed in deterministic mode. */
+    pub(crate) fn validate(&self, module: &Module) -> Vec<NonDeterminism> {
+        if !self.enabled {
+            return Vec::new();
+        }
+        let imports = module.imports().filter_map(|import| {
+            if let ExternType::Memory(memory) = import.ty() {
+                if memory.shared {
+                    return Some(NonDeterminism::SharedMemory {
+                        name: format!("{}.{}", import.module(), import.name()),
+                    });
+                }
+            }
+            self.forbids(import.module(), import.name())
+                .then(|| NonDeterminism::ForbiddenImport {
+                    namespace: import.module().to_string(),
+                    name: import.name().to_string(),
+                })
+        });
+        let exports = module.exports().filter_map(|export| match export.ty() {
+            ExternType::Memory(memory) if memory.shared => Some(NonDeterminism::SharedMemory {
+                name: export.name().to_string(),
+            }),
+            _ => None,
+        });
+        imports.chain(exports).collect()
+    }
+
+    /** The engine for the `WasmerStore`, with NaN canonicalization and features set. */
+    #[cfg(feature = "non-js")]
+    pub(crate) fn engine(&self, mut compiler: Cranelift) -> EngineBuilder {
+        if !self.enabled {
+            return EngineBuilder::new(compiler);
+        }
+        compiler.canonicalize_nans(true);
+        let features = Features {
+            threads: false,
+            relaxed_simd: false,
+            ..Features::new()
+        };
+        EngineBuilder::new(compiler).set_features(Some(features))
+    }
+}
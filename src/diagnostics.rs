@@ -0,0 +1,114 @@
+use std::time::Duration;
+
+use bevy::{
+    diagnostic::{Diagnostic, DiagnosticId, Diagnostics},
+    prelude::*,
+};
+
+use crate::{GeneralWasmScriptEnv, WasmScript, WasmScriptEnv, WasmScriptMetrics};
+
+/**
+The `WasmDiagnosticsPlugin` registers `Diagnostic`s for the load scripts put on each frame, so they
+can be shown by the `LogDiagnosticsPlugin` or any diagnostics overlay:
+```ignore
+app.add_plugin(WasmPlugin)
+    .add_plugin(WasmDiagnosticsPlugin)
+    .add_plugin(LogDiagnosticsPlugin::default());
+```
+
+Calls and call time are taken from the `WasmScriptMetrics`, so they still count each frame if the
+metrics are reset. This plugin should be added after the `WasmPlugin`.
+*/
+#[derive(Default)]
+pub struct WasmDiagnosticsPlugin;
+
+impl Plugin for WasmDiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(Self::setup_system)
+            .add_system(Self::diagnostic_system.in_base_set(CoreSet::Last));
+    }
+}
+
+#[derive(Default)]
+struct PreviousTotals {
+    calls: u64,
+    time: Duration,
+}
+
+impl WasmDiagnosticsPlugin {
+    /** Calls into every script this frame. */
+    pub const SCRIPT_CALLS: DiagnosticId =
+        DiagnosticId::from_u128(108659217988231445108724052467036395125);
+    /** Time spent in every script this frame, in milliseconds. */
+    pub const SCRIPT_TIME: DiagnosticId =
+        DiagnosticId::from_u128(61153671384267920117627091563454440983);
+    /** Scripts which are instantiated. */
+    pub const INSTANCES: DiagnosticId =
+        DiagnosticId::from_u128(85057289342982421669823976598738791082);
+    /** Scripts which are loaded, but not yet compiled. */
+    pub const COMPILE_QUEUE: DiagnosticId =
+        DiagnosticId::from_u128(284093590906455726040485408671925784135);
+    /** The exported memory of every instantiated script, in kibibytes. */
+    pub const GUEST_MEMORY: DiagnosticId =
+        DiagnosticId::from_u128(314163102871115645162877232011583950711);
+
+    pub fn setup_system(mut diagnostics: ResMut<Diagnostics>) {
+        diagnostics.add(Diagnostic::new(Self::SCRIPT_CALLS, "wasm_script_calls", 20));
+        diagnostics
+            .add(Diagnostic::new(Self::SCRIPT_TIME, "wasm_script_time", 20).with_suffix("ms"));
+        diagnostics
+            .add(Diagnostic::new(Self::INSTANCES, "wasm_instances", 1).with_smoothing_factor(0.0));
+        diagnostics.add(
+            Diagnostic::new(Self::COMPILE_QUEUE, "wasm_compile_queue", 1)
+                .with_smoothing_factor(0.0),
+        );
+        diagnostics.add(
+            Diagnostic::new(Self::GUEST_MEMORY, "wasm_guest_memory", 1)
+                .with_suffix("KiB")
+                .with_smoothing_factor(0.0),
+        );
+    }
+
+    fn diagnostic_system(
+        mut diagnostics: ResMut<Diagnostics>,
+        metrics: Res<WasmScriptMetrics>,
+        mut previous: Local<PreviousTotals>,
+        mut script_env: WasmScriptEnv,
+    ) {
+        let (calls, time) = metrics
+            .by_total_time()
+            .into_iter()
+            .fold((0, Duration::ZERO), |(calls, time), (_, metrics)| {
+                (calls + metrics.calls, time + metrics.total)
+            });
+        // Totals only shrink when the metrics are reset, which leaves just this frame's calls.
+        let frame_calls = calls.checked_sub(previous.calls).unwrap_or(calls);
+        let frame_time = time.checked_sub(previous.time).unwrap_or(time);
+        *previous = PreviousTotals { calls, time };
+        diagnostics.add_measurement(Self::SCRIPT_CALLS, || frame_calls as f64);
+        diagnostics.add_measurement(Self::SCRIPT_TIME, || frame_time.as_secs_f64() * 1000.0);
+
+        let mut context = script_env.call_context();
+        let mut instances = 0;
+        let mut compile_queue = 0;
+        let mut guest_memory = 0;
+        for (id, script) in context.assets.iter() {
+            match script {
+                WasmScript::Loaded(..) => compile_queue += 1,
+                WasmScript::Compiled(_) => {}
+                WasmScript::Instantiated(_, instance) => {
+                    instances += 1;
+                    let Ok(memory) = instance.exports.get_memory("memory") else {
+                        continue;
+                    };
+                    if let Ok(store) = context.stores.lock(&Handle::weak(id)) {
+                        guest_memory += memory.view(&*store).data_size();
+                    }
+                }
+            }
+        }
+        diagnostics.add_measurement(Self::INSTANCES, || instances as f64);
+        diagnostics.add_measurement(Self::COMPILE_QUEUE, || compile_queue as f64);
+        diagnostics.add_measurement(Self::GUEST_MEMORY, || guest_memory as f64 / 1024.0);
+    }
+}
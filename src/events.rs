@@ -0,0 +1,44 @@
+use bevy::prelude::Handle;
+
+use crate::{determinism::NonDeterminism, manifest::ManifestMismatch, WasmScript};
+
+/**
+`WasmScriptError` events are sent for problems which are found outside of a direct script call, such
+as while compiling or instantiating a script. Errors from script calls are still returned directly by
+`call_if_instantiated`.
+*/
+#[derive(Debug, Clone)]
+pub enum WasmScriptError {
+    /** A compiled script did not match a manifest which references it, and was not instantiated. */
+    ManifestMismatch {
+        script: Handle<WasmScript>,
+        manifest: String,
+        mismatches: Vec<ManifestMismatch>,
+    },
+    /** A compiled script uses features forbidden by `WasmDeterminism`, and was not instantiated. */
+    NonDeterministic {
+        script: Handle<WasmScript>,
+        problems: Vec<NonDeterminism>,
+    },
+    /** A script called an import from a namespace gated by a capability it was not granted. */
+    CapabilityDenied {
+        script: String,
+        capability: String,
+        namespace: String,
+        function: String,
+    },
+    /** A script tried to grow its memory past its limit, in pages. The `memory.grow` failed. */
+    MemoryLimitExceeded {
+        script: String,
+        limit: u32,
+        requested: u32,
+    },
+    /** A script sent an event of an unregistered type, or a payload which could not be decoded. */
+    InvalidScriptEvent {
+        script: String,
+        event: String,
+        error: String,
+    },
+    /** A script tried to query a component its script type does not read. */
+    QueryDenied { script: String, component: String },
+}
@@ -0,0 +1,186 @@
+use std::marker::PhantomData;
+
+use bevy::{
+    asset::HandleId,
+    prelude::{Assets, Handle, Resource, World},
+    utils::HashMap,
+};
+use wasmer::{FromToNativeWasmType, Function, Instance, Store, TypedFunction, WasmTypeList};
+
+use crate::{
+    metrics::WasmScriptMetrics,
+    recording::{begin_call, finish_call, record_args, WasmCallRecorder},
+    stores::{exported_function, instantiated, ScriptStoreAccess},
+    GeneralWasmScriptEnv, WasmDeterminism, WasmScript, WasmWatchdog,
+};
+
+/**
+Counts how many times each script asset has been instantiated, so that resolved `ScriptFunction`s
+know when they belong to a replaced instance.
+*/
+#[derive(Resource, Debug, Default)]
+pub struct ScriptGenerations(HashMap<HandleId, u64>);
+
+impl ScriptGenerations {
+    pub fn generation(&self, handle: &Handle<WasmScript>) -> u64 {
+        self.0.get(&handle.id()).copied().unwrap_or(0)
+    }
+}
+
+pub(crate) fn bump_generation(world: &mut World, handle: &Handle<WasmScript>) {
+    if let Some(mut generations) = world.get_resource_mut::<ScriptGenerations>() {
+        *generations.0.entry(handle.id()).or_insert(0) += 1;
+    }
+}
+
+/**
+What a `ScriptFunction` needs from a script environment to be called.
+*/
+pub struct ScriptCallContext<'a> {
+    pub(crate) stores: ScriptStoreAccess<'a>,
+    pub(crate) assets: &'a Assets<WasmScript>,
+    pub(crate) watchdog: &'a WasmWatchdog,
+    pub(crate) determinism: Option<&'a WasmDeterminism>,
+    pub(crate) generations: &'a ScriptGenerations,
+    pub(crate) metrics: &'a WasmScriptMetrics,
+    pub(crate) recorder: Option<&'a WasmCallRecorder>,
+}
+
+struct ResolvedFunction<Args, Rets> {
+    generation: u64,
+    instance: Instance,
+    export: Function,
+    function: TypedFunction<Args, Rets>,
+}
+
+/**
+A `ScriptFunction` is a handle to one exported function of a script, which can be stored on a
+component or resource and called repeatedly. The export is looked up and type-checked on the first
+call, and again only after the script has been re-instantiated, such as after hot reloading.
+
+`Args` and `Rets` are as in `wasmer::TypedFunction`: `()` for no arguments, a single type for one
+argument, or a tuple for more.
+
+```ignore
+let mut on_update = ScriptFunction::<(f64, f32), ()>::new(handle, "on_update");
+on_update.call_2(&mut script_env, entity_id, time.delta_seconds())?;
+```
+*/
+pub struct ScriptFunction<Args, Rets> {
+    handle: Handle<WasmScript>,
+    name: String,
+    resolved: Option<ResolvedFunction<Args, Rets>>,
+    marker: PhantomData<fn(Args) -> Rets>,
+}
+
+// The resolved instance and function hold no pointers, only handles to objects owned by the store
+// the script was instantiated into. `resolve` checks that they belong to the store locked for the
+// call, and they are only used while it is held: locked through its mutex for a script type's store,
+// or borrowed mutably through `WasmScriptEnv` for the `WasmerStore`. So no two threads use them, or
+// the objects behind them, at once.
+unsafe impl<Args, Rets> Send for ScriptFunction<Args, Rets> {}
+unsafe impl<Args, Rets> Sync for ScriptFunction<Args, Rets> {}
+
+impl<Args: WasmTypeList, Rets: WasmTypeList> ScriptFunction<Args, Rets> {
+    pub fn new(handle: Handle<WasmScript>, name: impl Into<String>) -> Self {
+        Self {
+            handle,
+            name: name.into(),
+            resolved: None,
+            marker: PhantomData,
+        }
+    }
+
+    pub fn handle(&self) -> &Handle<WasmScript> {
+        &self.handle
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /** Forget the resolved function, so that it is looked up again on the next call. */
+    pub fn invalidate(&mut self) {
+        self.resolved = None;
+    }
+
+    fn resolve(
+        &mut self,
+        assets: &Assets<WasmScript>,
+        generations: &ScriptGenerations,
+        store: &Store,
+    ) -> Result<(), anyhow::Error> {
+        let generation = generations.generation(&self.handle);
+        if self.resolved.as_ref().is_some_and(|resolved| {
+            resolved.generation == generation && resolved.export.is_from_store(store)
+        }) {
+            return Ok(());
+        }
+        self.resolved = None;
+        let instance = instantiated(assets, &self.handle)?;
+        let (export, function) = exported_function(instance, store, &self.name)
+            .and_then(|export| {
+                let function = export
+                    .typed::<Args, Rets>(store)
+                    .map_err(anyhow::Error::new)?;
+                Ok((export.clone(), function))
+            })
+            .map_err(|err| {
+                anyhow::Error::msg(format!("{} is not exported correctly: {}", self.name, err))
+            })?;
+        self.resolved = Some(ResolvedFunction {
+            generation,
+            instance: instance.clone(),
+            export,
+            function,
+        });
+        Ok(())
+    }
+}
+
+macro_rules! impl_script_function_call {
+    ($name:ident $(, $x:ident )*) => {
+        #[allow(unused_parens)]
+        impl<$($x: FromToNativeWasmType,)* Rets: WasmTypeList> ScriptFunction<($($x),*), Rets>
+        where
+            ($($x),*): WasmTypeList,
+        {
+            /**
+            Call the function, resolving it first if needed. Like `call_if_instantiated_N`, `N` is
+            the number of arguments, and calls are guarded by the `WasmWatchdog`.
+            */
+            #[allow(non_snake_case, clippy::too_many_arguments)]
+            pub fn $name(
+                &mut self,
+                script_env: &mut impl GeneralWasmScriptEnv,
+                $($x: $x,)*
+            ) -> Result<Rets, anyhow::Error> {
+                let mut context = script_env.call_context();
+                let mut store = context.stores.lock(&self.handle)?;
+                self.resolve(context.assets, context.generations, &store)?;
+                let Some(resolved) = &self.resolved else {
+                    return Err(anyhow::Error::msg("Script function not resolved."));
+                };
+                let recording = begin_call(context.recorder, context.assets, &self.handle, &self.name, &mut store, &resolved.instance);
+                let ($($x),*) = record_args(&mut store, &recording, ($($x),*));
+                let result = context.metrics.measure(&mut store, &resolved.instance, &self.name, |store| {
+                    context.watchdog.guard(
+                        context.determinism,
+                        store,
+                        &resolved.instance,
+                        &self.name,
+                        |store| resolved.function.call(store, $($x,)*),
+                    )
+                });
+                finish_call(&mut store, recording, result)
+            }
+        }
+    };
+}
+
+impl_script_function_call!(call_0);
+impl_script_function_call!(call_1, S0);
+impl_script_function_call!(call_2, S0, S1);
+impl_script_function_call!(call_3, S0, S1, S2);
+impl_script_function_call!(call_4, S0, S1, S2, S3);
+impl_script_function_call!(call_5, S0, S1, S2, S3, S4);
@@ -0,0 +1,68 @@
+use bevy::prelude::*;
+
+use crate::{
+    calls::IntoScriptArgs, GeneralWasmScriptEnv, WasmScript, WasmScriptComponent,
+    WasmScriptComponentEnv,
+};
+
+pub(crate) fn exports_function(
+    assets: &Assets<WasmScript>,
+    handle: &Handle<WasmScript>,
+    function_name: &str,
+) -> bool {
+    matches!(
+        assets.get(handle),
+        Some(WasmScript::Instantiated(_, instance))
+            if instance.exports.get_function(function_name).is_ok()
+    )
+}
+
+/**
+Add a system which reads every `E` event, and calls `handler` on the scripts of the entities chosen by
+`route`. `route` returns the receiving entities, each with the arguments for its call. By convention,
+the first argument is the receiving entity's `EntityId`, as with other component script calls.
+
+Entities without an `S` component are skipped, as are scripts which don't export `handler`. Handlers
+are called in the order the events were sent, and on each of an entity's scripts in order.
+*/
+pub(crate) fn add_wasm_event_handler<S, E, Args>(
+    app: &mut App,
+    handler: &str,
+    route: impl Fn(&E) -> Vec<(Entity, Args)> + Send + Sync + 'static,
+) where
+    S: WasmScriptComponent,
+    E: Event,
+    Args: IntoScriptArgs,
+{
+    let handler = handler.to_string();
+    app.add_system(
+        move |mut events: EventReader<E>,
+              scripted_entities: Query<&S>,
+              assets: Res<Assets<WasmScript>>,
+              mut script_env: WasmScriptComponentEnv<S>| {
+            for event in events.iter() {
+                for (entity, args) in route(event) {
+                    let Ok(script) = scripted_entities.get(entity) else {
+                        continue;
+                    };
+                    let args = args.into_script_args();
+                    for handle in script.get_wasm_script_handles() {
+                        if !exports_function(&assets, handle, &handler) {
+                            continue;
+                        }
+                        if let Err(err) =
+                            script_env.call_if_instantiated_with_values(handle, &handler, &args)
+                        {
+                            bevy::log::error!(
+                                "Failed to run {} for {:?}: {}",
+                                handler,
+                                entity,
+                                err
+                            );
+                        }
+                    }
+                }
+            }
+        },
+    );
+}
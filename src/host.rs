@@ -0,0 +1,120 @@
+use std::any::TypeId;
+
+use bevy::ecs::{component::ComponentId, query::Access};
+use wasmer::{FunctionEnv, FunctionEnvMut, Imports, Instance, Memory, Store};
+
+use crate::{
+    coroutines::script_coroutine_imports,
+    permissions::PendingScriptErrors,
+    queries::{script_query_imports, ScriptQueries},
+    scene_scripts::script_config_imports,
+    script_events::{script_event_imports, ScriptEventSender},
+    script_systems::{script_system_imports, ScriptSystemDeclarations},
+    timers::{script_timer_imports, WasmScriptTimers},
+    WasmScriptError, WorldPointer,
+};
+
+/**
+The environment shared by the imports this crate provides to every script, alongside those defined
+by the script type. The script's exported `memory`, if any, is bound once it has been instantiated.
+*/
+#[derive(Clone)]
+pub(crate) struct HostEnv {
+    pub(crate) script: String,
+    pub(crate) memory: Option<Memory>,
+    pub(crate) world: WorldPointer,
+    pub(crate) errors: PendingScriptErrors,
+    pub(crate) events: ScriptEventSender,
+    pub(crate) systems: ScriptSystemDeclarations,
+    pub(crate) queries: ScriptQueries,
+    pub(crate) timers: WasmScriptTimers,
+}
+
+impl HostEnv {
+    pub(crate) fn report(&self, error: WasmScriptError) {
+        self.errors.push(error);
+    }
+}
+
+/**
+Read `len` bytes of the calling script's memory, starting at `ptr`. The range is checked against the
+memory before anything is allocated, since both come from the script.
+*/
+pub(crate) fn read_guest_bytes(
+    env: &FunctionEnvMut<HostEnv>,
+    ptr: i32,
+    len: i32,
+) -> Option<Vec<u8>> {
+    let memory = env.data().memory.clone()?;
+    let view = memory.view(env);
+    let offset = u64::try_from(ptr).ok()?;
+    let len = u64::try_from(len).ok()?;
+    if offset.checked_add(len)? > view.data_size() {
+        return None;
+    }
+    let mut bytes = vec![0; usize::try_from(len).ok()?];
+    view.read(offset, &mut bytes).ok()?;
+    Some(bytes)
+}
+
+pub(crate) struct HostImports {
+    env: FunctionEnv<HostEnv>,
+    imports: Imports,
+}
+
+impl HostImports {
+    pub(crate) fn new(
+        world_pointer: &WorldPointer,
+        store: &mut Store,
+        script: &str,
+        query_access: Access<ComponentId>,
+        group: TypeId,
+    ) -> Self {
+        let world = world_pointer.read();
+        let env = FunctionEnv::new(
+            store,
+            HostEnv {
+                script: script.to_string(),
+                memory: None,
+                world: world_pointer.clone(),
+                errors: world
+                    .get_resource::<PendingScriptErrors>()
+                    .cloned()
+                    .unwrap_or_default(),
+                events: ScriptEventSender::from_world(world),
+                systems: world
+                    .get_resource::<ScriptSystemDeclarations>()
+                    .cloned()
+                    .unwrap_or_default(),
+                queries: ScriptQueries::new(world, query_access, group),
+                timers: world
+                    .get_resource::<WasmScriptTimers>()
+                    .cloned()
+                    .unwrap_or_default(),
+            },
+        );
+        let mut imports = Imports::new();
+        imports.extend(&script_event_imports(store, &env));
+        imports.extend(&script_system_imports(store, &env));
+        imports.extend(&script_query_imports(store, &env));
+        imports.extend(&script_coroutine_imports(store, &env));
+        imports.extend(&script_timer_imports(store, &env));
+        imports.extend(&script_config_imports(store, &env));
+        Self { env, imports }
+    }
+
+    /**
+    Combine the host imports with those of the script type. The script type's imports take
+    precedence.
+    */
+    pub(crate) fn with(&self, imports: Imports) -> Imports {
+        let mut combined = self.imports.clone();
+        combined.extend(&imports);
+        combined
+    }
+
+    pub(crate) fn bind(&self, store: &mut Store, instance: &Instance) {
+        let memory = instance.exports.get_memory("memory").ok().cloned();
+        self.env.as_mut(store).memory = memory;
+    }
+}
@@ -1,7 +1,7 @@
 use assets::{compile_wasm_scripts, WasmAssetLoader, WatAssetLoader};
 use bevy::prelude::{
-    AddAsset, App, CoreSet, FromWorld, IntoSystemAppConfig, IntoSystemConfig, Plugin, Resource,
-    World,
+    AddAsset, App, Component, CoreSchedule, CoreSet, Entity, Event, FromWorld, IntoSystemAppConfig,
+    IntoSystemConfig, Plugin, Resource, World,
 };
 
 extern crate anyhow;
@@ -13,24 +13,97 @@ mod assets;
 mod calls;
 mod commands;
 mod components;
+mod console;
+mod coroutines;
+mod determinism;
+mod diagnostics;
 mod entity;
+mod events;
+mod functions;
+mod handlers;
+mod host;
+mod limits;
+mod manifest;
+mod metrics;
+mod mods;
+mod multi_scripts;
+mod permissions;
+mod queries;
+mod recording;
+mod replay;
 mod resources;
+mod rollback;
+pub mod scene_scripts;
+mod script_events;
+mod script_systems;
+mod snapshots;
+mod stores;
+mod testing;
+mod timers;
+mod watchdog;
 mod world_pointer;
 
 pub use assets::WasmScript;
 pub use calls::{
-    GeneralWasmScriptEnv, WasmScriptComponentEnv, WasmScriptEnv, WasmScriptResourceEnv,
+    BatchResults, GeneralWasmScriptEnv, IntoScriptArgs, WasmScriptComponentEnv, WasmScriptEnv,
+    WasmScriptResourceEnv,
 };
 use commands::ScriptCommandQueue;
 pub use commands::ScriptSystemWithCommands;
 use components::instantiate_wasm_component_scripts;
 pub use components::WasmScriptComponent;
+use console::register_console_lookup;
+pub use console::{WasmConsole, WasmConsolePlugin};
+use coroutines::resume_wasm_coroutines;
+pub use coroutines::{CoroutineWait, WasmCoroutines};
+pub use determinism::{NonDeterminism, WasmDeterminism};
+pub use diagnostics::WasmDiagnosticsPlugin;
 pub use entity::*;
+pub use events::WasmScriptError;
+pub use functions::{ScriptCallContext, ScriptFunction, ScriptGenerations};
+use handlers::add_wasm_event_handler;
+#[cfg(feature = "non-js")]
+use limits::LimitingTunables;
+pub use limits::WasmMemoryLimits;
+use manifest::WasmManifestAssetLoader;
+pub use manifest::{ManifestMismatch, ScriptEntryPoint, ScriptValueType, WasmScriptManifest};
+#[cfg(feature = "non-js")]
+use metrics::FuelMiddleware;
+pub use metrics::{ScriptMetrics, WasmScriptMetrics};
+pub use mods::{FailedMod, LoadedMod, LoadedMods, ModLoadFailure, WasmModManifest, WasmModsPlugin};
+pub use multi_scripts::{EachResults, WasmScripts};
+use permissions::{send_pending_script_errors, PendingScriptErrors};
+pub use permissions::{ScriptCapabilities, ScriptPermissions};
+use queries::register_query_access;
+pub use queries::{ScriptQueryRegistry, MAX_OPEN_QUERIES};
+pub use recording::{RecordedEntry, RecordedImport, WasmCallRecorder};
+pub use replay::{ReplayMismatch, ReplayReport, WasmReplay};
 use resources::instantiate_wasm_resource_scripts;
 pub use resources::{instantiate_resource_script, WasmScriptResource};
+pub use rollback::{RollbackSnapshot, ScriptHostState, WasmRollback};
+use scene_scripts::load_script_paths;
+pub use scene_scripts::WasmScriptPath;
+pub use script_events::ScriptEventRegistry;
+use script_events::{send_script_events, ScriptEventBuffer};
+use script_systems::{register_script_systems, ScriptSystemDeclarations};
+pub use script_systems::{
+    wasm_system_phase, ScriptSystem, WasmScriptSystems, DEFAULT_SCRIPT_PHASE,
+    FIXED_UPDATE_SCRIPT_PHASE, POST_UPDATE_SCRIPT_PHASE, PRE_UPDATE_SCRIPT_PHASE,
+};
+use serde::de::DeserializeOwned;
+pub use snapshots::{ScriptSnapshot, SnapshotValue, WasmScriptSnapshots};
 #[cfg(feature = "non-js")]
-use wasmer::Cranelift;
+use std::sync::Arc;
+pub use stores::{ScriptStoreAccess, WasmScriptStores};
+pub use testing::{WasmTestApp, WasmTestScript};
+use timers::tick_script_timers;
+pub use timers::{TimerReloadPolicy, WasmScriptTimers};
 use wasmer::Store;
+#[cfg(feature = "non-js")]
+use wasmer::{BaseTunables, CompilerConfig, Cranelift, EngineBuilder, Target};
+#[cfg(feature = "non-js")]
+use watchdog::DeadlineMiddleware;
+pub use watchdog::{ScriptTimeout, WasmWatchdog};
 pub use world_pointer::WorldPointer;
 
 /** The `WasmerStore` is an essential item for the use of wasm scripts. However, it should not
@@ -39,10 +112,33 @@ be referenced directly by systems. `WasmScriptEnv`, `WasmScriptComponentEnv`, an
 #[derive(Resource)]
 pub struct WasmerStore(pub Store);
 
+#[cfg(feature = "non-js")]
+fn limiting_tunables(world: &mut World) -> LimitingTunables {
+    LimitingTunables {
+        base: BaseTunables::for_target(&Target::default()),
+        limits: world
+            .get_resource_or_insert_with(WasmMemoryLimits::default)
+            .clone(),
+        errors: world
+            .get_resource_or_insert_with(PendingScriptErrors::default)
+            .clone(),
+    }
+}
+
+/** The engine scripts are compiled with: with the watchdog and fuel middlewares, for `determinism`. */
+#[cfg(feature = "non-js")]
+pub(crate) fn script_engine(determinism: &WasmDeterminism) -> EngineBuilder {
+    let mut compiler = Cranelift::default();
+    compiler.push_middleware(Arc::new(DeadlineMiddleware::default()));
+    compiler.push_middleware(Arc::new(FuelMiddleware::default()));
+    determinism.engine(compiler)
+}
+
 impl FromWorld for WasmerStore {
     #[cfg(feature = "non-js")]
-    fn from_world(_world: &mut World) -> Self {
-        WasmerStore(Store::new(Cranelift::default()))
+    fn from_world(world: &mut World) -> Self {
+        let engine = script_engine(&world.get_resource_or_insert_with(WasmDeterminism::default));
+        WasmerStore(Store::new_with_tunables(engine, limiting_tunables(world)))
     }
     #[cfg(feature = "js")]
     fn from_world(_world: &mut World) -> Self {
@@ -50,37 +146,150 @@ impl FromWorld for WasmerStore {
     }
 }
 
+impl WasmerStore {
+    /** A new store sharing this store's engine, for the `WasmScriptStores`. */
+    #[cfg(feature = "non-js")]
+    pub(crate) fn sibling(&self, world: &mut World) -> WasmerStore {
+        WasmerStore(Store::new_with_tunables(
+            self.0.engine().clone(),
+            limiting_tunables(world),
+        ))
+    }
+    #[cfg(feature = "js")]
+    pub(crate) fn sibling(&self, _world: &mut World) -> WasmerStore {
+        WasmerStore(Store::new())
+    }
+}
+
 #[derive(Default)]
 pub struct WasmPlugin;
 
 impl Plugin for WasmPlugin {
     fn build(&self, app: &mut App) {
         app.add_asset::<WasmScript>()
+            .add_asset::<WasmScriptManifest>()
+            .add_event::<WasmScriptError>()
+            .init_resource::<ScriptPermissions>()
+            .init_resource::<PendingScriptErrors>()
+            .init_resource::<WasmMemoryLimits>()
+            .init_resource::<WasmWatchdog>()
+            .init_resource::<WasmScriptMetrics>()
+            .init_resource::<WasmDeterminism>()
+            .init_resource::<ScriptGenerations>()
+            .init_resource::<ScriptEventRegistry>()
+            .init_resource::<ScriptEventBuffer>()
+            .init_resource::<WasmScriptSystems>()
+            .init_resource::<ScriptSystemDeclarations>()
+            .init_resource::<ScriptQueryRegistry>()
+            .init_resource::<WasmCoroutines>()
+            .init_resource::<WasmScriptTimers>()
+            .init_resource::<WasmScriptSnapshots>()
+            .init_resource::<WasmRollback>()
             .init_resource::<WasmerStore>()
+            .init_resource::<WasmScriptStores>()
             .add_asset_loader(WasmAssetLoader)
             .add_asset_loader(WatAssetLoader)
-            .add_system(compile_wasm_scripts.in_base_set(CoreSet::Last));
+            .add_asset_loader(WasmManifestAssetLoader)
+            .register_type::<WasmScriptPath>()
+            // The type of `WasmScriptPath::config`, which scenes need to deserialize it.
+            .register_type::<bevy::utils::HashMap<String, f64>>()
+            .add_wasm_script_component::<WasmScriptPath>()
+            .add_wasm_script_component::<WasmScripts>()
+            .add_system(load_script_paths.in_base_set(CoreSet::PreUpdate))
+            .add_system(send_script_events.in_base_set(CoreSet::PostUpdate))
+            .add_system(wasm_system_phase(PRE_UPDATE_SCRIPT_PHASE).in_base_set(CoreSet::PreUpdate))
+            .add_system(wasm_system_phase(DEFAULT_SCRIPT_PHASE))
+            .add_system(
+                wasm_system_phase(POST_UPDATE_SCRIPT_PHASE).in_base_set(CoreSet::PostUpdate),
+            )
+            .add_system(
+                wasm_system_phase(FIXED_UPDATE_SCRIPT_PHASE).in_schedule(CoreSchedule::FixedUpdate),
+            )
+            .add_system(resume_wasm_coroutines)
+            .add_system(tick_script_timers)
+            .add_system(register_script_systems.in_base_set(CoreSet::Last))
+            .add_system(compile_wasm_scripts.in_base_set(CoreSet::Last))
+            .add_system(send_pending_script_errors.in_base_set(CoreSet::Last));
     }
 }
 
 pub trait WasmScriptAdder {
     fn add_wasm_script_component<S: WasmScriptComponent>(&mut self) -> &mut Self;
     fn add_wasm_script_resource<R: WasmScriptResource>(&mut self) -> &mut Self;
+    /** Require `capability` for scripts to use any import from the given `namespaces`. */
+    fn add_wasm_capability(&mut self, capability: &str, namespaces: &[&str]) -> &mut Self;
+    /** Allow scripts to send events of type `E` by `name`. See `ScriptEventRegistry`. */
+    fn add_wasm_script_event<E: Event + DeserializeOwned>(&mut self, name: &str) -> &mut Self;
+    /**
+    Call `handler` on the `S` scripts of the entities `route` picks for each `E` event, with the
+    arguments it returns for each entity.
+    */
+    fn add_wasm_event_handler<S: WasmScriptComponent, E: Event, Args: IntoScriptArgs>(
+        &mut self,
+        handler: &str,
+        route: impl Fn(&E) -> Vec<(Entity, Args)> + Send + Sync + 'static,
+    ) -> &mut Self;
+    /**
+    Allow scripts to query `C` by `name`, copying `encode`d components into their memory. See
+    `ScriptQueryRegistry`.
+    */
+    fn add_wasm_query_component<C: Component, const N: usize>(
+        &mut self,
+        name: &str,
+        encode: impl Fn(&C) -> [u8; N] + Send + Sync + 'static,
+    ) -> &mut Self;
 }
 
 impl WasmScriptAdder for App {
     fn add_wasm_script_component<S: WasmScriptComponent>(&mut self) -> &mut Self {
+        register_query_access::<S, S::ImportQueriedComponents>(&mut self.world);
+        register_console_lookup::<S>(&mut self.world);
         self.add_system(instantiate_wasm_component_scripts::<S>)
             .init_resource::<ScriptCommandQueue<S>>()
     }
 
     fn add_wasm_script_resource<R: WasmScriptResource>(&mut self) -> &mut Self {
+        register_query_access::<R, R::ImportQueriedComponents>(&mut self.world);
         self.add_system(instantiate_wasm_resource_scripts::<R>)
             .init_resource::<ScriptCommandQueue<R>>()
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    fn add_wasm_capability(&mut self, capability: &str, namespaces: &[&str]) -> &mut Self {
+        let mut permissions = self
+            .world
+            .get_resource_or_insert_with(ScriptPermissions::default);
+        for namespace in namespaces {
+            permissions.gate_namespace(*namespace, capability);
+        }
+        self
+    }
+
+    fn add_wasm_script_event<E: Event + DeserializeOwned>(&mut self, name: &str) -> &mut Self {
+        self.add_event::<E>();
+        self.world
+            .get_resource_or_insert_with(ScriptEventRegistry::default)
+            .register::<E>(name);
+        self
+    }
+
+    fn add_wasm_event_handler<S: WasmScriptComponent, E: Event, Args: IntoScriptArgs>(
+        &mut self,
+        handler: &str,
+        route: impl Fn(&E) -> Vec<(Entity, Args)> + Send + Sync + 'static,
+    ) -> &mut Self {
+        add_wasm_event_handler::<S, E, Args>(self, handler, route);
+        self
+    }
+
+    fn add_wasm_query_component<C: Component, const N: usize>(
+        &mut self,
+        name: &str,
+        encode: impl Fn(&C) -> [u8; N] + Send + Sync + 'static,
+    ) -> &mut Self {
+        let component_id = self.world.init_component::<C>();
+        self.world
+            .get_resource_or_insert_with(ScriptQueryRegistry::default)
+            .register::<C, N>(name, component_id, encode);
+        self
+    }
 }
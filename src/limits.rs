@@ -0,0 +1,225 @@
+use std::sync::{Arc, Mutex};
+
+use bevy::prelude::*;
+
+#[derive(Debug, Default)]
+struct MemoryLimitState {
+    default_limit: Option<u32>,
+    instantiating: Option<(String, Option<u32>)>,
+}
+
+/**
+`WasmMemoryLimits` caps how far script instances may grow their linear memory, in wasm pages of
+64KiB. A limit can be set for every script with `set_default_limit`, for a script type with
+`WasmScriptComponent::memory_limit` or `WasmScriptResource::memory_limit`, and for a single script
+with `memory_limit` in its manifest. The smallest applicable limit is used.
+
+A script whose declared minimum memory is over its limit will fail to instantiate. When a script tries
+to grow past its limit, `memory.grow` fails inside the script and a
+`WasmScriptError::MemoryLimitExceeded` event is sent.
+
+Limits are enforced through the `WasmerStore`'s tunables, and so only apply with the `non-js` feature.
+*/
+#[derive(Resource, Debug, Default, Clone)]
+pub struct WasmMemoryLimits(Arc<Mutex<MemoryLimitState>>);
+
+impl WasmMemoryLimits {
+    pub fn set_default_limit(&self, pages: Option<u32>) {
+        if let Ok(mut state) = self.0.lock() {
+            state.default_limit = pages;
+        }
+    }
+
+    pub fn default_limit(&self) -> Option<u32> {
+        self.0.lock().ok().and_then(|state| state.default_limit)
+    }
+
+    /**
+    Apply `limit` to any memories created until the returned scope is dropped. Instantiation happens
+    synchronously, so this is how the tunables know which script a memory belongs to.
+    */
+    pub(crate) fn instantiating(&self, script: &str, limit: Option<u32>) -> InstantiationScope {
+        if let Ok(mut state) = self.0.lock() {
+            state.instantiating = Some((script.to_string(), limit));
+        }
+        InstantiationScope(self.clone())
+    }
+
+    #[cfg_attr(not(feature = "non-js"), allow(dead_code))]
+    fn current(&self) -> (String, Option<u32>) {
+        let Ok(state) = self.0.lock() else {
+            return (String::new(), None);
+        };
+        let (script, limit) = state
+            .instantiating
+            .clone()
+            .unwrap_or_else(|| (String::new(), None));
+        let limit = match (limit, state.default_limit) {
+            (Some(limit), Some(default_limit)) => Some(limit.min(default_limit)),
+            (limit, default_limit) => limit.or(default_limit),
+        };
+        (script, limit)
+    }
+}
+
+pub(crate) struct InstantiationScope(WasmMemoryLimits);
+
+impl Drop for InstantiationScope {
+    fn drop(&mut self) {
+        if let Ok(mut state) = (self.0).0.lock() {
+            state.instantiating = None;
+        }
+    }
+}
+
+pub(crate) fn smallest_limit(limits: impl IntoIterator<Item = Option<u32>>) -> Option<u32> {
+    limits.into_iter().flatten().min()
+}
+
+#[cfg(feature = "non-js")]
+pub(crate) use tunables::LimitingTunables;
+
+#[cfg(feature = "non-js")]
+mod tunables {
+    use std::ptr::NonNull;
+
+    use wasmer::{
+        vm::{MemoryError, MemoryStyle, TableStyle, VMMemory, VMMemoryDefinition, VMTable},
+        BaseTunables, MemoryType, Pages, TableType, Tunables,
+    };
+    use wasmer_vm::{LinearMemory, Trap, VMTableDefinition};
+
+    use super::WasmMemoryLimits;
+    use crate::{permissions::PendingScriptErrors, WasmScriptError};
+
+    /**
+    Tunables which lower the maximum of every memory created to the limit of the script being
+    instantiated.
+    */
+    pub(crate) struct LimitingTunables {
+        pub(crate) base: BaseTunables,
+        pub(crate) limits: WasmMemoryLimits,
+        pub(crate) errors: PendingScriptErrors,
+    }
+
+    impl LimitingTunables {
+        fn limited(&self, ty: &MemoryType) -> (MemoryType, String, Option<u32>) {
+            let (script, limit) = self.limits.current();
+            let mut ty = *ty;
+            if let Some(limit) = limit {
+                ty.maximum = Some(
+                    ty.maximum
+                        .map_or(Pages(limit), |maximum| maximum.min(Pages(limit))),
+                );
+            }
+            (ty, script, limit)
+        }
+
+        fn wrap(&self, memory: VMMemory, script: String, limit: Option<u32>) -> VMMemory {
+            VMMemory(Box::new(LimitedMemory {
+                inner: memory,
+                script,
+                limit,
+                errors: self.errors.clone(),
+            }))
+        }
+    }
+
+    impl Tunables for LimitingTunables {
+        fn memory_style(&self, memory: &MemoryType) -> MemoryStyle {
+            self.base.memory_style(memory)
+        }
+
+        fn table_style(&self, table: &TableType) -> TableStyle {
+            self.base.table_style(table)
+        }
+
+        fn create_host_memory(
+            &self,
+            ty: &MemoryType,
+            style: &MemoryStyle,
+        ) -> Result<VMMemory, MemoryError> {
+            let (ty, script, limit) = self.limited(ty);
+            let memory = self.base.create_host_memory(&ty, style)?;
+            Ok(self.wrap(memory, script, limit))
+        }
+
+        unsafe fn create_vm_memory(
+            &self,
+            ty: &MemoryType,
+            style: &MemoryStyle,
+            vm_definition_location: NonNull<VMMemoryDefinition>,
+        ) -> Result<VMMemory, MemoryError> {
+            let (ty, script, limit) = self.limited(ty);
+            let memory = self
+                .base
+                .create_vm_memory(&ty, style, vm_definition_location)?;
+            Ok(self.wrap(memory, script, limit))
+        }
+
+        fn create_host_table(&self, ty: &TableType, style: &TableStyle) -> Result<VMTable, String> {
+            self.base.create_host_table(ty, style)
+        }
+
+        unsafe fn create_vm_table(
+            &self,
+            ty: &TableType,
+            style: &TableStyle,
+            vm_definition_location: NonNull<VMTableDefinition>,
+        ) -> Result<VMTable, String> {
+            self.base.create_vm_table(ty, style, vm_definition_location)
+        }
+    }
+
+    /**
+    A memory which reports failures to grow past its limit.
+    */
+    #[derive(Debug)]
+    struct LimitedMemory {
+        inner: VMMemory,
+        script: String,
+        limit: Option<u32>,
+        errors: PendingScriptErrors,
+    }
+
+    impl LinearMemory for LimitedMemory {
+        fn ty(&self) -> MemoryType {
+            self.inner.ty()
+        }
+
+        fn size(&self) -> Pages {
+            self.inner.size()
+        }
+
+        fn style(&self) -> MemoryStyle {
+            self.inner.style()
+        }
+
+        fn grow(&mut self, delta: Pages) -> Result<Pages, MemoryError> {
+            let requested = self.inner.size().0.saturating_add(delta.0);
+            let result = self.inner.grow(delta);
+            if let (Err(_), Some(limit)) = (&result, self.limit) {
+                if requested > limit {
+                    self.errors.push(WasmScriptError::MemoryLimitExceeded {
+                        script: self.script.clone(),
+                        limit,
+                        requested,
+                    });
+                }
+            }
+            result
+        }
+
+        fn vmmemory(&self) -> NonNull<VMMemoryDefinition> {
+            self.inner.vmmemory()
+        }
+
+        fn try_clone(&self) -> Option<Box<dyn LinearMemory + 'static>> {
+            None
+        }
+
+        unsafe fn initialize_with_data(&self, start: usize, data: &[u8]) -> Result<(), Trap> {
+            self.inner.initialize_with_data(start, data)
+        }
+    }
+}
@@ -0,0 +1,272 @@
+use std::fmt::Display;
+
+use bevy::{
+    asset::{AssetLoader, AssetPath, LoadContext, LoadedAsset},
+    prelude::{Assets, Handle, World},
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use serde::{Deserialize, Serialize};
+use wasmer::{Module, Type};
+
+use crate::WasmScript;
+
+/**
+The value types which may appear in the signature of an entry point, as declared in a manifest.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ScriptValueType {
+    I32,
+    I64,
+    F32,
+    F64,
+    V128,
+    ExternRef,
+    FuncRef,
+}
+
+impl From<Type> for ScriptValueType {
+    fn from(ty: Type) -> Self {
+        match ty {
+            Type::I32 => Self::I32,
+            Type::I64 => Self::I64,
+            Type::F32 => Self::F32,
+            Type::F64 => Self::F64,
+            Type::V128 => Self::V128,
+            Type::ExternRef => Self::ExternRef,
+            Type::FuncRef => Self::FuncRef,
+        }
+    }
+}
+
+/**
+An exported function which the host expects to call, along with its expected signature.
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScriptEntryPoint {
+    pub name: String,
+    #[serde(default)]
+    pub params: Vec<ScriptValueType>,
+    #[serde(default)]
+    pub results: Vec<ScriptValueType>,
+}
+
+/**
+The on-disk representation of a `.script.ron` file. The `script` path is relative to the manifest.
+*/
+#[derive(Debug, Deserialize)]
+struct ScriptManifestDescriptor {
+    name: String,
+    #[serde(default)]
+    version: String,
+    script: String,
+    #[serde(default)]
+    entry_points: Vec<ScriptEntryPoint>,
+    #[serde(default)]
+    imports: Vec<String>,
+    #[serde(default)]
+    capabilities: Vec<String>,
+    #[serde(default)]
+    memory_limit: Option<u32>,
+}
+
+/**
+A `WasmScriptManifest` describes a single script: its name and version, the entry points the host
+expects it to export, the import namespaces it is allowed to use, and the capabilities it requests.
+
+Manifests are loaded from `.script.ron` files, which reference a `.wasm` or `.wat` file. Loading the
+manifest will also load the referenced script. When that script is compiled, `compile_wasm_scripts`
+checks the module against every loaded manifest which references it. A manifest which loads after its
+script has compiled is checked as soon as it loads, and an already instantiated script which does not
+match it is dropped back to its compiled module. A script with mismatches is never instantiated until
+either the script or the manifest is fixed. Mismatches are logged and sent as
+`WasmScriptError::ManifestMismatch` events.
+
+An example manifest:
+```ron
+(
+    name: "add_one",
+    version: "1.0.0",
+    script: "add_one.wat",
+    entry_points: [
+        (name: "main", params: [I32], results: [I32]),
+    ],
+    imports: [],
+    capabilities: [],
+    // Optional, in 64KiB wasm pages.
+    memory_limit: Some(16),
+)
+```
+*/
+#[derive(Debug, TypeUuid)]
+#[uuid = "5a2e3f8c-7d0b-4a61-9f3e-2c8b1d6e4a90"]
+pub struct WasmScriptManifest {
+    pub name: String,
+    pub version: String,
+    pub script: Handle<WasmScript>,
+    pub entry_points: Vec<ScriptEntryPoint>,
+    pub imports: Vec<String>,
+    pub capabilities: Vec<String>,
+    pub memory_limit: Option<u32>,
+}
+
+/**
+A single difference between a compiled module and a manifest which references it.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ManifestMismatch {
+    MissingEntryPoint(String),
+    EntryPointSignature {
+        name: String,
+        expected: (Vec<ScriptValueType>, Vec<ScriptValueType>),
+        found: (Vec<ScriptValueType>, Vec<ScriptValueType>),
+    },
+    UndeclaredImport {
+        namespace: String,
+        name: String,
+    },
+}
+
+impl Display for ManifestMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingEntryPoint(name) => write!(f, "entry point {} is not exported", name),
+            Self::EntryPointSignature {
+                name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "entry point {} has signature {:?} -> {:?}, expected {:?} -> {:?}",
+                name, found.0, found.1, expected.0, expected.1
+            ),
+            Self::UndeclaredImport { namespace, name } => write!(
+                f,
+                "import {}.{} is from an undeclared namespace",
+                namespace, name
+            ),
+        }
+    }
+}
+
+impl WasmScriptManifest {
+    /**
+    Check a compiled module against this manifest, returning every mismatch found.
+    */
+    pub fn validate(&self, module: &Module) -> Vec<ManifestMismatch> {
+        let mut mismatches = Vec::new();
+        for entry_point in &self.entry_points {
+            match module
+                .exports()
+                .functions()
+                .find(|export| export.name() == entry_point.name)
+            {
+                Some(export) => {
+                    let params: Vec<ScriptValueType> =
+                        export.ty().params().iter().map(|ty| (*ty).into()).collect();
+                    let results: Vec<ScriptValueType> = export
+                        .ty()
+                        .results()
+                        .iter()
+                        .map(|ty| (*ty).into())
+                        .collect();
+                    if params != entry_point.params || results != entry_point.results {
+                        mismatches.push(ManifestMismatch::EntryPointSignature {
+                            name: entry_point.name.clone(),
+                            expected: (entry_point.params.clone(), entry_point.results.clone()),
+                            found: (params, results),
+                        });
+                    }
+                }
+                None => mismatches.push(ManifestMismatch::MissingEntryPoint(
+                    entry_point.name.clone(),
+                )),
+            }
+        }
+        for import in module.imports() {
+            if !self
+                .imports
+                .iter()
+                .any(|namespace| namespace == import.module())
+            {
+                mismatches.push(ManifestMismatch::UndeclaredImport {
+                    namespace: import.module().to_string(),
+                    name: import.name().to_string(),
+                });
+            }
+        }
+        mismatches
+    }
+}
+
+/**
+Every loaded manifest which describes the given script.
+*/
+pub(crate) fn manifests_for<'w>(
+    world: &'w World,
+    handle: &Handle<WasmScript>,
+) -> Vec<&'w WasmScriptManifest> {
+    world
+        .get_resource::<Assets<WasmScriptManifest>>()
+        .map(|manifests| {
+            manifests
+                .iter()
+                .map(|(_, manifest)| manifest)
+                .filter(|manifest| manifest.script == *handle)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/**
+Whether a compiled script matches every loaded manifest which describes it. Scripts which do not are
+never instantiated, whichever of the script and its manifests loaded first.
+*/
+pub(crate) fn matches_manifests(world: &World, handle: &Handle<WasmScript>) -> bool {
+    let Some(WasmScript::Compiled(module)) = world
+        .get_resource::<Assets<WasmScript>>()
+        .and_then(|scripts| scripts.get(handle))
+    else {
+        return true;
+    };
+    manifests_for(world, handle)
+        .iter()
+        .all(|manifest| manifest.validate(module).is_empty())
+}
+
+pub struct WasmManifestAssetLoader;
+
+impl AssetLoader for WasmManifestAssetLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let descriptor: ScriptManifestDescriptor = ron::de::from_bytes(bytes)?;
+            let script_path = load_context.path().parent().map_or_else(
+                || descriptor.script.clone().into(),
+                |parent| parent.join(&descriptor.script),
+            );
+            let script_path = AssetPath::new(script_path, None);
+            let script = load_context.get_handle(script_path.clone());
+            load_context.set_default_asset(
+                LoadedAsset::new(WasmScriptManifest {
+                    name: descriptor.name,
+                    version: descriptor.version,
+                    script,
+                    entry_points: descriptor.entry_points,
+                    imports: descriptor.imports,
+                    capabilities: descriptor.capabilities,
+                    memory_limit: descriptor.memory_limit,
+                })
+                .with_dependency(script_path),
+            );
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["script.ron"]
+    }
+}
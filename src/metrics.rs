@@ -0,0 +1,279 @@
+use std::{
+    cmp::Reverse,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use bevy::{prelude::*, utils::HashMap};
+use wasmer::{Instance, Store, Value};
+
+/**
+The name of the exported global which every compiled module adds the number of operators it runs to.
+*/
+pub(crate) const FUEL_GLOBAL: &str = "__bevy_wasm_fuel";
+
+/** The calls made to one script, as measured by `WasmScriptMetrics`. */
+#[derive(Debug, Clone, Default)]
+pub struct ScriptMetrics {
+    pub calls: u64,
+    /** How many calls returned an error, including timeouts. */
+    pub errors: u64,
+    pub total: Duration,
+    pub max: Duration,
+    /** The number of wasm operators run, with the `non-js` feature. */
+    pub fuel: u64,
+}
+
+impl ScriptMetrics {
+    pub fn average(&self) -> Duration {
+        if self.calls == 0 {
+            Duration::ZERO
+        } else {
+            self.total / self.calls as u32
+        }
+    }
+}
+
+/**
+`WasmScriptMetrics` measures every call into every script, by script name, so that scripts which eat
+frame time can be found, such as from a debug overlay:
+```ignore
+fn show_metrics(metrics: Res<WasmScriptMetrics>) {
+    for (script, metrics) in metrics.by_total_time() {
+        println!("{}: {} calls, {:?} average", script, metrics.calls, metrics.average());
+    }
+}
+```
+
+Calls are counted from when the script is called to when it returns, including the host imports it
+calls. Fuel is the number of wasm operators the script ran, which doesn't depend on the machine.
+
+Each call also runs in a `wasm_call` tracing span, tagged with its `script` and `export`, as compiling
+runs in a `wasm_compile` span and instantiating in a `wasm_instantiate` span. A batch call runs in a
+single span, but still counts once per call.
+*/
+#[derive(Resource, Debug, Clone, Default)]
+pub struct WasmScriptMetrics(Arc<Mutex<HashMap<String, ScriptMetrics>>>);
+
+impl WasmScriptMetrics {
+    pub fn get(&self, script: &str) -> Option<ScriptMetrics> {
+        self.0
+            .lock()
+            .ok()
+            .and_then(|metrics| metrics.get(script).cloned())
+    }
+
+    /** The metrics of every script called since the last `reset`, by most time spent first. */
+    pub fn by_total_time(&self) -> Vec<(String, ScriptMetrics)> {
+        let mut metrics: Vec<_> = self.0.lock().map_or_else(
+            |_| Vec::new(),
+            |metrics| {
+                metrics
+                    .iter()
+                    .map(|(script, metrics)| (script.clone(), metrics.clone()))
+                    .collect()
+            },
+        );
+        metrics.sort_by_key(|(_, metrics)| Reverse(metrics.total));
+        metrics
+    }
+
+    /** Forget every measurement, such as at the start of each frame or profiling session. */
+    pub fn reset(&self) {
+        if let Ok(mut metrics) = self.0.lock() {
+            metrics.clear();
+        }
+    }
+
+    /** Run `call`, an export call to `instance`, in a tracing span, and measure it. */
+    pub(crate) fn measure<R>(
+        &self,
+        store: &mut Store,
+        instance: &Instance,
+        export: &str,
+        call: impl FnOnce(&mut Store) -> Result<R, anyhow::Error>,
+    ) -> Result<R, anyhow::Error> {
+        let mut batch = self.batch(store, instance, export);
+        let result = batch.measure(store, call);
+        batch.finish(store);
+        result
+    }
+
+    /**
+    Measure a batch of calls to the same export, in a single tracing span. The fuel is read, and the
+    metrics updated, once for the whole batch by `MeasuredBatch::finish`.
+    */
+    pub(crate) fn batch<'a>(
+        &'a self,
+        store: &mut Store,
+        instance: &'a Instance,
+        export: &str,
+    ) -> MeasuredBatch<'a> {
+        let script = instance.module().name().unwrap_or("");
+        let span = bevy::log::info_span!("wasm_call", script, export).entered();
+        MeasuredBatch {
+            metrics: self,
+            instance,
+            _span: span,
+            fuel_before: fuel(store, instance),
+            measured: ScriptMetrics::default(),
+        }
+    }
+}
+
+/** Calls being measured by `WasmScriptMetrics::batch`. */
+pub(crate) struct MeasuredBatch<'a> {
+    metrics: &'a WasmScriptMetrics,
+    instance: &'a Instance,
+    // Entered until the batch is finished.
+    _span: bevy::utils::tracing::span::EnteredSpan,
+    fuel_before: Option<i64>,
+    measured: ScriptMetrics,
+}
+
+impl MeasuredBatch<'_> {
+    pub(crate) fn measure<R>(
+        &mut self,
+        store: &mut Store,
+        call: impl FnOnce(&mut Store) -> Result<R, anyhow::Error>,
+    ) -> Result<R, anyhow::Error> {
+        let start = Instant::now();
+        let result = call(store);
+        let elapsed = start.elapsed();
+        self.measured.calls += 1;
+        self.measured.errors += result.is_err() as u64;
+        self.measured.total += elapsed;
+        self.measured.max = self.measured.max.max(elapsed);
+        result
+    }
+
+    pub(crate) fn finish(self, store: &mut Store) {
+        let measured = self.measured;
+        let fuel = fuel(store, self.instance)
+            .zip(self.fuel_before)
+            .map_or(0, |(after, before)| after.wrapping_sub(before) as u64);
+        let script = self.instance.module().name().unwrap_or("");
+        if let Ok(mut metrics) = self.metrics.0.lock() {
+            let metrics = metrics.entry(script.to_string()).or_default();
+            metrics.calls += measured.calls;
+            metrics.errors += measured.errors;
+            metrics.total += measured.total;
+            metrics.max = metrics.max.max(measured.max);
+            metrics.fuel += fuel;
+        }
+    }
+}
+
+fn fuel(store: &mut Store, instance: &Instance) -> Option<i64> {
+    match instance.exports.get_global(FUEL_GLOBAL).ok()?.get(store) {
+        Value::I64(fuel) => Some(fuel),
+        _ => None,
+    }
+}
+
+#[cfg(feature = "non-js")]
+pub(crate) use middleware::FuelMiddleware;
+
+#[cfg(feature = "non-js")]
+mod middleware {
+    use std::sync::Mutex;
+
+    use wasmer::{
+        wasmparser::Operator, FunctionMiddleware, GlobalInit, LocalFunctionIndex, MiddlewareError,
+        MiddlewareReaderState, ModuleMiddleware, Mutability, Type,
+    };
+    use wasmer_types::{ExportIndex, GlobalIndex, GlobalType, ModuleInfo};
+
+    use super::FUEL_GLOBAL;
+
+    /**
+    Adds the fuel global to every module, and adds the number of operators in each block to it
+    before the block ends or branches.
+
+    Like the `DeadlineMiddleware`, this relies on wasmer holding the engine's lock for the whole of a
+    `Module::new`, so it must only ever be added to the compiler of a single engine.
+    */
+    #[derive(Debug, Default)]
+    pub(crate) struct FuelMiddleware {
+        global_index: Mutex<Option<GlobalIndex>>,
+    }
+
+    impl ModuleMiddleware for FuelMiddleware {
+        fn generate_function_middleware(
+            &self,
+            _local_function_index: LocalFunctionIndex,
+        ) -> Box<dyn FunctionMiddleware> {
+            let global_index = self
+                .global_index
+                .lock()
+                .unwrap()
+                .expect("Fuel global not added to module");
+            Box::new(FunctionFuel {
+                global_index: global_index.as_u32(),
+                pending: 0,
+            })
+        }
+
+        fn transform_module_info(&self, module_info: &mut ModuleInfo) {
+            let global_index = module_info
+                .globals
+                .push(GlobalType::new(Type::I64, Mutability::Var));
+            module_info
+                .global_initializers
+                .push(GlobalInit::I64Const(0));
+            module_info
+                .exports
+                .insert(FUEL_GLOBAL.to_string(), ExportIndex::Global(global_index));
+            *self.global_index.lock().unwrap() = Some(global_index);
+        }
+    }
+
+    #[derive(Debug)]
+    struct FunctionFuel {
+        global_index: u32,
+        // Operators fed since fuel was last added.
+        pending: i64,
+    }
+
+    impl FunctionMiddleware for FunctionFuel {
+        fn feed<'a>(
+            &mut self,
+            operator: Operator<'a>,
+            state: &mut MiddlewareReaderState<'a>,
+        ) -> Result<(), MiddlewareError> {
+            self.pending += 1;
+            let ends_block = matches!(
+                operator,
+                Operator::Loop { .. }
+                    | Operator::Block { .. }
+                    | Operator::If { .. }
+                    | Operator::Else
+                    | Operator::End
+                    | Operator::Br { .. }
+                    | Operator::BrIf { .. }
+                    | Operator::BrTable { .. }
+                    | Operator::Return
+                    | Operator::Call { .. }
+                    | Operator::CallIndirect { .. }
+                    | Operator::Unreachable
+            );
+            if ends_block {
+                state.extend([
+                    Operator::GlobalGet {
+                        global_index: self.global_index,
+                    },
+                    Operator::I64Const {
+                        value: self.pending,
+                    },
+                    Operator::I64Add,
+                    Operator::GlobalSet {
+                        global_index: self.global_index,
+                    },
+                ]);
+                self.pending = 0;
+            }
+            state.push_operator(operator);
+            Ok(())
+        }
+    }
+}
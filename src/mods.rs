@@ -0,0 +1,564 @@
+use std::path::{Path, PathBuf};
+
+use bevy::{
+    asset::{AssetLoader, AssetPath, LoadContext, LoadState, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::{BoxedFuture, HashSet},
+};
+use serde::Deserialize;
+
+use crate::{assets::retry_instantiation, WasmScript, WasmScriptManifest};
+
+/**
+The on-disk representation of a `.mod.ron` file. Script manifest paths are relative to the mod.
+*/
+#[derive(Debug, Deserialize)]
+struct ModManifestDescriptor {
+    name: String,
+    #[serde(default)]
+    version: String,
+    #[serde(default)]
+    priority: i32,
+    #[serde(default)]
+    dependencies: Vec<String>,
+    #[serde(default)]
+    scripts: Vec<String>,
+}
+
+/**
+A `WasmModManifest` describes a single mod: its name and version, the other mods it depends on, its
+load priority, and the script manifests it provides. Mods are loaded from `.mod.ron` files.
+
+An example mod manifest:
+```ron
+(
+    name: "bouncy_balls",
+    version: "0.1.0",
+    priority: 0,
+    dependencies: ["base_physics"],
+    scripts: ["bounce.script.ron"],
+)
+```
+*/
+#[derive(Debug, TypeUuid)]
+#[uuid = "c4b1e2d7-3f6a-4e58-8a09-7d2f5b1c9e36"]
+pub struct WasmModManifest {
+    pub name: String,
+    pub version: String,
+    pub priority: i32,
+    pub dependencies: Vec<String>,
+    pub scripts: Vec<Handle<WasmScriptManifest>>,
+}
+
+pub struct WasmModManifestLoader;
+
+impl AssetLoader for WasmModManifestLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let descriptor: ModManifestDescriptor = ron::de::from_bytes(bytes)?;
+            let parent = load_context
+                .path()
+                .parent()
+                .map_or_else(PathBuf::new, Path::to_path_buf);
+            let script_paths: Vec<AssetPath<'static>> = descriptor
+                .scripts
+                .iter()
+                .map(|script| AssetPath::new(parent.join(script), None))
+                .collect();
+            let scripts = script_paths
+                .iter()
+                .map(|path| load_context.get_handle(path.clone()))
+                .collect();
+            load_context.set_default_asset(
+                LoadedAsset::new(WasmModManifest {
+                    name: descriptor.name,
+                    version: descriptor.version,
+                    priority: descriptor.priority,
+                    dependencies: descriptor.dependencies,
+                    scripts,
+                })
+                .with_dependencies(script_paths),
+            );
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["mod.ron"]
+    }
+}
+
+/**
+Why a mod was not activated.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModLoadFailure {
+    /** The mod manifest, or one of its script manifests, could not be loaded. */
+    LoadFailed,
+    /** Another mod with the same name was already found. */
+    DuplicateName,
+    /** A dependency is not present in the mods folder. */
+    MissingDependency(String),
+    /** A dependency was found, but failed to load. */
+    DependencyFailed(String),
+    /** The mod depends on itself, through one or more other mods. */
+    DependencyCycle,
+}
+
+#[derive(Debug, Clone)]
+pub struct LoadedMod {
+    pub name: String,
+    pub version: String,
+    pub path: PathBuf,
+    pub manifest: Handle<WasmModManifest>,
+    pub scripts: Vec<Handle<WasmScriptManifest>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct FailedMod {
+    pub name: Option<String>,
+    pub path: PathBuf,
+    pub reason: ModLoadFailure,
+}
+
+/**
+`LoadedMods` lists every mod found by the `WasmModsPlugin`. Once all of the mods have finished
+loading, `resolved` is set, `active` holds the mods in load order, and `failed` holds every mod which
+could not be activated.
+
+Mods are ordered so that dependencies always come before the mods depending on them. Otherwise, mods
+with a lower `priority` come first, with ties broken by name. The order is not applied to scripts by
+the plugin, and is only the order in which the host should call into them, as in the `mods` example.
+
+Scripts provided by a mod are not instantiated until every mod has been resolved, and are never
+instantiated if their mod is not active.
+*/
+#[derive(Resource, Debug, Default)]
+pub struct LoadedMods {
+    pub resolved: bool,
+    pub active: Vec<LoadedMod>,
+    pub failed: Vec<FailedMod>,
+}
+
+impl LoadedMods {
+    pub fn get(&self, name: &str) -> Option<&LoadedMod> {
+        self.active.iter().find(|loaded| loaded.name == name)
+    }
+}
+
+#[derive(Resource, Debug, Default)]
+struct DiscoveredMods(Vec<(PathBuf, Handle<WasmModManifest>)>);
+
+/**
+The `WasmModsPlugin` scans `mods_folder` (relative to the asset folder) for mods, and loads them
+through the `AssetServer`. A mod is any `.mod.ron` file, either directly in the mods folder or in one
+of its sub-folders. The results are available in the `LoadedMods` resource.
+
+This plugin should be added after the `WasmPlugin`.
+*/
+pub struct WasmModsPlugin {
+    pub mods_folder: String,
+}
+
+impl Default for WasmModsPlugin {
+    fn default() -> Self {
+        Self {
+            mods_folder: "mods".to_string(),
+        }
+    }
+}
+
+#[derive(Resource, Debug, Clone)]
+struct WasmModsFolder(PathBuf);
+
+impl Plugin for WasmModsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<WasmModManifest>()
+            .add_asset_loader(WasmModManifestLoader)
+            .insert_resource(WasmModsFolder(self.mods_folder.clone().into()))
+            .init_resource::<DiscoveredMods>()
+            .init_resource::<LoadedMods>()
+            .add_startup_system(discover_mods)
+            .add_system(resolve_mods);
+    }
+}
+
+fn is_mod_manifest(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name.to_string_lossy().ends_with(".mod.ron"))
+}
+
+fn discover_mods(
+    folder: Res<WasmModsFolder>,
+    asset_server: Res<AssetServer>,
+    mut discovered: ResMut<DiscoveredMods>,
+) {
+    let asset_io = asset_server.asset_io();
+    let entries = match asset_io.read_directory(&folder.0) {
+        Ok(entries) => entries,
+        Err(err) => {
+            bevy::log::warn!("Could not read mods folder {:?}: {}", folder.0, err);
+            return;
+        }
+    };
+    let mut manifest_paths = Vec::new();
+    for entry in entries {
+        if asset_io.is_dir(&entry) {
+            if let Ok(mod_entries) = asset_io.read_directory(&entry) {
+                manifest_paths.extend(mod_entries.filter(|path| is_mod_manifest(path)));
+            }
+        } else if is_mod_manifest(&entry) {
+            manifest_paths.push(entry);
+        }
+    }
+    manifest_paths.sort();
+    for path in manifest_paths {
+        let handle = asset_server.load(path.as_path());
+        discovered.0.push((path, handle));
+    }
+}
+
+/**
+Whether a script may be instantiated, as far as mods are concerned. Scripts which no discovered mod
+provides are always allowed.
+*/
+pub(crate) fn mod_allows(world: &World, handle: &Handle<WasmScript>) -> bool {
+    let (Some(discovered), Some(loaded_mods), Some(mods), Some(script_manifests)) = (
+        world.get_resource::<DiscoveredMods>(),
+        world.get_resource::<LoadedMods>(),
+        world.get_resource::<Assets<WasmModManifest>>(),
+        world.get_resource::<Assets<WasmScriptManifest>>(),
+    ) else {
+        return true;
+    };
+    let provides = |manifest: &Handle<WasmModManifest>| {
+        mods.get(manifest).is_some_and(|manifest| {
+            manifest.scripts.iter().any(|script| {
+                script_manifests
+                    .get(script)
+                    .is_some_and(|script| script.script == *handle)
+            })
+        })
+    };
+    if !discovered.0.iter().any(|(_, manifest)| provides(manifest)) {
+        return true;
+    }
+    loaded_mods.resolved
+        && loaded_mods
+            .active
+            .iter()
+            .any(|loaded| provides(&loaded.manifest))
+}
+
+fn resolve_mods(
+    asset_server: Res<AssetServer>,
+    manifests: Res<Assets<WasmModManifest>>,
+    script_manifests: Res<Assets<WasmScriptManifest>>,
+    mut scripts: ResMut<Assets<WasmScript>>,
+    discovered: Res<DiscoveredMods>,
+    mut loaded_mods: ResMut<LoadedMods>,
+) {
+    if loaded_mods.resolved {
+        return;
+    }
+    let mut candidates = Vec::new();
+    let mut failed = Vec::new();
+    for (path, handle) in discovered.0.iter() {
+        // Mods wait on their script manifests, and on the scripts those point to.
+        let state = match manifests.get(handle) {
+            Some(manifest) => asset_server.get_group_load_state(
+                manifest.scripts.iter().map(|script| script.id()).chain(
+                    manifest
+                        .scripts
+                        .iter()
+                        .filter_map(|script| script_manifests.get(script))
+                        .map(|script| script.script.id()),
+                ),
+            ),
+            None => asset_server.get_load_state(handle),
+        };
+        match (state, manifests.get(handle)) {
+            (LoadState::Loaded, Some(manifest)) => candidates.push(LoadedMod {
+                name: manifest.name.clone(),
+                version: manifest.version.clone(),
+                path: path.clone(),
+                manifest: handle.clone(),
+                scripts: manifest.scripts.clone(),
+            }),
+            (LoadState::Failed, manifest) => failed.push(FailedMod {
+                name: manifest.map(|manifest| manifest.name.clone()),
+                path: path.clone(),
+                reason: ModLoadFailure::LoadFailed,
+            }),
+            // Still waiting on at least one mod.
+            _ => return,
+        }
+    }
+    let (active, mut unresolved) =
+        resolve_load_order(candidates, |manifest| manifests.get(manifest), &failed);
+    failed.append(&mut unresolved);
+    for failure in failed.iter() {
+        bevy::log::warn!(
+            "Could not load mod {:?}: {:?}",
+            failure.path,
+            failure.reason
+        );
+    }
+    // Scripts which compiled while the mods were resolving were held back until now.
+    for script in active
+        .iter()
+        .flat_map(|loaded| loaded.scripts.iter())
+        .filter_map(|script| script_manifests.get(script))
+    {
+        retry_instantiation(&mut scripts, &script.script);
+    }
+    *loaded_mods = LoadedMods {
+        resolved: true,
+        active,
+        failed,
+    };
+}
+
+fn resolve_load_order<'a>(
+    mut candidates: Vec<LoadedMod>,
+    manifest_of: impl Fn(&Handle<WasmModManifest>) -> Option<&'a WasmModManifest>,
+    failed: &[FailedMod],
+) -> (Vec<LoadedMod>, Vec<FailedMod>) {
+    let mut unresolved = Vec::new();
+    let mut names = HashSet::new();
+    candidates.retain(|candidate| {
+        if names.insert(candidate.name.clone()) {
+            true
+        } else {
+            unresolved.push(FailedMod {
+                name: Some(candidate.name.clone()),
+                path: candidate.path.clone(),
+                reason: ModLoadFailure::DuplicateName,
+            });
+            false
+        }
+    });
+    let failed_names: HashSet<String> = failed
+        .iter()
+        .filter_map(|failure| failure.name.clone())
+        .collect();
+    let dependencies_of = |candidate: &LoadedMod| {
+        manifest_of(&candidate.manifest)
+            .map(|manifest| manifest.dependencies.clone())
+            .unwrap_or_default()
+    };
+    let priority_of = |candidate: &LoadedMod| {
+        manifest_of(&candidate.manifest).map_or(0, |manifest| manifest.priority)
+    };
+
+    let mut active: Vec<LoadedMod> = Vec::new();
+    loop {
+        // Fail anything depending on a mod which is missing or has failed.
+        let mut newly_failed = false;
+        candidates.retain(|candidate| {
+            let unavailable = dependencies_of(candidate)
+                .into_iter()
+                .find_map(|dependency| {
+                    if failed_names.contains(&dependency)
+                        || unresolved.iter().any(|failure| {
+                            // The first mod with a duplicated name is still loaded.
+                            failure.reason != ModLoadFailure::DuplicateName
+                                && failure.name.as_ref() == Some(&dependency)
+                        })
+                    {
+                        Some(ModLoadFailure::DependencyFailed(dependency))
+                    } else if !names.contains(&dependency) {
+                        Some(ModLoadFailure::MissingDependency(dependency))
+                    } else {
+                        None
+                    }
+                });
+            match unavailable {
+                Some(reason) => {
+                    newly_failed = true;
+                    unresolved.push(FailedMod {
+                        name: Some(candidate.name.clone()),
+                        path: candidate.path.clone(),
+                        reason,
+                    });
+                    false
+                }
+                None => true,
+            }
+        });
+        if newly_failed {
+            continue;
+        }
+        let next = candidates
+            .iter()
+            .enumerate()
+            .filter(|(_, candidate)| {
+                dependencies_of(candidate)
+                    .iter()
+                    .all(|dependency| active.iter().any(|loaded| &loaded.name == dependency))
+            })
+            .min_by(|(_, left), (_, right)| {
+                priority_of(left)
+                    .cmp(&priority_of(right))
+                    .then_with(|| left.name.cmp(&right.name))
+            })
+            .map(|(index, _)| index);
+        match next {
+            Some(index) => active.push(candidates.remove(index)),
+            None => break,
+        }
+    }
+    // Anything left over is waiting on itself.
+    unresolved.extend(candidates.into_iter().map(|candidate| FailedMod {
+        name: Some(candidate.name),
+        path: candidate.path,
+        reason: ModLoadFailure::DependencyCycle,
+    }));
+    (active, unresolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::asset::HandleId;
+
+    use super::*;
+
+    fn mods(descriptions: &[(&str, i32, &[&str])]) -> Vec<(LoadedMod, WasmModManifest)> {
+        descriptions
+            .iter()
+            .map(|(name, priority, dependencies)| {
+                let manifest = Handle::weak(HandleId::random::<WasmModManifest>());
+                (
+                    LoadedMod {
+                        name: name.to_string(),
+                        version: String::new(),
+                        path: PathBuf::from(format!("mods/{}.mod.ron", name)),
+                        manifest,
+                        scripts: Vec::new(),
+                    },
+                    WasmModManifest {
+                        name: name.to_string(),
+                        version: String::new(),
+                        priority: *priority,
+                        dependencies: dependencies.iter().map(|name| name.to_string()).collect(),
+                        scripts: Vec::new(),
+                    },
+                )
+            })
+            .collect()
+    }
+
+    fn resolve(
+        descriptions: &[(&str, i32, &[&str])],
+        failed: &[FailedMod],
+    ) -> (Vec<String>, Vec<(String, ModLoadFailure)>) {
+        let mods = mods(descriptions);
+        let candidates = mods.iter().map(|(loaded, _)| loaded.clone()).collect();
+        let (active, unresolved) = resolve_load_order(
+            candidates,
+            |handle| {
+                mods.iter()
+                    .find(|(loaded, _)| loaded.manifest == *handle)
+                    .map(|(_, manifest)| manifest)
+            },
+            failed,
+        );
+        (
+            active.into_iter().map(|loaded| loaded.name).collect(),
+            unresolved
+                .into_iter()
+                .map(|failure| (failure.name.unwrap(), failure.reason))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn dependencies_load_first() {
+        let (active, unresolved) =
+            resolve(&[("doubler", -10, &["adder"]), ("adder", 10, &[])], &[]);
+        assert_eq!(active, ["adder", "doubler"]);
+        assert!(unresolved.is_empty());
+    }
+
+    #[test]
+    fn priority_ties_are_broken_by_name() {
+        let (active, _) = resolve(&[("c", 1, &[]), ("b", 0, &[]), ("a", 1, &[])], &[]);
+        assert_eq!(active, ["b", "a", "c"]);
+    }
+
+    #[test]
+    fn missing_dependencies_fail() {
+        let (active, unresolved) = resolve(&[("a", 0, &["missing"]), ("b", 0, &["a"])], &[]);
+        assert!(active.is_empty());
+        assert_eq!(
+            unresolved,
+            [
+                (
+                    "a".to_string(),
+                    ModLoadFailure::MissingDependency("missing".to_string())
+                ),
+                (
+                    "b".to_string(),
+                    ModLoadFailure::DependencyFailed("a".to_string())
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn failed_dependencies_fail() {
+        let failed = [FailedMod {
+            name: Some("broken".to_string()),
+            path: PathBuf::from("mods/broken.mod.ron"),
+            reason: ModLoadFailure::LoadFailed,
+        }];
+        let (active, unresolved) = resolve(&[("a", 0, &["broken"])], &failed);
+        assert!(active.is_empty());
+        assert_eq!(
+            unresolved,
+            [(
+                "a".to_string(),
+                ModLoadFailure::DependencyFailed("broken".to_string())
+            )]
+        );
+    }
+
+    #[test]
+    fn cycles_fail() {
+        let (active, unresolved) =
+            resolve(&[("a", 0, &["b"]), ("b", 0, &["a"]), ("c", 0, &[])], &[]);
+        assert_eq!(active, ["c"]);
+        assert_eq!(
+            unresolved,
+            [
+                ("a".to_string(), ModLoadFailure::DependencyCycle),
+                ("b".to_string(), ModLoadFailure::DependencyCycle),
+            ]
+        );
+    }
+
+    #[test]
+    fn duplicate_names_fail() {
+        let (active, unresolved) = resolve(&[("a", 0, &[]), ("a", 1, &[])], &[]);
+        assert_eq!(active, ["a"]);
+        assert_eq!(
+            unresolved,
+            [("a".to_string(), ModLoadFailure::DuplicateName)]
+        );
+    }
+
+    #[test]
+    fn dependents_of_duplicated_names_load() {
+        let (active, unresolved) = resolve(&[("a", 0, &[]), ("a", 1, &[]), ("b", 0, &["a"])], &[]);
+        assert_eq!(active, ["a", "b"]);
+        assert_eq!(
+            unresolved,
+            [("a".to_string(), ModLoadFailure::DuplicateName)]
+        );
+    }
+}
@@ -0,0 +1,100 @@
+use bevy::prelude::*;
+use wasmer::Value;
+
+use crate::{
+    calls::IntoScriptArgs, handlers::exports_function, GeneralWasmScriptEnv, WasmScript,
+    WasmScriptComponent,
+};
+
+/** The results of `WasmScripts::call_each`, each alongside the script it was called on. */
+pub type EachResults = Vec<(Handle<WasmScript>, Result<Box<[Value]>, anyhow::Error>)>;
+
+/**
+`WasmScripts` attaches an ordered list of scripts to one entity, such as a movement script and an AI
+script, without a component type for each. The `WasmPlugin` registers it as a `WasmScriptComponent`,
+so each script is instantiated independently as soon as it loads, and event handlers added with
+`add_wasm_event_handler::<WasmScripts, _, _>` are called on each script in order.
+
+```ignore
+commands.spawn(WasmScripts::new([
+    asset_server.load("movement.wat"),
+    asset_server.load("ai.wat"),
+]));
+```
+
+Other lifecycle calls are made with `call_each`, which skips the scripts that don't export them.
+*/
+#[derive(Component, Default, Debug, Clone)]
+pub struct WasmScripts {
+    handles: Vec<Handle<WasmScript>>,
+    // Returned by `get_wasm_script_handle` while there are no scripts.
+    none: Handle<WasmScript>,
+}
+
+impl WasmScripts {
+    pub fn new(handles: impl IntoIterator<Item = Handle<WasmScript>>) -> Self {
+        Self {
+            handles: handles.into_iter().collect(),
+            ..Default::default()
+        }
+    }
+
+    /** Add a script after the existing ones. */
+    pub fn push(&mut self, handle: Handle<WasmScript>) {
+        self.handles.push(handle);
+    }
+
+    /** Remove every copy of a script, keeping the order of the rest. */
+    pub fn remove(&mut self, handle: &Handle<WasmScript>) {
+        self.handles.retain(|existing| existing != handle);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Handle<WasmScript>> {
+        self.handles.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.handles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.handles.is_empty()
+    }
+
+    /**
+    Call `function_name` with `args` on each script in order, skipping scripts which aren't
+    instantiated or don't export it. Each script's result is returned alongside its handle, so one
+    failing script doesn't stop the calls to the rest.
+    */
+    pub fn call_each(
+        &self,
+        script_env: &mut impl GeneralWasmScriptEnv,
+        function_name: &str,
+        args: impl IntoScriptArgs,
+    ) -> EachResults {
+        let args = args.into_script_args();
+        let mut results = Vec::new();
+        for handle in &self.handles {
+            if !exports_function(script_env.call_context().assets, handle, function_name) {
+                continue;
+            }
+            let result = script_env.call_if_instantiated_with_values(handle, function_name, &args);
+            results.push((handle.clone_weak(), result));
+        }
+        results
+    }
+}
+
+impl WasmScriptComponent for WasmScripts {
+    type ImportQueriedComponents = ();
+    type ImportResources = ();
+
+    /** The first script. */
+    fn get_wasm_script_handle(&self) -> &Handle<WasmScript> {
+        self.handles.first().unwrap_or(&self.none)
+    }
+
+    fn get_wasm_script_handles(&self) -> &[Handle<WasmScript>] {
+        &self.handles
+    }
+}
@@ -0,0 +1,184 @@
+use std::sync::{Arc, Mutex};
+
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use wasmer::{Extern, Function, Imports, Module, RuntimeError, Store};
+
+use crate::{manifest::manifests_for, WasmScript, WasmScriptError};
+
+/**
+The named capabilities granted to a script. Capabilities gate whole import namespaces, as registered
+with `add_wasm_capability`. A script which is not granted a capability will still instantiate, but
+every function in the gated namespaces is replaced with a stub which traps and sends a
+`WasmScriptError::CapabilityDenied` event. Memories, globals and tables in gated namespaces can't be
+stubbed, so they are left out, and a script which imports one fails to instantiate.
+
+Scripts are granted no capabilities by default. Script types grant them by overriding
+`capabilities`, which is the most any script of that type is granted. Scripts described by a
+`WasmScriptManifest` are only granted the capabilities they request, and never more than the
+script type allows, since manifests are written by the script's author.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScriptCapabilities {
+    All,
+    Only(HashSet<String>),
+}
+
+impl Default for ScriptCapabilities {
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+impl ScriptCapabilities {
+    pub fn none() -> Self {
+        Self::Only(HashSet::new())
+    }
+
+    pub fn only<S: Into<String>>(capabilities: impl IntoIterator<Item = S>) -> Self {
+        Self::Only(capabilities.into_iter().map(Into::into).collect())
+    }
+
+    pub fn allows(&self, capability: &str) -> bool {
+        match self {
+            Self::All => true,
+            Self::Only(capabilities) => capabilities.contains(capability),
+        }
+    }
+
+    pub fn intersection(&self, other: &Self) -> Self {
+        match (self, other) {
+            (Self::All, other) | (other, Self::All) => other.clone(),
+            (Self::Only(left), Self::Only(right)) => {
+                Self::Only(left.intersection(right).cloned().collect())
+            }
+        }
+    }
+}
+
+/**
+Maps import namespaces to the capability required to use them. Namespaces which are not registered
+here are available to every script.
+*/
+#[derive(Resource, Debug, Default)]
+pub struct ScriptPermissions {
+    gated_namespaces: HashMap<String, String>,
+}
+
+impl ScriptPermissions {
+    pub fn gate_namespace(&mut self, namespace: impl Into<String>, capability: impl Into<String>) {
+        self.gated_namespaces
+            .insert(namespace.into(), capability.into());
+    }
+
+    pub fn required_capability(&self, namespace: &str) -> Option<&str> {
+        self.gated_namespaces.get(namespace).map(String::as_str)
+    }
+}
+
+/**
+Errors raised from inside host functions, while a script is running, can't be sent as events
+directly. They're queued here instead, and sent by `send_pending_script_errors`.
+*/
+#[derive(Resource, Debug, Default, Clone)]
+pub(crate) struct PendingScriptErrors(pub(crate) Arc<Mutex<Vec<WasmScriptError>>>);
+
+impl PendingScriptErrors {
+    pub(crate) fn push(&self, error: WasmScriptError) {
+        if let Ok(mut pending) = self.0.lock() {
+            pending.push(error);
+        }
+    }
+}
+
+pub(crate) fn send_pending_script_errors(
+    pending: Res<PendingScriptErrors>,
+    mut script_errors: EventWriter<WasmScriptError>,
+) {
+    if let Ok(mut pending) = pending.0.lock() {
+        script_errors.send_batch(pending.drain(..));
+    }
+}
+
+/**
+The capabilities a script is granted: those allowed by its script type, narrowed to those requested
+by any manifest describing it.
+*/
+pub(crate) fn granted_capabilities(
+    world: &World,
+    handle: &Handle<WasmScript>,
+    allowed: &ScriptCapabilities,
+) -> ScriptCapabilities {
+    let describing = manifests_for(world, handle);
+    if describing.is_empty() {
+        allowed.clone()
+    } else {
+        allowed.intersection(&ScriptCapabilities::only(
+            describing
+                .iter()
+                .flat_map(|manifest| manifest.capabilities.iter().cloned()),
+        ))
+    }
+}
+
+/**
+Replace every function in a namespace the script is not granted with a denying stub of the same
+signature. Every other kind of import from such a namespace is left out.
+*/
+pub(crate) fn restrict_imports(
+    world: &World,
+    wasmer_store: &mut Store,
+    module: &Module,
+    capabilities: &ScriptCapabilities,
+    imports: Imports,
+) -> Imports {
+    let (Some(permissions), Some(pending)) = (
+        world.get_resource::<ScriptPermissions>(),
+        world.get_resource::<PendingScriptErrors>(),
+    ) else {
+        return imports;
+    };
+    let script_name = module.name().unwrap_or("");
+    let mut restricted = Imports::new();
+    for ((namespace, name), export) in &imports {
+        let capability = match permissions.required_capability(&namespace) {
+            Some(capability) if !capabilities.allows(capability) => capability,
+            _ => {
+                restricted.define(&namespace, &name, export);
+                continue;
+            }
+        };
+        let denied = WasmScriptError::CapabilityDenied {
+            script: script_name.to_string(),
+            capability: capability.to_string(),
+            namespace: namespace.clone(),
+            function: name.clone(),
+        };
+        match export {
+            Extern::Function(function) => {
+                let ty = function.ty(wasmer_store);
+                let message = format!(
+                    "{}.{} requires the {} capability",
+                    namespace, name, capability
+                );
+                let pending = pending.clone();
+                let stub = Function::new(wasmer_store, ty, move |_args| {
+                    pending.push(denied.clone());
+                    Err(RuntimeError::new(message.clone()))
+                });
+                restricted.define(&namespace, &name, stub);
+            }
+            _ => {
+                if module
+                    .imports()
+                    .any(|import| import.module() == namespace && import.name() == name)
+                {
+                    pending.push(denied);
+                }
+            }
+        }
+    }
+    restricted
+}
@@ -0,0 +1,375 @@
+use std::{any::TypeId, sync::Arc};
+
+use bevy::{
+    ecs::{
+        component::ComponentId,
+        query::{Access, FilteredAccess, ReadOnlyWorldQuery, WorldQuery},
+        world::EntityRef,
+    },
+    prelude::*,
+    utils::HashMap,
+};
+use wasmer::{imports, Function, FunctionEnv, FunctionEnvMut, Imports, Store};
+
+use crate::{
+    host::{read_guest_bytes, HostEnv},
+    stores::caller_query_access,
+    EntityId, EntityIdTrait, WasmScriptError,
+};
+
+/** The most queries a script instance may have open at once. */
+pub const MAX_OPEN_QUERIES: usize = 64;
+
+type EncodeComponent = Arc<dyn Fn(EntityRef, &mut Vec<u8>) -> bool + Send + Sync>;
+
+#[derive(Clone)]
+struct QueryableComponent {
+    name: String,
+    component_id: ComponentId,
+    size: usize,
+    encode: EncodeComponent,
+}
+
+/**
+`ScriptQueryRegistry` lists the components scripts may query, as registered with
+`add_wasm_query_component`. Each component is encoded into a fixed number of bytes when copied into
+a script's memory.
+
+Scripts query through the `query` import namespace:
+* `component_id(name_ptr: i32, name_len: i32) -> i32` looks up a registered component by name, or
+  returns -1.
+* `query(ids_ptr: i32, ids_len: i32) -> i32` opens a query over every entity with all of the given
+  components, listed as `i32` ids in the script's memory. Returns a query handle, -1 for an empty list
+  or an unknown component, -2 if the script may not read one of the components, or -3 if the script
+  already has `MAX_OPEN_QUERIES` open.
+* `query_row_size(query: i32) -> i32` is the size of each row: an 8 byte `EntityId`, followed by
+  each component's bytes in the order they were queried.
+* `query_next(query: i32, out_ptr: i32, out_len: i32) -> i32` copies as many rows as fit into the
+  buffer, returning how many were copied. Returns 0 once the query is exhausted, or -1 if rows remain
+  but the buffer is too small for one.
+* `query_close(query: i32) -> i32` releases the query.
+
+A script may only query the components its script type reads through `ImportQueriedComponents`, and
+only while it is called through the `WasmScriptComponentEnv` or `WasmScriptResourceEnv` of that type,
+since those are the systems which hold access to them. Entities excluded by the env's `Without` filter
+are left out. Queries made during any other call, such as through `WasmScriptEnv`, a script system or
+a timer, are denied. Denied queries are reported as `WasmScriptError::QueryDenied`. The matching
+entities are collected when the query is opened, and rows are only copied while the script is called
+with the same access.
+*/
+#[derive(Resource, Clone, Default)]
+pub struct ScriptQueryRegistry {
+    components: Arc<Vec<QueryableComponent>>,
+}
+
+impl ScriptQueryRegistry {
+    pub(crate) fn register<C: Component, const N: usize>(
+        &mut self,
+        name: &str,
+        component_id: ComponentId,
+        encode: impl Fn(&C) -> [u8; N] + Send + Sync + 'static,
+    ) {
+        let components = Arc::make_mut(&mut self.components);
+        let queryable = QueryableComponent {
+            name: name.to_string(),
+            component_id,
+            size: N,
+            encode: Arc::new(
+                move |entity: EntityRef, out: &mut Vec<u8>| match entity.get::<C>() {
+                    Some(component) => {
+                        out.extend_from_slice(&encode(component));
+                        true
+                    }
+                    None => false,
+                },
+            ),
+        };
+        match components.iter_mut().find(|existing| existing.name == name) {
+            Some(existing) => *existing = queryable,
+            None => components.push(queryable),
+        }
+    }
+
+    pub fn component_id(&self, name: &str) -> Option<i32> {
+        self.components
+            .iter()
+            .position(|component| component.name == name)
+            .map(|index| index as i32)
+    }
+}
+
+/**
+The components each script type reads through `ImportQueriedComponents`, by the type of the script.
+*/
+#[derive(Resource, Default)]
+pub(crate) struct ScriptQueryAccess(HashMap<TypeId, Access<ComponentId>>);
+
+pub(crate) fn register_query_access<Script: 'static, Q: WorldQuery>(world: &mut World) {
+    let state = Q::init_state(world);
+    let mut access = FilteredAccess::default();
+    Q::update_component_access(&state, &mut access);
+    world
+        .get_resource_or_insert_with(ScriptQueryAccess::default)
+        .0
+        .insert(TypeId::of::<Script>(), access.access().clone());
+}
+
+pub(crate) fn query_access_for<Script: ?Sized + 'static>(world: &World) -> Access<ComponentId> {
+    world
+        .get_resource::<ScriptQueryAccess>()
+        .and_then(|access| access.0.get(&TypeId::of::<Script>()))
+        .cloned()
+        .unwrap_or_default()
+}
+
+/**
+Whether an entity passes the `Without` filter of the env calling a script.
+*/
+pub(crate) trait EntityFilter {
+    fn allows(&self, entity: Entity) -> bool;
+}
+
+impl<F: ReadOnlyWorldQuery> EntityFilter for Query<'_, '_, Entity, F> {
+    fn allows(&self, entity: Entity) -> bool {
+        self.contains(entity)
+    }
+}
+
+/**
+The query access a system calling a script holds: that of the script type `group`, over the entities
+its filter allows.
+*/
+#[derive(Clone, Copy)]
+pub(crate) struct CallerQueryAccess<'a> {
+    pub(crate) group: TypeId,
+    pub(crate) entities: &'a dyn EntityFilter,
+}
+
+#[derive(Clone)]
+struct OpenQuery {
+    components: Vec<usize>,
+    entities: Vec<Entity>,
+    cursor: usize,
+}
+
+/**
+The queries a single script instance has open, and what it may query.
+*/
+#[derive(Clone)]
+pub(crate) struct ScriptQueries {
+    registry: ScriptQueryRegistry,
+    access: Access<ComponentId>,
+    group: TypeId,
+    open: Vec<Option<OpenQuery>>,
+}
+
+impl ScriptQueries {
+    pub(crate) fn new(world: &World, access: Access<ComponentId>, group: TypeId) -> Self {
+        Self {
+            registry: world
+                .get_resource::<ScriptQueryRegistry>()
+                .cloned()
+                .unwrap_or_default(),
+            access,
+            group,
+            open: Vec::new(),
+        }
+    }
+
+    /** The access of the current caller, if it holds this script's query access. */
+    fn caller(&self) -> Option<CallerQueryAccess<'static>> {
+        caller_query_access().filter(|caller| caller.group == self.group)
+    }
+
+    /** Whether the script may read the component of the entity during the current call. */
+    pub(crate) fn may_read(&self, component_id: ComponentId, entity: Entity) -> bool {
+        self.access.has_read(component_id)
+            && self
+                .caller()
+                .is_some_and(|caller| caller.entities.allows(entity))
+    }
+
+    fn get(&self, query: i32) -> Option<&OpenQuery> {
+        usize::try_from(query)
+            .ok()
+            .and_then(|index| self.open.get(index))
+            .and_then(Option::as_ref)
+    }
+
+    fn row_size(&self, query: &OpenQuery) -> usize {
+        std::mem::size_of::<EntityId>()
+            + query
+                .components
+                .iter()
+                .map(|index| self.registry.components[*index].size)
+                .sum::<usize>()
+    }
+}
+
+fn component_id(env: FunctionEnvMut<HostEnv>, name_ptr: i32, name_len: i32) -> i32 {
+    read_guest_bytes(&env, name_ptr, name_len)
+        .and_then(|name| String::from_utf8(name).ok())
+        .and_then(|name| env.data().queries.registry.component_id(&name))
+        .unwrap_or(-1)
+}
+
+fn query(mut env: FunctionEnvMut<HostEnv>, ids_ptr: i32, ids_len: i32) -> i32 {
+    if ids_len <= 0 {
+        return -1;
+    }
+    let Some(ids) = read_guest_bytes(&env, ids_ptr, ids_len.saturating_mul(4)) else {
+        return -1;
+    };
+    let host = env.data();
+    let caller = host.queries.caller();
+    let mut components = Vec::new();
+    for id in ids.chunks_exact(4) {
+        let id = i32::from_le_bytes([id[0], id[1], id[2], id[3]]);
+        let Some(index) = usize::try_from(id)
+            .ok()
+            .filter(|index| *index < host.queries.registry.components.len())
+        else {
+            return -1;
+        };
+        let component = &host.queries.registry.components[index];
+        if caller.is_none() || !host.queries.access.has_read(component.component_id) {
+            host.report(WasmScriptError::QueryDenied {
+                script: host.script.clone(),
+                component: component.name.clone(),
+            });
+            return -2;
+        }
+        components.push(index);
+    }
+    let Some(caller) = caller else {
+        return -2;
+    };
+    let open_queries = host
+        .queries
+        .open
+        .iter()
+        .filter(|open| open.is_some())
+        .count();
+    if open_queries >= MAX_OPEN_QUERIES {
+        return -3;
+    }
+    let component_ids: Vec<ComponentId> = components
+        .iter()
+        .map(|index| host.queries.registry.components[*index].component_id)
+        .collect();
+    let entities = host
+        .world
+        .read()
+        .archetypes()
+        .iter()
+        .filter(|archetype| component_ids.iter().all(|id| archetype.contains(*id)))
+        .flat_map(|archetype| archetype.entities().iter().map(|entity| entity.entity()))
+        .filter(|entity| caller.entities.allows(*entity))
+        .collect();
+    let opened = OpenQuery {
+        components,
+        entities,
+        cursor: 0,
+    };
+    let open = &mut env.data_mut().queries.open;
+    match open.iter().position(Option::is_none) {
+        Some(index) => {
+            open[index] = Some(opened);
+            index as i32
+        }
+        None => {
+            open.push(Some(opened));
+            open.len() as i32 - 1
+        }
+    }
+}
+
+fn query_row_size(env: FunctionEnvMut<HostEnv>, query: i32) -> i32 {
+    let queries = &env.data().queries;
+    queries
+        .get(query)
+        .map_or(-1, |open| queries.row_size(open) as i32)
+}
+
+fn query_next(mut env: FunctionEnvMut<HostEnv>, query: i32, out_ptr: i32, out_len: i32) -> i32 {
+    let (Some(memory), Ok(out_ptr), Ok(out_len)) = (
+        env.data().memory.clone(),
+        u64::try_from(out_ptr),
+        usize::try_from(out_len),
+    ) else {
+        return -1;
+    };
+    let host = env.data();
+    let Some(open) = host.queries.get(query) else {
+        return -1;
+    };
+    let Some(caller) = host.queries.caller() else {
+        return -1;
+    };
+    let row_size = host.queries.row_size(open);
+    let capacity = out_len / row_size;
+    if capacity == 0 && open.cursor < open.entities.len() {
+        return -1;
+    }
+    let world = host.world.read();
+    let mut rows = Vec::with_capacity(capacity.min(open.entities.len()) * row_size);
+    let mut copied = 0;
+    let mut cursor = open.cursor;
+    while copied < capacity && cursor < open.entities.len() {
+        let entity = open.entities[cursor];
+        cursor += 1;
+        let Some(entity_ref) = world
+            .get_entity(entity)
+            .filter(|_| caller.entities.allows(entity))
+        else {
+            continue;
+        };
+        let start = rows.len();
+        rows.extend_from_slice(&EntityId::from_entity(entity).to_le_bytes());
+        let complete = open
+            .components
+            .iter()
+            .all(|index| (host.queries.registry.components[*index].encode)(entity_ref, &mut rows));
+        if complete {
+            copied += 1;
+        } else {
+            // The entity lost a component since the query was opened.
+            rows.truncate(start);
+        }
+    }
+    if memory.view(&env).write(out_ptr, &rows).is_err() {
+        return -1;
+    }
+    if let Some(Some(open)) = usize::try_from(query)
+        .ok()
+        .and_then(|index| env.data_mut().queries.open.get_mut(index))
+    {
+        open.cursor = cursor;
+    }
+    copied as i32
+}
+
+fn query_close(mut env: FunctionEnvMut<HostEnv>, query: i32) -> i32 {
+    match usize::try_from(query)
+        .ok()
+        .and_then(|index| env.data_mut().queries.open.get_mut(index))
+    {
+        Some(open @ Some(_)) => {
+            *open = None;
+            0
+        }
+        _ => -1,
+    }
+}
+
+pub(crate) fn script_query_imports(store: &mut Store, env: &FunctionEnv<HostEnv>) -> Imports {
+    imports! {
+        "query" => {
+            "component_id" => Function::new_typed_with_env(store, env, component_id),
+            "query" => Function::new_typed_with_env(store, env, query),
+            "query_row_size" => Function::new_typed_with_env(store, env, query_row_size),
+            "query_next" => Function::new_typed_with_env(store, env, query_next),
+            "query_close" => Function::new_typed_with_env(store, env, query_close),
+        }
+    }
+}
@@ -0,0 +1,467 @@
+use std::{
+    cell::RefCell,
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use bevy::{prelude::*, utils::HashSet};
+use serde::{Deserialize, Serialize};
+use wasmer::{
+    Extern, Function, FunctionEnv, FunctionEnvMut, Imports, Instance, Store, Type, Value,
+    WasmTypeList,
+};
+
+use crate::{snapshots::snapshot_raw, ScriptSnapshot, SnapshotValue, WasmScript};
+
+/** A host import called by a script, with the values it returned or the error it trapped with. */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedImport {
+    pub namespace: String,
+    pub name: String,
+    pub args: Vec<SnapshotValue>,
+    pub results: Result<Vec<SnapshotValue>, String>,
+}
+
+/** One line of a recording made by the `WasmCallRecorder`. */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum RecordedEntry {
+    /** A call into a script, with every host import it called, in order. */
+    Call {
+        script: String,
+        export: String,
+        args: Vec<SnapshotValue>,
+        imports: Vec<RecordedImport>,
+        results: Result<Vec<SnapshotValue>, String>,
+    },
+    /** A host import called outside of a recorded call, such as by a script's start function. */
+    Import {
+        script: String,
+        import: RecordedImport,
+    },
+    /**
+    The state of a script's instance before its first recorded call, which replays start from. Always
+    a `ScriptSnapshot::Raw`.
+    */
+    Snapshot {
+        script: String,
+        snapshot: ScriptSnapshot,
+    },
+}
+
+impl RecordedEntry {
+    pub fn script(&self) -> &str {
+        match self {
+            Self::Call { script, .. }
+            | Self::Import { script, .. }
+            | Self::Snapshot { script, .. } => script,
+        }
+    }
+}
+
+#[derive(Default)]
+struct RecorderShared {
+    recording: AtomicBool,
+    output: Mutex<Option<BufWriter<File>>>,
+    // The scripts, by name, whose imports were wrapped to be recorded when they were instantiated.
+    wrapped: Mutex<HashSet<String>>,
+    // The scripts, by name, whose instance has been snapshotted since recording last started.
+    snapshotted: Mutex<HashSet<String>>,
+}
+
+/**
+`WasmCallRecorder` records script calls to a file, to reproduce misbehaving scripts offline with a
+`WasmReplay`. While recording, every call made through `GeneralWasmScriptEnv`, a `ScriptFunction` or
+`WasmCoroutines` is written with its script, export, arguments and results, along with every host
+import the script called and the values it returned. Before a script's first call in a recording,
+the state of its instance is written too, so that replays start from where the script was rather
+than from a fresh instance.
+
+Recording host imports means wrapping each of them, which slows every import call down, so imports
+are only wrapped for scripts instantiated while recording. Calls to scripts instantiated before
+recording started are not recorded, as their replays would have no imports to return; start
+recording before the scripts to record are loaded:
+```ignore
+let recorder = WasmCallRecorder::default();
+recorder.start("scripts.rec")?;
+app.insert_resource(recorder);
+```
+
+Recordings are RON, one `RecordedEntry` per line, and can be read with `load`. Only numeric values
+are recorded.
+*/
+#[derive(Resource, Clone, Default)]
+pub struct WasmCallRecorder(Arc<RecorderShared>);
+
+impl WasmCallRecorder {
+    /** Start recording to `path`, replacing any recording already there. */
+    pub fn start(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let file = BufWriter::new(File::create(path)?);
+        if let Ok(mut snapshotted) = self.0.snapshotted.lock() {
+            snapshotted.clear();
+        }
+        if let Ok(mut output) = self.0.output.lock() {
+            if let Some(mut previous) = output.replace(file) {
+                previous.flush()?;
+            }
+        }
+        self.0.recording.store(true, Ordering::Release);
+        Ok(())
+    }
+
+    /** Stop recording, and flush the recording to its file. */
+    pub fn stop(&self) -> io::Result<()> {
+        self.0.recording.store(false, Ordering::Release);
+        match self
+            .0
+            .output
+            .lock()
+            .ok()
+            .and_then(|mut output| output.take())
+        {
+            Some(mut output) => output.flush(),
+            None => Ok(()),
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.0.recording.load(Ordering::Acquire)
+    }
+
+    /** Read a recording made by `start`. */
+    pub fn load(path: impl AsRef<Path>) -> Result<Vec<RecordedEntry>, anyhow::Error> {
+        let mut entries = Vec::new();
+        for line in BufReader::new(File::open(path)?).lines() {
+            let line = line?;
+            if !line.trim().is_empty() {
+                entries.push(ron::from_str(&line)?);
+            }
+        }
+        Ok(entries)
+    }
+
+    /** Whether calls to the named script can be recorded, with its imports. */
+    fn records(&self, script: &str) -> bool {
+        self.is_recording()
+            && self
+                .0
+                .wrapped
+                .lock()
+                .is_ok_and(|wrapped| wrapped.contains(script))
+    }
+
+    /** Write a snapshot of the script's instance, unless one has been written since recording started. */
+    fn snapshot_once(&self, script: &str, store: &mut Store, instance: &Instance) {
+        let first = self
+            .0
+            .snapshotted
+            .lock()
+            .is_ok_and(|mut snapshotted| snapshotted.insert(script.to_string()));
+        if first {
+            self.write(&RecordedEntry::Snapshot {
+                script: script.to_string(),
+                snapshot: snapshot_raw(store, instance),
+            });
+        }
+    }
+
+    fn write(&self, entry: &RecordedEntry) {
+        let Ok(mut output) = self.0.output.lock() else {
+            return;
+        };
+        let Some(output) = output.as_mut() else {
+            return;
+        };
+        let written = ron::to_string(entry)
+            .map_err(io::Error::other)
+            .and_then(|line| writeln!(output, "{}", line));
+        if let Err(err) = written {
+            bevy::log::error!("Failed to record a script call: {}", err);
+        }
+    }
+}
+
+struct PendingCall {
+    script: String,
+    export: String,
+    args: Vec<SnapshotValue>,
+    imports: Vec<RecordedImport>,
+}
+
+thread_local! {
+    // The recorded calls running on this thread, innermost last.
+    static PENDING_CALLS: RefCell<Vec<PendingCall>> = const { RefCell::new(Vec::new()) };
+}
+
+/**
+A call being recorded. Host imports called until it is finished are recorded as part of it. Calls
+which fail before they reach the script are dropped without being recorded.
+*/
+pub(crate) struct CallRecording {
+    recorder: WasmCallRecorder,
+    depth: usize,
+}
+
+impl Drop for CallRecording {
+    fn drop(&mut self) {
+        PENDING_CALLS.with(|pending| pending.borrow_mut().truncate(self.depth));
+    }
+}
+
+/** Start recording a call, if the recorder is recording the script. */
+pub(crate) fn begin_call(
+    recorder: Option<&WasmCallRecorder>,
+    assets: &Assets<WasmScript>,
+    handle: &Handle<WasmScript>,
+    export: &str,
+    store: &mut Store,
+    instance: &Instance,
+) -> Option<CallRecording> {
+    script_recorder(recorder, assets, handle, store, instance)
+        .map(|recorder| recorder.begin(export))
+}
+
+/**
+The recorder, if it is recording the script, and the name of the script, looked up once for a batch
+of calls. The script's instance is snapshotted first if this is its first call in the recording.
+*/
+pub(crate) fn script_recorder(
+    recorder: Option<&WasmCallRecorder>,
+    assets: &Assets<WasmScript>,
+    handle: &Handle<WasmScript>,
+    store: &mut Store,
+    instance: &Instance,
+) -> Option<ScriptRecorder> {
+    let recorder = recorder.filter(|recorder| recorder.is_recording())?;
+    let script = assets.get(handle).map(WasmScript::name).unwrap_or_default();
+    if !recorder.records(&script) {
+        return None;
+    }
+    recorder.snapshot_once(&script, store, instance);
+    Some(ScriptRecorder {
+        recorder: recorder.clone(),
+        script,
+    })
+}
+
+pub(crate) struct ScriptRecorder {
+    recorder: WasmCallRecorder,
+    script: String,
+}
+
+impl ScriptRecorder {
+    /** Start recording a call to the script. */
+    pub(crate) fn begin(&self, export: &str) -> CallRecording {
+        let depth = PENDING_CALLS.with(|pending| {
+            let mut pending = pending.borrow_mut();
+            pending.push(PendingCall {
+                script: self.script.clone(),
+                export: export.to_string(),
+                args: Vec::new(),
+                imports: Vec::new(),
+            });
+            pending.len() - 1
+        });
+        CallRecording {
+            recorder: self.recorder.clone(),
+            depth,
+        }
+    }
+}
+
+fn recorded_values(values: &[Value]) -> Vec<SnapshotValue> {
+    values
+        .iter()
+        .filter_map(|value| SnapshotValue::from_value(value.clone()))
+        .collect()
+}
+
+/**
+The values of a typed argument or result list. Lists holding anything other than numbers are not
+converted, and have no values.
+*/
+fn list_values<L: WasmTypeList>(store: &mut Store, list: L) -> (L, Vec<Value>) {
+    let numeric = L::wasm_types()
+        .iter()
+        .all(|ty| matches!(ty, Type::I32 | Type::I64 | Type::F32 | Type::F64));
+    if !numeric {
+        return (list, Vec::new());
+    }
+    // SAFETY: Numbers are converted to raw values and back unchanged.
+    unsafe {
+        let mut array = list.into_array(store);
+        let values = L::wasm_types()
+            .iter()
+            .zip(array.as_mut().iter())
+            .map(|(ty, raw)| Value::from_raw(store, *ty, *raw))
+            .collect();
+        (L::from_array(store, array), values)
+    }
+}
+
+fn with_call(recording: &CallRecording, update: impl FnOnce(&mut PendingCall)) {
+    PENDING_CALLS.with(|pending| {
+        if let Some(call) = pending.borrow_mut().get_mut(recording.depth) {
+            update(call);
+        }
+    });
+}
+
+/** Record the arguments of a typed call, returning them unchanged. */
+pub(crate) fn record_args<Args: WasmTypeList>(
+    store: &mut Store,
+    recording: &Option<CallRecording>,
+    args: Args,
+) -> Args {
+    let Some(recording) = recording else {
+        return args;
+    };
+    let (args, values) = list_values(store, args);
+    with_call(recording, |call| call.args = recorded_values(&values));
+    args
+}
+
+pub(crate) fn record_value_args(recording: &Option<CallRecording>, args: &[Value]) {
+    if let Some(recording) = recording {
+        with_call(recording, |call| call.args = recorded_values(args));
+    }
+}
+
+fn finish(recording: CallRecording, results: Result<Vec<SnapshotValue>, String>) {
+    let call = PENDING_CALLS.with(|pending| {
+        let mut pending = pending.borrow_mut();
+        pending.truncate(recording.depth + 1);
+        pending.pop()
+    });
+    if let Some(call) = call {
+        recording.recorder.write(&RecordedEntry::Call {
+            script: call.script,
+            export: call.export,
+            args: call.args,
+            imports: call.imports,
+            results,
+        });
+    }
+}
+
+/** Record the results of a typed call, returning them unchanged. */
+pub(crate) fn finish_call<Rets: WasmTypeList>(
+    store: &mut Store,
+    recording: Option<CallRecording>,
+    result: Result<Rets, anyhow::Error>,
+) -> Result<Rets, anyhow::Error> {
+    let Some(recording) = recording else {
+        return result;
+    };
+    match result {
+        Ok(rets) => {
+            let (rets, values) = list_values(store, rets);
+            finish(recording, Ok(recorded_values(&values)));
+            Ok(rets)
+        }
+        Err(err) => {
+            finish(recording, Err(err.to_string()));
+            Err(err)
+        }
+    }
+}
+
+pub(crate) fn finish_value_call(
+    recording: Option<CallRecording>,
+    result: Result<Box<[Value]>, anyhow::Error>,
+) -> Result<Box<[Value]>, anyhow::Error> {
+    if let Some(recording) = recording {
+        let results = match &result {
+            Ok(values) => Ok(recorded_values(values)),
+            Err(err) => Err(err.to_string()),
+        };
+        finish(recording, results);
+    }
+    result
+}
+
+fn record_import(recorder: &WasmCallRecorder, script: &str, import: RecordedImport) {
+    let import = PENDING_CALLS.with(|pending| match pending.borrow_mut().last_mut() {
+        Some(call) => {
+            call.imports.push(import);
+            None
+        }
+        None => Some(import),
+    });
+    if let Some(import) = import {
+        recorder.write(&RecordedEntry::Import {
+            script: script.to_string(),
+            import,
+        });
+    }
+}
+
+/**
+Wrap every imported function so that its calls are recorded, if a `WasmCallRecorder` is recording.
+The imports of scripts instantiated while not recording are left as they are.
+*/
+pub(crate) fn record_imports(
+    world: &World,
+    wasmer_store: &mut Store,
+    script_name: &str,
+    imports: Imports,
+) -> Imports {
+    let Some(recorder) = world.get_resource::<WasmCallRecorder>() else {
+        return imports;
+    };
+    // The script is being instantiated again, so the next call records the state of the new instance.
+    if let Ok(mut snapshotted) = recorder.0.snapshotted.lock() {
+        snapshotted.remove(script_name);
+    }
+    if let Ok(mut wrapped) = recorder.0.wrapped.lock() {
+        if !recorder.is_recording() {
+            wrapped.remove(script_name);
+            return imports;
+        }
+        wrapped.insert(script_name.to_string());
+    }
+    let env = FunctionEnv::new(wasmer_store, ());
+    let mut recorded = Imports::new();
+    for ((namespace, name), export) in &imports {
+        let Extern::Function(function) = export else {
+            recorded.define(&namespace, &name, export);
+            continue;
+        };
+        let ty = function.ty(wasmer_store);
+        let recorder = recorder.clone();
+        let script = script_name.to_string();
+        let import_namespace = namespace.clone();
+        let import_name = name.clone();
+        let wrapper = Function::new_with_env(
+            wasmer_store,
+            &env,
+            ty,
+            move |mut env: FunctionEnvMut<()>, args: &[Value]| {
+                let result = function.call(&mut env, args);
+                if recorder.is_recording() {
+                    let results = match &result {
+                        Ok(values) => Ok(recorded_values(values)),
+                        Err(err) => Err(err.message()),
+                    };
+                    record_import(
+                        &recorder,
+                        &script,
+                        RecordedImport {
+                            namespace: import_namespace.clone(),
+                            name: import_name.clone(),
+                            args: recorded_values(args),
+                            results,
+                        },
+                    );
+                }
+                result.map(Vec::from)
+            },
+        );
+        recorded.define(&namespace, &name, wrapper);
+    }
+    recorded
+}
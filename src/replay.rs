@@ -0,0 +1,351 @@
+use std::{
+    collections::VecDeque,
+    fmt::Display,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use wasmer::{ExternType, Function, Imports, Instance, Module, RuntimeError, Store, Value};
+
+use crate::{
+    snapshots::restore_raw, RecordedEntry, RecordedImport, ScriptSnapshot, SnapshotValue,
+    WasmCallRecorder, WasmDeterminism, WasmWatchdog,
+};
+
+type RecordedResults = Result<Vec<SnapshotValue>, String>;
+
+/** A difference between a replayed call and its recording. */
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplayMismatch {
+    /** The call returned different results, or failed when it succeeded, or the other way around. */
+    Results {
+        call: usize,
+        export: String,
+        recorded: RecordedResults,
+        replayed: RecordedResults,
+    },
+    /**
+    The script called a different host import than it did when recorded, or more of them. The call is
+    interrupted, since there is no recorded result to return.
+    */
+    Import {
+        call: usize,
+        recorded: Option<String>,
+        replayed: String,
+    },
+    /** The script called the same host import, but with different arguments. */
+    ImportArgs {
+        call: usize,
+        import: String,
+        recorded: Vec<SnapshotValue>,
+        replayed: Vec<SnapshotValue>,
+    },
+    /** The script called fewer host imports than it did when recorded. */
+    MissingImports { call: usize, remaining: usize },
+}
+
+impl Display for ReplayMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Results {
+                call,
+                export,
+                recorded,
+                replayed,
+            } => write!(
+                f,
+                "call {} to {} returned {:?}, but {:?} when recorded",
+                call, export, replayed, recorded
+            ),
+            Self::Import {
+                call,
+                recorded: Some(recorded),
+                replayed,
+            } => write!(
+                f,
+                "call {} imported {}, but {} when recorded",
+                call, replayed, recorded
+            ),
+            Self::Import {
+                call,
+                recorded: None,
+                replayed,
+            } => write!(
+                f,
+                "call {} imported {}, after every recorded import",
+                call, replayed
+            ),
+            Self::ImportArgs {
+                call,
+                import,
+                recorded,
+                replayed,
+            } => write!(
+                f,
+                "call {} imported {} with {:?}, but {:?} when recorded",
+                call, import, replayed, recorded
+            ),
+            Self::MissingImports { call, remaining } => write!(
+                f,
+                "call {} made {} fewer imports than when recorded",
+                call, remaining
+            ),
+        }
+    }
+}
+
+/** The outcome of a `WasmReplay`. */
+#[derive(Debug, Clone, Default)]
+pub struct ReplayReport {
+    /** How many calls were replayed. */
+    pub calls: usize,
+    pub mismatches: Vec<ReplayMismatch>,
+}
+
+impl ReplayReport {
+    /** Whether every call replayed exactly as recorded. */
+    pub fn matched(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+#[derive(Default)]
+struct ReplayState {
+    call: usize,
+    imports: VecDeque<RecordedImport>,
+    mismatches: Vec<ReplayMismatch>,
+}
+
+fn same_values(recorded: &[SnapshotValue], replayed: &[SnapshotValue]) -> bool {
+    // Compared by bits, so that a NaN matches the same NaN.
+    let bits = |value: &SnapshotValue| match *value {
+        SnapshotValue::I32(value) => (0, value as u64),
+        SnapshotValue::I64(value) => (1, value as u64),
+        SnapshotValue::F32(value) => (2, value.to_bits() as u64),
+        SnapshotValue::F64(value) => (3, value.to_bits()),
+    };
+    recorded.len() == replayed.len() && recorded.iter().map(bits).eq(replayed.iter().map(bits))
+}
+
+fn same_results(recorded: &RecordedResults, replayed: &RecordedResults) -> bool {
+    match (recorded, replayed) {
+        (Ok(recorded), Ok(replayed)) => same_values(recorded, replayed),
+        // Error messages include traces which differ between runs.
+        (Err(_), Err(_)) => true,
+        _ => false,
+    }
+}
+
+fn to_values(values: &[SnapshotValue]) -> Vec<Value> {
+    values.iter().map(|value| value.to_value()).collect()
+}
+
+fn from_values(values: &[Value]) -> Vec<SnapshotValue> {
+    values
+        .iter()
+        .filter_map(|value| SnapshotValue::from_value(value.clone()))
+        .collect()
+}
+
+fn replay_import(
+    state: &Mutex<ReplayState>,
+    import: &str,
+    args: &[Value],
+) -> Result<Vec<Value>, RuntimeError> {
+    let mut state = state
+        .lock()
+        .map_err(|_| RuntimeError::new("Replay state poisoned by an earlier panic."))?;
+    let call = state.call;
+    let recorded = match state.imports.pop_front() {
+        Some(recorded) if format!("{}.{}", recorded.namespace, recorded.name) == import => recorded,
+        recorded => {
+            state.mismatches.push(ReplayMismatch::Import {
+                call,
+                recorded: recorded
+                    .map(|recorded| format!("{}.{}", recorded.namespace, recorded.name)),
+                replayed: import.to_string(),
+            });
+            return Err(RuntimeError::new("The replay diverged from the recording."));
+        }
+    };
+    let replayed = from_values(args);
+    if !same_values(&recorded.args, &replayed) {
+        state.mismatches.push(ReplayMismatch::ImportArgs {
+            call,
+            import: import.to_string(),
+            recorded: recorded.args.clone(),
+            replayed,
+        });
+    }
+    match recorded.results {
+        Ok(results) => Ok(to_values(&results)),
+        Err(message) => Err(RuntimeError::new(message)),
+    }
+}
+
+/**
+`WasmReplay` re-runs the calls a `WasmCallRecorder` recorded for one script, without a game world.
+Instead of calling into the game, each host import returns what it returned when recorded, so a
+misbehaving script can be reproduced and debugged offline:
+```ignore
+let replay = WasmReplay::load("scripts.rec", "patrol")?;
+let report = replay.run(&std::fs::read("assets/patrol.wasm")?)?;
+for mismatch in report.mismatches.iter() {
+    println!("{}", mismatch);
+}
+```
+
+Scripts are found in the recording by name, which is the file stem of their asset. Replays start from
+the state the script was in when recording started, restoring the snapshot recorded before its first
+call. Scripts are compiled as configured by `with_determinism`, which should match the
+`WasmDeterminism` of the game that recorded them, as float results are compared bit for bit and only
+deterministic mode canonicalizes NaNs.
+
+Each replayed call is interrupted once it runs longer than `with_timeout`, ten seconds by default, so
+a call which timed out when recorded replays as the same failure rather than running forever. This
+needs the `non-js` feature, without which calls can't be interrupted.
+
+Coroutines are recorded as calls, but the host suspends and resumes them by calling the asyncify
+exports from inside its `coroutine` imports, which replays don't do, so a coroutine which suspends
+replays as if its waits returned immediately.
+*/
+#[derive(Debug, Clone)]
+pub struct WasmReplay {
+    entries: Vec<RecordedEntry>,
+    determinism: WasmDeterminism,
+    timeout: Duration,
+}
+
+impl WasmReplay {
+    /** Replay the entries recorded for `script`. */
+    pub fn new(script: &str, entries: impl IntoIterator<Item = RecordedEntry>) -> Self {
+        Self {
+            entries: entries
+                .into_iter()
+                .filter(|entry| entry.script() == script)
+                .collect(),
+            determinism: WasmDeterminism::default(),
+            timeout: Duration::from_secs(10),
+        }
+    }
+
+    /** Interrupt replayed calls which run longer than `timeout`. */
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /** Compile the script as it is compiled with `determinism`. */
+    pub fn with_determinism(mut self, determinism: WasmDeterminism) -> Self {
+        self.determinism = determinism;
+        self
+    }
+
+    /** Replay the entries recorded for `script` in the recording at `path`. */
+    pub fn load(path: impl AsRef<Path>, script: &str) -> Result<Self, anyhow::Error> {
+        Ok(Self::new(script, WasmCallRecorder::load(path)?))
+    }
+
+    /** Instantiate the script from its `.wasm` or `.wat` source, and replay every recorded call. */
+    pub fn run(&self, wasm: &[u8]) -> Result<ReplayReport, anyhow::Error> {
+        #[cfg(feature = "non-js")]
+        let mut store = Store::new(crate::script_engine(&self.determinism));
+        #[cfg(feature = "js")]
+        let mut store = Store::default();
+        let module = Module::new(&store, wasm)?;
+        let state = Arc::new(Mutex::new(ReplayState::default()));
+        // Imports recorded before the first call were made while instantiating, by a start function.
+        if let Ok(mut state) = state.lock() {
+            state.imports = self
+                .entries
+                .iter()
+                .map_while(|entry| match entry {
+                    RecordedEntry::Import { import, .. } => Some(import.clone()),
+                    RecordedEntry::Call { .. } | RecordedEntry::Snapshot { .. } => None,
+                })
+                .collect();
+        }
+        let mut imports = Imports::new();
+        for import in module.imports() {
+            let name = format!("{}.{}", import.module(), import.name());
+            let ExternType::Function(ty) = import.ty() else {
+                return Err(anyhow::Error::msg(format!(
+                    "{} is not a function; only imported functions can be replayed.",
+                    name
+                )));
+            };
+            let state = state.clone();
+            let function = Function::new(&mut store, ty, move |args| {
+                replay_import(&state, &name, args)
+            });
+            imports.define(import.module(), import.name(), function);
+        }
+        let instance = Instance::new(&mut store, &module, &imports)?;
+        let watchdog = WasmWatchdog::with_timeout(self.timeout);
+        let mut report = ReplayReport::default();
+        for entry in self.entries.iter() {
+            let (export, args, imports, results) = match entry {
+                RecordedEntry::Call {
+                    export,
+                    args,
+                    imports,
+                    results,
+                    ..
+                } => (export, args, imports, results),
+                RecordedEntry::Snapshot {
+                    snapshot: ScriptSnapshot::Raw { memory, globals },
+                    ..
+                } => {
+                    restore_raw(&mut store, &instance, memory, globals)?;
+                    continue;
+                }
+                RecordedEntry::Snapshot { .. } => {
+                    return Err(anyhow::Error::msg(
+                        "Only raw snapshots of a script can be replayed.",
+                    ));
+                }
+                RecordedEntry::Import { .. } => continue,
+            };
+            let call = report.calls;
+            if let Ok(mut state) = state.lock() {
+                state.call = call;
+                state.imports = imports.iter().cloned().collect();
+            }
+            let replayed = instance
+                .exports
+                .get_function(export)
+                .map_err(anyhow::Error::new)
+                .and_then(|function| {
+                    // Not in deterministic mode, which would leave the call unguarded.
+                    watchdog.guard(None, &mut store, &instance, export, |store| {
+                        function.call(store, &to_values(args))
+                    })
+                })
+                .map(|values| from_values(&values))
+                .map_err(|err| err.to_string());
+            let Ok(mut state) = state.lock() else {
+                return Err(anyhow::Error::msg(
+                    "Replay state poisoned by an earlier panic.",
+                ));
+            };
+            report.mismatches.append(&mut state.mismatches);
+            if !same_results(results, &replayed) {
+                report.mismatches.push(ReplayMismatch::Results {
+                    call,
+                    export: export.clone(),
+                    recorded: results.clone(),
+                    replayed,
+                });
+            }
+            if !state.imports.is_empty() {
+                report.mismatches.push(ReplayMismatch::MissingImports {
+                    call,
+                    remaining: state.imports.len(),
+                });
+            }
+            report.calls += 1;
+        }
+        Ok(report)
+    }
+}
@@ -1,15 +1,29 @@
+use std::any::TypeId;
+
 use anyhow::anyhow;
 use bevy::{
     ecs::{
+        component::ComponentId,
         event::ManualEventReader,
-        query::WorldQuery,
+        query::{Access, WorldQuery},
         system::{SystemParam, SystemState},
     },
     prelude::*,
 };
 use wasmer::{imports, Imports, Instance, Module};
 
-use crate::{WasmScript, WasmerStore, WorldPointer};
+use crate::{
+    assets::script_instantiated,
+    host::HostImports,
+    limits::smallest_limit,
+    manifest::{manifests_for, matches_manifests},
+    mods::mod_allows,
+    permissions::{granted_capabilities, restrict_imports},
+    queries::query_access_for,
+    recording::record_imports,
+    stores::{assign_group_store, group_store},
+    ScriptCapabilities, WasmMemoryLimits, WasmScript, WasmerStore, WorldPointer,
+};
 
 fn instantiate_with_imports(
     wasmer_store: &mut WasmerStore,
@@ -24,22 +38,64 @@ fn instantiate_if_compiled(
     world: &mut World,
     wasm_script_handle: Handle<WasmScript>,
     get_imports: &impl Fn(&mut WasmerStore, &mut WorldPointer) -> Imports,
+    capabilities: &ScriptCapabilities,
+    memory_limit: Option<u32>,
+    query_access: Access<ComponentId>,
+    group: TypeId,
 ) -> Result<bool, anyhow::Error> {
     // SAFETY: Probably not safe?
     // Need to figure out how world access actually works and how long we can keep a WorldPointer around...
     unsafe {
+        if !matches_manifests(world, &wasm_script_handle) || !mod_allows(world, &wasm_script_handle)
+        {
+            return Ok(false);
+        }
         let mut world_pointer = WorldPointer::new(world).clone();
-        let mut state =
-            SystemState::<(ResMut<Assets<WasmScript>>, ResMut<WasmerStore>)>::new(world);
-        let (mut wasm_assets, mut wasmer_store) = state.get_mut(world);
+        let capabilities = granted_capabilities(world, &wasm_script_handle, capabilities);
+        let memory_limit = smallest_limit(
+            manifests_for(world, &wasm_script_handle)
+                .iter()
+                .map(|manifest| manifest.memory_limit)
+                .chain([memory_limit]),
+        );
+        let memory_limits = world.get_resource::<WasmMemoryLimits>().cloned();
+        let store = group_store(world, group);
+        let mut state = SystemState::<ResMut<Assets<WasmScript>>>::new(world);
+        let mut wasm_assets = state.get_mut(world);
         let wasm_script = wasm_assets
             .get_mut(&wasm_script_handle)
             .ok_or(anyhow!("Asset not properly loaded?"))?;
         if let WasmScript::Compiled(module) = wasm_script {
-            let imports = &get_imports(&mut wasmer_store, &mut world_pointer);
+            let mut wasmer_store = store
+                .lock()
+                .map_err(|_| anyhow!("Script store poisoned by an earlier panic."))?;
+            let host_imports = HostImports::new(
+                &world_pointer,
+                &mut wasmer_store.0,
+                module.name().unwrap_or(""),
+                query_access,
+                group,
+            );
+            let imports = get_imports(&mut wasmer_store, &mut world_pointer);
+            let imports = restrict_imports(
+                world_pointer.read(),
+                &mut wasmer_store.0,
+                module,
+                &capabilities,
+                host_imports.with(imports),
+            );
+            let imports = &record_imports(
+                world_pointer.read(),
+                &mut wasmer_store.0,
+                module.name().unwrap_or(""),
+                imports,
+            );
+            let name = module.name().unwrap_or("").to_string();
+            let _scope = memory_limits.map(|limits| limits.instantiating(&name, memory_limit));
+            let _span = bevy::log::info_span!("wasm_instantiate", script = name.as_str()).entered();
             match instantiate_with_imports(&mut wasmer_store, module, imports) {
                 Ok(instance) => {
-                    let name = module.name().unwrap_or("").to_string();
+                    host_imports.bind(&mut wasmer_store.0, &instance);
                     *wasm_script = WasmScript::Instantiated(name, instance);
                     Ok(true)
                 }
@@ -72,6 +128,10 @@ fn is_script_asset_modified(world: &mut World, resource_handle: &Handle<WasmScri
 /**
 `WasmScriptResource` is similar to `WasmScriptComponent`. You can register a resource with a single
 associated script, using `add_wasm_script_resource`.
+
+SAFETY: As with `WasmScriptComponent`, imports must only use the components and resources listed in
+`ImportQueriedComponents` and `ImportResources`, and `WorldPointer::commands` for this type, since
+those are what the `WasmScriptResourceEnv` calling the script holds.
  */
 pub trait WasmScriptResource: Resource {
     type ImportQueriedComponents: WorldQuery;
@@ -81,6 +141,17 @@ pub trait WasmScriptResource: Resource {
     fn get_imports(_wasmer_store: &mut WasmerStore, _world_pointer: &mut WorldPointer) -> Imports {
         imports! {}
     }
+    /**
+    The capabilities this resource's script may be granted, none by default. Manifests may narrow
+    these further.
+    */
+    fn capabilities() -> ScriptCapabilities {
+        ScriptCapabilities::none()
+    }
+    /** The most memory, in wasm pages, this resource's script may use. See `WasmMemoryLimits`. */
+    fn memory_limit() -> Option<u32> {
+        None
+    }
 }
 
 /**
@@ -106,7 +177,18 @@ pub fn instantiate_resource_script<R: Resource>(
         {
             if is_script_asset_modified(&mut world, &resource_handle) {
                 // TODO: Error reporting.
-                instantiate_if_compiled(&mut world, resource_handle, &get_imports);
+                if let Ok(true) = instantiate_if_compiled(
+                    &mut world,
+                    resource_handle.clone(),
+                    &get_imports,
+                    &ScriptCapabilities::none(),
+                    None,
+                    Access::default(),
+                    TypeId::of::<R>(),
+                ) {
+                    assign_group_store(world, &resource_handle, TypeId::of::<R>());
+                    script_instantiated(world, resource_handle);
+                }
             }
         }
     }
@@ -119,8 +201,20 @@ pub fn instantiate_wasm_resource_scripts<R: WasmScriptResource>(mut world: &mut
         .cloned()
     {
         if is_script_asset_modified(&mut world, &resource_handle) {
+            let query_access = query_access_for::<R>(world);
             // TODO: Error reporting.
-            instantiate_if_compiled(&mut world, resource_handle, &R::get_imports);
+            if let Ok(true) = instantiate_if_compiled(
+                &mut world,
+                resource_handle.clone(),
+                &R::get_imports,
+                &R::capabilities(),
+                R::memory_limit(),
+                query_access,
+                TypeId::of::<R>(),
+            ) {
+                assign_group_store(world, &resource_handle, TypeId::of::<R>());
+                script_instantiated(world, resource_handle);
+            }
         }
     }
 }
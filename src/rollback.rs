@@ -0,0 +1,316 @@
+use std::{collections::VecDeque, sync::Arc};
+
+use bevy::{asset::HandleId, ecs::system::SystemParam, prelude::*, utils::HashMap};
+use wasmer::{Memory, MemoryAccessError, MemoryView, Store};
+
+use crate::{
+    snapshots::{grow_memory_to, mutable_globals, set_globals},
+    stores::instantiated,
+    timers::SavedTimers,
+    GeneralWasmScriptEnv, SnapshotValue, WasmCoroutines, WasmScript, WasmScriptTimers,
+};
+
+/**
+Memory is compared, and shared between snapshots, in chunks of this many bytes, so that a frame which
+only touches a few chunks only copies those. Wasmer doesn't track which pages a script writes to, so
+every chunk is still read and compared: taking a snapshot costs time in proportion to the size of
+each script's memory, however little of it changed.
+*/
+const CHUNK_SIZE: usize = 4096;
+
+#[derive(Debug, Clone)]
+struct MemoryChunks {
+    len: usize,
+    chunks: Vec<Arc<[u8]>>,
+}
+
+#[derive(Debug, Clone)]
+struct InstanceState {
+    generation: u64,
+    memory: Option<MemoryChunks>,
+    globals: Vec<(String, SnapshotValue)>,
+}
+
+/**
+A `RollbackSnapshot` is the exported memory and mutable exported globals of every instantiated script,
+along with the timers scripts have set, cheap enough to take every frame. Memory chunks which are
+unchanged since the `previous` snapshot are shared with it rather than copied, and restoring only
+writes the chunks which differ from the live memory.
+
+Unlike `ScriptSnapshot`, the script's `serialize` hooks are not called, and the snapshot can't be
+saved. Scripts instantiated after the snapshot was taken are left alone when it is restored, as are
+scripts which have since been reloaded, and scripts whose store couldn't be locked when it was taken.
+
+Timers are restored as they were, with the time remaining on each, so a timer set after the snapshot
+was taken is cleared and one which fired since is set again. The rest of what the host keeps for
+scripts is not captured, such as suspended coroutines and open queries. So snapshots are refused while any coroutine is suspended, as checked by
+`ScriptHostState`, and scripts must not keep a query open across the frames being rolled back.
+
+Memories can't shrink, so memory a script grew after the snapshot was taken is zeroed when restoring
+it rather than removed, and `memory.size` keeps returning the grown size. Since an allocator which
+grows memory again on the re-simulated frames gets different addresses than it did the first time,
+scripts which are rolled back should reserve the memory they need up front, through their minimum
+memory size, rather than growing it.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct RollbackSnapshot {
+    instances: HashMap<HandleId, InstanceState>,
+    timers: Option<SavedTimers>,
+}
+
+impl RollbackSnapshot {
+    pub fn take(
+        script_env: &mut impl GeneralWasmScriptEnv,
+        host: &ScriptHostState,
+        previous: Option<&RollbackSnapshot>,
+    ) -> Result<Self, anyhow::Error> {
+        host.check_idle()?;
+        let mut context = script_env.call_context();
+        let mut instances = HashMap::default();
+        for (id, script) in context.assets.iter() {
+            let WasmScript::Instantiated(_, instance) = script else {
+                continue;
+            };
+            let handle = Handle::weak(id);
+            let previous = previous
+                .and_then(|previous| previous.instances.get(&id))
+                .and_then(|previous| previous.memory.as_ref());
+            // One script's store being unavailable shouldn't keep every other script from rolling
+            // back, so it is left out of the snapshot instead.
+            let mut store = match context.stores.lock(&handle) {
+                Ok(store) => store,
+                Err(err) => {
+                    error!("Could not take a rollback snapshot of a script: {}", err);
+                    continue;
+                }
+            };
+            let memory = match instance.exports.get_memory("memory") {
+                Ok(memory) => Some(snapshot_memory(&memory.view(&*store), previous)?),
+                Err(_) => None,
+            };
+            let globals = mutable_globals(&mut store, instance);
+            instances.insert(
+                id,
+                InstanceState {
+                    generation: context.generations.generation(&handle),
+                    memory,
+                    globals,
+                },
+            );
+        }
+        let timers = host
+            .timers
+            .as_ref()
+            .map(|timers| timers.save())
+            .transpose()?;
+        Ok(Self { instances, timers })
+    }
+
+    /**
+    Restore every script in the snapshot. A script which can't be restored doesn't stop the others
+    from being restored, but is reported in the returned error.
+    */
+    pub fn restore(
+        &self,
+        script_env: &mut impl GeneralWasmScriptEnv,
+        host: &ScriptHostState,
+    ) -> Result<(), anyhow::Error> {
+        host.check_idle()?;
+        let mut context = script_env.call_context();
+        let mut failures = Vec::new();
+        for (id, state) in &self.instances {
+            let handle = Handle::weak(*id);
+            if context.generations.generation(&handle) != state.generation {
+                continue;
+            }
+            let Ok(instance) = instantiated(context.assets, &handle) else {
+                continue;
+            };
+            let restored = context.stores.lock(&handle).and_then(|mut store| {
+                if let Some(chunks) = &state.memory {
+                    let memory = instance.exports.get_memory("memory")?;
+                    restore_memory(&mut store, memory, chunks)?;
+                }
+                set_globals(&mut store, instance, &state.globals)
+            });
+            if let Err(err) = restored {
+                failures.push(err.to_string());
+            }
+        }
+        if let (Some(timers), Some(saved)) = (&host.timers, &self.timers) {
+            if let Err(err) = timers.restore(saved) {
+                failures.push(err.to_string());
+            }
+        }
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow::Error::msg(format!(
+                "Could not restore {} script(s): {}",
+                failures.len(),
+                failures.join("; ")
+            )))
+        }
+    }
+}
+
+fn snapshot_memory(
+    view: &MemoryView,
+    previous: Option<&MemoryChunks>,
+) -> Result<MemoryChunks, MemoryAccessError> {
+    let len = view.data_size() as usize;
+    let mut buffer = vec![0; CHUNK_SIZE];
+    let chunks = (0..len)
+        .step_by(CHUNK_SIZE)
+        .enumerate()
+        .map(|(index, offset)| {
+            let chunk = &mut buffer[..CHUNK_SIZE.min(len - offset)];
+            view.read(offset as u64, chunk)?;
+            Ok(
+                match previous.and_then(|previous| previous.chunks.get(index)) {
+                    Some(previous) if **previous == *chunk => previous.clone(),
+                    _ => Arc::from(&*chunk),
+                },
+            )
+        })
+        .collect::<Result<_, MemoryAccessError>>()?;
+    Ok(MemoryChunks { len, chunks })
+}
+
+fn restore_memory(
+    store: &mut Store,
+    memory: &Memory,
+    saved: &MemoryChunks,
+) -> Result<(), anyhow::Error> {
+    grow_memory_to(store, memory, saved.len)?;
+    let view = memory.view(store);
+    let len = view.data_size() as usize;
+    let mut buffer = vec![0; CHUNK_SIZE];
+    for offset in (0..len).step_by(CHUNK_SIZE) {
+        let live = &mut buffer[..CHUNK_SIZE.min(len - offset)];
+        view.read(offset as u64, live)?;
+        match saved.chunks.get(offset / CHUNK_SIZE) {
+            Some(chunk) if **chunk != *live => view.write(offset as u64, chunk)?,
+            Some(_) => {}
+            // Memory can't shrink, so memory grown since the snapshot is zeroed instead. See
+            // `RollbackSnapshot`.
+            None if live.iter().any(|byte| *byte != 0) => {
+                live.fill(0);
+                view.write(offset as u64, live)?;
+            }
+            None => {}
+        }
+    }
+    Ok(())
+}
+
+/**
+`WasmRollback` keeps a `RollbackSnapshot` of every script for each of the last `max_frames` frames,
+for rollback netcode. Save each frame as it is simulated, and load an earlier frame before
+re-simulating from it:
+```ignore
+fn save(
+    mut rollback: ResMut<WasmRollback>,
+    frame: Res<Frame>,
+    mut script_env: WasmScriptEnv,
+    host: ScriptHostState,
+) {
+    if let Err(err) = rollback.save_frame(frame.0, &mut script_env, &host) {
+        error!("Could not save frame {}: {}", frame.0, err);
+    }
+}
+```
+
+Each snapshot shares unchanged memory with the one saved before it. Saving a frame drops any frames
+saved after it, as they belong to the timeline being re-simulated.
+*/
+#[derive(Resource, Debug)]
+pub struct WasmRollback {
+    max_frames: usize,
+    frames: VecDeque<(i32, RollbackSnapshot)>,
+}
+
+impl Default for WasmRollback {
+    fn default() -> Self {
+        Self::new(16)
+    }
+}
+
+impl WasmRollback {
+    pub fn new(max_frames: usize) -> Self {
+        Self {
+            max_frames: max_frames.max(1),
+            frames: VecDeque::new(),
+        }
+    }
+
+    pub fn save_frame(
+        &mut self,
+        frame: i32,
+        script_env: &mut impl GeneralWasmScriptEnv,
+        host: &ScriptHostState,
+    ) -> Result<(), anyhow::Error> {
+        let snapshot = RollbackSnapshot::take(
+            script_env,
+            host,
+            self.frames.back().map(|(_, snapshot)| snapshot),
+        )?;
+        self.frames.retain(|(saved, _)| *saved < frame);
+        self.frames.push_back((frame, snapshot));
+        while self.frames.len() > self.max_frames {
+            self.frames.pop_front();
+        }
+        Ok(())
+    }
+
+    /** Restore every script to how it was when `frame` was saved. */
+    pub fn load_frame(
+        &self,
+        frame: i32,
+        script_env: &mut impl GeneralWasmScriptEnv,
+        host: &ScriptHostState,
+    ) -> Result<(), anyhow::Error> {
+        self.get(frame)
+            .ok_or_else(|| anyhow::Error::msg(format!("Frame {} was not saved.", frame)))?
+            .restore(script_env, host)
+    }
+
+    pub fn get(&self, frame: i32) -> Option<&RollbackSnapshot> {
+        self.frames
+            .iter()
+            .find(|(saved, _)| *saved == frame)
+            .map(|(_, snapshot)| snapshot)
+    }
+
+    /** The saved frames, oldest first. */
+    pub fn frames(&self) -> impl Iterator<Item = i32> + '_ {
+        self.frames.iter().map(|(frame, _)| *frame)
+    }
+}
+
+/**
+`ScriptHostState` is the state the host keeps for scripts outside of their instances. Rollback
+snapshots capture the timers, but can't capture suspended coroutines, so frames can only be saved or
+loaded while none are suspended.
+*/
+#[derive(SystemParam)]
+pub struct ScriptHostState<'w> {
+    coroutines: Option<Res<'w, WasmCoroutines>>,
+    timers: Option<Res<'w, WasmScriptTimers>>,
+}
+
+impl ScriptHostState<'_> {
+    /** Fails if any coroutine is suspended. */
+    pub fn check_idle(&self) -> Result<(), anyhow::Error> {
+        if self
+            .coroutines
+            .as_ref()
+            .is_some_and(|coroutines| !coroutines.is_empty())
+        {
+            return Err(anyhow::Error::msg(
+                "Scripts can't be rolled back while a coroutine is suspended.",
+            ));
+        }
+        Ok(())
+    }
+}
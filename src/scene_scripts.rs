@@ -0,0 +1,114 @@
+use bevy::{prelude::*, utils::HashMap};
+use wasmer::{imports, Function, FunctionEnv, FunctionEnvMut, Imports, Store};
+
+use crate::{
+    host::{read_guest_bytes, HostEnv},
+    EntityId, EntityIdTrait, WasmScript, WasmScriptComponent, WasmScriptError,
+};
+
+/**
+`WasmScriptPath` is a reflected component for entities which run the script at `path`, so that
+scripted entities can be authored in scene files:
+```ron
+"bevy_wasm_scripting::scene_scripts::WasmScriptPath": (
+    path: "patrol.wat",
+    config: { "speed": 2.5 },
+),
+```
+The `WasmPlugin` registers it as a `WasmScriptComponent`, and loads the script through the
+`AssetServer` whenever `path` changes. Scripts are called through
+`WasmScriptComponentEnv<WasmScriptPath>`, or may register their own systems.
+
+Scripts read their entity's `config` through the `config` import namespace:
+* `get(entity: f64, key_ptr: i32, key_len: i32) -> f64` returns the value for the key, or NaN if
+  there is none.
+
+Only scripts whose type may read `WasmScriptPath`, as this type can, may read configs.
+
+Scenes name reflected components by their full type name, so this type stays at
+`bevy_wasm_scripting::scene_scripts::WasmScriptPath`, which is why this module is public.
+*/
+#[derive(Component, Reflect, Default, Debug, Clone)]
+#[reflect(Component)]
+pub struct WasmScriptPath {
+    pub path: String,
+    pub config: HashMap<String, f64>,
+    #[reflect(ignore)]
+    handle: Handle<WasmScript>,
+    #[reflect(ignore)]
+    loaded_path: Option<String>,
+}
+
+impl WasmScriptPath {
+    pub fn new(path: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            ..Default::default()
+        }
+    }
+
+    /** The handle of the script, once `path` has been loaded. */
+    pub fn handle(&self) -> &Handle<WasmScript> {
+        &self.handle
+    }
+
+    pub fn with_config(mut self, key: impl Into<String>, value: f64) -> Self {
+        self.config.insert(key.into(), value);
+        self
+    }
+}
+
+impl WasmScriptComponent for WasmScriptPath {
+    type ImportQueriedComponents = &'static WasmScriptPath;
+    type ImportResources = ();
+
+    fn get_wasm_script_handle(&self) -> &Handle<WasmScript> {
+        &self.handle
+    }
+}
+
+pub(crate) fn load_script_paths(
+    asset_server: Res<AssetServer>,
+    mut scripts: Query<&mut WasmScriptPath, Changed<WasmScriptPath>>,
+) {
+    for mut script in scripts.iter_mut() {
+        if script.loaded_path.as_ref() == Some(&script.path) {
+            continue;
+        }
+        let script = &mut *script;
+        script.handle = asset_server.load(script.path.as_str());
+        script.loaded_path = Some(script.path.clone());
+    }
+}
+
+fn get(env: FunctionEnvMut<HostEnv>, entity: EntityId, key_ptr: i32, key_len: i32) -> f64 {
+    let Some(key) =
+        read_guest_bytes(&env, key_ptr, key_len).and_then(|key| String::from_utf8(key).ok())
+    else {
+        return f64::NAN;
+    };
+    let host = env.data();
+    let world = host.world.read();
+    let may_read = world
+        .component_id::<WasmScriptPath>()
+        .is_some_and(|component_id| host.queries.may_read(component_id, entity.to_entity()));
+    if !may_read {
+        host.report(WasmScriptError::QueryDenied {
+            script: host.script.clone(),
+            component: "WasmScriptPath".to_string(),
+        });
+        return f64::NAN;
+    }
+    world
+        .get::<WasmScriptPath>(entity.to_entity())
+        .and_then(|script| script.config.get(&key).copied())
+        .unwrap_or(f64::NAN)
+}
+
+pub(crate) fn script_config_imports(store: &mut Store, env: &FunctionEnv<HostEnv>) -> Imports {
+    imports! {
+        "config" => {
+            "get" => Function::new_typed_with_env(store, env, get),
+        }
+    }
+}
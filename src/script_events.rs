@@ -0,0 +1,162 @@
+use std::sync::{Arc, Mutex};
+
+use bevy::prelude::*;
+use serde::de::DeserializeOwned;
+use wasmer::{imports, Function, FunctionEnv, FunctionEnvMut, Imports, Store};
+
+use crate::{
+    host::{read_guest_bytes, HostEnv},
+    WasmScriptError,
+};
+
+type PendingEvent = Box<dyn FnOnce(&mut World) + Send + Sync>;
+
+#[derive(Clone)]
+struct ScriptEventType {
+    name: String,
+    decode: fn(&[u8]) -> Result<PendingEvent, String>,
+}
+
+fn decode_event<E: Event + DeserializeOwned>(payload: &[u8]) -> Result<PendingEvent, String> {
+    let event: E = ron::de::from_bytes(payload).map_err(|err| err.to_string())?;
+    Ok(Box::new(move |world: &mut World| world.send_event(event)))
+}
+
+/**
+`ScriptEventRegistry` lists the event types scripts may send, as registered with
+`add_wasm_script_event`. Each type is identified by the name it was registered with, and by a
+numeric id assigned in registration order.
+
+Scripts send events through the `events` import namespace:
+* `event_type_id(name_ptr: i32, name_len: i32) -> i32` looks up the id of a named event type, or
+  returns -1 if there is none.
+* `send_event(type_id: i32, ptr: i32, len: i32) -> i32` sends an event, serialized as RON in the
+  script's exported `memory`. It returns 0 on success, -1 for an unknown type, or -2 if the payload
+  could not be read or deserialized.
+
+Events are buffered while scripts run, and written to their `Events<T>` in `CoreSet::PostUpdate`.
+Rejected events are reported as `WasmScriptError::InvalidScriptEvent`. The `events` namespace can be
+gated behind a capability like any other.
+*/
+#[derive(Resource, Clone, Default)]
+pub struct ScriptEventRegistry {
+    types: Arc<Vec<ScriptEventType>>,
+}
+
+impl ScriptEventRegistry {
+    pub(crate) fn register<E: Event + DeserializeOwned>(&mut self, name: &str) {
+        let types = Arc::make_mut(&mut self.types);
+        let event_type = ScriptEventType {
+            name: name.to_string(),
+            decode: decode_event::<E>,
+        };
+        match types.iter_mut().find(|existing| existing.name == name) {
+            Some(existing) => *existing = event_type,
+            None => types.push(event_type),
+        }
+    }
+
+    pub fn type_id(&self, name: &str) -> Option<i32> {
+        self.types
+            .iter()
+            .position(|event_type| event_type.name == name)
+            .map(|index| index as i32)
+    }
+
+    pub fn type_name(&self, type_id: i32) -> Option<&str> {
+        usize::try_from(type_id)
+            .ok()
+            .and_then(|index| self.types.get(index))
+            .map(|event_type| event_type.name.as_str())
+    }
+}
+
+#[derive(Resource, Clone, Default)]
+pub(crate) struct ScriptEventBuffer(Arc<Mutex<Vec<PendingEvent>>>);
+
+/**
+What a script's host imports need to decode and buffer events.
+*/
+#[derive(Clone)]
+pub(crate) struct ScriptEventSender {
+    registry: ScriptEventRegistry,
+    buffer: ScriptEventBuffer,
+}
+
+impl ScriptEventSender {
+    pub(crate) fn from_world(world: &World) -> Self {
+        Self {
+            registry: world
+                .get_resource::<ScriptEventRegistry>()
+                .cloned()
+                .unwrap_or_default(),
+            buffer: world
+                .get_resource::<ScriptEventBuffer>()
+                .cloned()
+                .unwrap_or_default(),
+        }
+    }
+}
+
+fn event_type_id(env: FunctionEnvMut<HostEnv>, name_ptr: i32, name_len: i32) -> i32 {
+    read_guest_bytes(&env, name_ptr, name_len)
+        .and_then(|name| String::from_utf8(name).ok())
+        .and_then(|name| env.data().events.registry.type_id(&name))
+        .unwrap_or(-1)
+}
+
+fn send_event(env: FunctionEnvMut<HostEnv>, type_id: i32, ptr: i32, len: i32) -> i32 {
+    let host = env.data();
+    let Some(event_type) = usize::try_from(type_id)
+        .ok()
+        .and_then(|index| host.events.registry.types.get(index))
+    else {
+        host.report(WasmScriptError::InvalidScriptEvent {
+            script: host.script.clone(),
+            event: type_id.to_string(),
+            error: "unknown event type".to_string(),
+        });
+        return -1;
+    };
+    let decoded = read_guest_bytes(&env, ptr, len)
+        .ok_or_else(|| "payload is outside of the script's memory".to_string())
+        .and_then(|payload| (event_type.decode)(&payload));
+    match decoded {
+        Ok(event) => {
+            if let Ok(mut buffer) = host.events.buffer.0.lock() {
+                buffer.push(event);
+            }
+            0
+        }
+        Err(error) => {
+            host.report(WasmScriptError::InvalidScriptEvent {
+                script: host.script.clone(),
+                event: event_type.name.clone(),
+                error,
+            });
+            -2
+        }
+    }
+}
+
+pub(crate) fn script_event_imports(store: &mut Store, env: &FunctionEnv<HostEnv>) -> Imports {
+    imports! {
+        "events" => {
+            "event_type_id" => Function::new_typed_with_env(store, env, event_type_id),
+            "send_event" => Function::new_typed_with_env(store, env, send_event),
+        }
+    }
+}
+
+pub(crate) fn send_script_events(world: &mut World) {
+    let pending: Vec<PendingEvent> = match world.get_resource::<ScriptEventBuffer>() {
+        Some(buffer) => match buffer.0.lock() {
+            Ok(mut buffer) => buffer.drain(..).collect(),
+            Err(_) => return,
+        },
+        None => return,
+    };
+    for event in pending {
+        event(world);
+    }
+}
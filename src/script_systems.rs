@@ -0,0 +1,356 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use bevy::{ecs::system::SystemState, prelude::*, utils::HashSet};
+use wasmer::{imports, Function, FunctionEnv, FunctionEnvMut, Imports, Store};
+
+use crate::{
+    host::{read_guest_bytes, HostEnv},
+    stores::current_script,
+    GeneralWasmScriptEnv, WasmScript, WasmScriptEnv,
+};
+
+/** The phase for script systems which don't name one. The `WasmPlugin` runs it in `Update`. */
+pub const DEFAULT_SCRIPT_PHASE: &str = "update";
+/** A phase the `WasmPlugin` runs in `PreUpdate`. */
+pub const PRE_UPDATE_SCRIPT_PHASE: &str = "pre_update";
+/** A phase the `WasmPlugin` runs in `PostUpdate`. */
+pub const POST_UPDATE_SCRIPT_PHASE: &str = "post_update";
+/** A phase the `WasmPlugin` runs in the `FixedUpdate` schedule. */
+pub const FIXED_UPDATE_SCRIPT_PHASE: &str = "fixed_update";
+
+/** The export called to let a script declare its systems. */
+const REGISTER_EXPORT: &str = "register";
+
+/**
+A system declared by a script: the export to call, the phase it runs in, the exports which must all
+return non-zero for it to run, and its ordering within the phase. Every system is labelled with its
+export, as well as any labels the script gave it.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptSystem {
+    pub script: Handle<WasmScript>,
+    pub export: String,
+    pub phase: String,
+    pub run_conditions: Vec<String>,
+    pub labels: Vec<String>,
+    /** Labels of the systems in the same phase this system runs before. */
+    pub before: Vec<String>,
+    /** Labels of the systems in the same phase this system runs after. */
+    pub after: Vec<String>,
+}
+
+impl ScriptSystem {
+    fn runs_before(&self, other: &ScriptSystem) -> bool {
+        self.before.iter().any(|label| other.labels.contains(label))
+            || other.after.iter().any(|label| self.labels.contains(label))
+    }
+}
+
+#[derive(Debug, Default)]
+struct DeclaredSystem {
+    export: String,
+    phase: String,
+    run_conditions: Vec<String>,
+    labels: Vec<String>,
+    before: Vec<String>,
+    after: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+struct Declarations {
+    // The script running its `register` export, which is the only one which may declare systems.
+    registering: Option<Handle<WasmScript>>,
+    systems: Vec<DeclaredSystem>,
+}
+
+/**
+Collects the systems declared by the script currently running its `register` export.
+*/
+#[derive(Resource, Debug, Default, Clone)]
+pub(crate) struct ScriptSystemDeclarations(Arc<Mutex<Declarations>>);
+
+/**
+`WasmScriptSystems` holds every system declared by a script. When a script is instantiated, or
+re-instantiated after hot reloading, its previous systems are dropped and its `register` export is
+called. While its `register` runs, the script may use the `systems` import namespace:
+* `add_system(export_ptr: i32, export_len: i32, phase_ptr: i32, phase_len: i32) -> i32` declares a
+  system calling `export`, which takes no arguments and returns nothing, in the named phase. An empty
+  phase name is the `DEFAULT_SCRIPT_PHASE`. Returns the id of the system, or -1 outside of
+  `register`.
+* `add_run_condition(system: i32, export_ptr: i32, export_len: i32) -> i32` only runs the system
+  while `export`, which takes no arguments and returns an `i32`, returns non-zero.
+* `add_system_label(system: i32, label_ptr: i32, label_len: i32) -> i32` labels the system, for
+  other systems to be ordered against. Every system is already labelled with its export.
+* `add_system_before(system: i32, label_ptr: i32, label_len: i32) -> i32` and
+  `add_system_after(...)` run the system before or after every system in its phase with the label,
+  from any script.
+
+The last three return 0, or -1 for an unknown system or outside of `register`.
+
+Scripts choose the schedule their systems run in through the phase. The `WasmPlugin` runs the
+`PRE_UPDATE_SCRIPT_PHASE`, `DEFAULT_SCRIPT_PHASE` and `POST_UPDATE_SCRIPT_PHASE` in the matching
+base sets of `Update`, and the `FIXED_UPDATE_SCRIPT_PHASE` in `FixedUpdate`. Ordering against host
+systems, such as "after physics", is done by the host placing another phase with
+`wasm_system_phase` where it should run, for scripts to name.
+
+Script systems are not Bevy systems of their own: each phase is a single system which runs every
+script system in it, ordered by their labels and otherwise in the order they were declared. This
+lets hot reloading replace a script's systems, as Bevy can't remove systems from a schedule once
+added. Systems whose ordering is cyclic are run in the order they were declared, with an error.
+*/
+#[derive(Resource, Debug, Default)]
+pub struct WasmScriptSystems {
+    systems: Vec<ScriptSystem>,
+    pending_registration: HashSet<Handle<WasmScript>>,
+}
+
+impl WasmScriptSystems {
+    /** Every script system, in the order they run within their phases. */
+    pub fn iter(&self) -> impl Iterator<Item = &ScriptSystem> {
+        self.systems.iter()
+    }
+
+    pub fn in_phase<'a>(&'a self, phase: &'a str) -> impl Iterator<Item = &'a ScriptSystem> {
+        self.systems
+            .iter()
+            .filter(move |system| system.phase == phase)
+    }
+}
+
+/**
+Mark a freshly instantiated script to have its systems registered again.
+*/
+pub(crate) fn schedule_registration(world: &mut World, handle: Handle<WasmScript>) {
+    if let Some(mut systems) = world.get_resource_mut::<WasmScriptSystems>() {
+        systems.pending_registration.insert(handle);
+    }
+}
+
+/**
+Order systems by their labels within each phase, keeping the order they are in otherwise. Systems in
+a cycle keep their order too.
+*/
+fn order_systems(mut remaining: Vec<ScriptSystem>) -> Vec<ScriptSystem> {
+    let mut ordered = Vec::with_capacity(remaining.len());
+    while !remaining.is_empty() {
+        let next = remaining
+            .iter()
+            .enumerate()
+            .position(|(index, system)| {
+                !remaining.iter().enumerate().any(|(other_index, other)| {
+                    other_index != index && other.phase == system.phase && other.runs_before(system)
+                })
+            })
+            .unwrap_or_else(|| {
+                bevy::log::error!(
+                    "Script systems in phase {} are ordered in a cycle",
+                    remaining[0].phase
+                );
+                0
+            });
+        ordered.push(remaining.remove(next));
+    }
+    ordered
+}
+
+fn read_guest_string(env: &FunctionEnvMut<HostEnv>, ptr: i32, len: i32) -> Option<String> {
+    read_guest_bytes(env, ptr, len).and_then(|bytes| String::from_utf8(bytes).ok())
+}
+
+/** The declarations, if the calling script is the one running its `register` export. */
+fn caller_declarations<'a>(
+    env: &'a FunctionEnvMut<HostEnv>,
+) -> Option<MutexGuard<'a, Declarations>> {
+    let caller = current_script()?;
+    let declarations = env.data().systems.0.lock().ok()?;
+    (declarations.registering.as_ref().map(Handle::id) == Some(caller.id())).then_some(declarations)
+}
+
+/** Update a system declared by the calling script. Returns 0, or -1 if it can't. */
+fn update_system(
+    env: &FunctionEnvMut<HostEnv>,
+    system: i32,
+    ptr: i32,
+    len: i32,
+    update: impl FnOnce(&mut DeclaredSystem, String),
+) -> i32 {
+    let Some(value) = read_guest_string(env, ptr, len) else {
+        return -1;
+    };
+    let Some(mut declarations) = caller_declarations(env) else {
+        return -1;
+    };
+    match usize::try_from(system)
+        .ok()
+        .and_then(|index| declarations.systems.get_mut(index))
+    {
+        Some(declared) => {
+            update(declared, value);
+            0
+        }
+        None => -1,
+    }
+}
+
+fn add_system(
+    env: FunctionEnvMut<HostEnv>,
+    export_ptr: i32,
+    export_len: i32,
+    phase_ptr: i32,
+    phase_len: i32,
+) -> i32 {
+    let (Some(export), Some(phase)) = (
+        read_guest_string(&env, export_ptr, export_len),
+        read_guest_string(&env, phase_ptr, phase_len),
+    ) else {
+        return -1;
+    };
+    let phase = if phase.is_empty() {
+        DEFAULT_SCRIPT_PHASE.to_string()
+    } else {
+        phase
+    };
+    let Some(mut declarations) = caller_declarations(&env) else {
+        return -1;
+    };
+    declarations.systems.push(DeclaredSystem {
+        labels: vec![export.clone()],
+        export,
+        phase,
+        ..Default::default()
+    });
+    declarations.systems.len() as i32 - 1
+}
+
+fn add_run_condition(env: FunctionEnvMut<HostEnv>, system: i32, ptr: i32, len: i32) -> i32 {
+    update_system(&env, system, ptr, len, |declared, export| {
+        declared.run_conditions.push(export)
+    })
+}
+
+fn add_system_label(env: FunctionEnvMut<HostEnv>, system: i32, ptr: i32, len: i32) -> i32 {
+    update_system(&env, system, ptr, len, |declared, label| {
+        declared.labels.push(label)
+    })
+}
+
+fn add_system_before(env: FunctionEnvMut<HostEnv>, system: i32, ptr: i32, len: i32) -> i32 {
+    update_system(&env, system, ptr, len, |declared, label| {
+        declared.before.push(label)
+    })
+}
+
+fn add_system_after(env: FunctionEnvMut<HostEnv>, system: i32, ptr: i32, len: i32) -> i32 {
+    update_system(&env, system, ptr, len, |declared, label| {
+        declared.after.push(label)
+    })
+}
+
+pub(crate) fn script_system_imports(store: &mut Store, env: &FunctionEnv<HostEnv>) -> Imports {
+    imports! {
+        "systems" => {
+            "add_system" => Function::new_typed_with_env(store, env, add_system),
+            "add_run_condition" => Function::new_typed_with_env(store, env, add_run_condition),
+            "add_system_label" => Function::new_typed_with_env(store, env, add_system_label),
+            "add_system_before" => Function::new_typed_with_env(store, env, add_system_before),
+            "add_system_after" => Function::new_typed_with_env(store, env, add_system_after),
+        }
+    }
+}
+
+type RegisterState = SystemState<(
+    ResMut<'static, WasmScriptSystems>,
+    Res<'static, ScriptSystemDeclarations>,
+    Res<'static, Assets<WasmScript>>,
+    WasmScriptEnv<'static, 'static>,
+)>;
+
+// Exclusive, like every crate system calling scripts through `WasmScriptEnv`. See `WasmScriptStores`.
+pub(crate) fn register_script_systems(world: &mut World, state: &mut RegisterState) {
+    let (mut script_systems, declarations, assets, mut script_env) = state.get_mut(world);
+    let pending: Vec<Handle<WasmScript>> = script_systems.pending_registration.drain().collect();
+    if pending.is_empty() {
+        return;
+    }
+    for handle in pending {
+        script_systems
+            .systems
+            .retain(|system| system.script != handle);
+        let exports_register = matches!(
+            assets.get(&handle),
+            Some(WasmScript::Instantiated(_, instance))
+                if instance.exports.get_function(REGISTER_EXPORT).is_ok()
+        );
+        if !exports_register {
+            continue;
+        }
+        if let Ok(mut declarations) = declarations.0.lock() {
+            declarations.registering = Some(handle.clone());
+            declarations.systems.clear();
+        }
+        let result = script_env.call_if_instantiated_0::<()>(&handle, REGISTER_EXPORT);
+        let declared = match declarations.0.lock() {
+            Ok(mut declarations) => {
+                declarations.registering = None;
+                std::mem::take(&mut declarations.systems)
+            }
+            Err(_) => Vec::new(),
+        };
+        if let Err(err) = result {
+            bevy::log::error!("Failed to register script systems: {}", err);
+            continue;
+        }
+        script_systems
+            .systems
+            .extend(declared.into_iter().map(|declared| ScriptSystem {
+                script: handle.clone(),
+                export: declared.export,
+                phase: declared.phase,
+                run_conditions: declared.run_conditions,
+                labels: declared.labels,
+                before: declared.before,
+                after: declared.after,
+            }));
+    }
+    let systems = std::mem::take(&mut script_systems.systems);
+    script_systems.systems = order_systems(systems);
+}
+
+/**
+An exclusive system which runs every script system declared in `phase`, in order. Add it wherever
+those systems should run, for example:
+```ignore
+app.add_system(
+    wasm_system_phase("tick")
+        .in_schedule(CoreSchedule::FixedUpdate)
+        .after(physics),
+);
+```
+*/
+pub fn wasm_system_phase(phase: &str) -> impl FnMut(&mut World) + Send + Sync + 'static {
+    let phase = phase.to_string();
+    let mut state: Option<SystemState<(Res<WasmScriptSystems>, WasmScriptEnv)>> = None;
+    move |world: &mut World| {
+        let state = state.get_or_insert_with(|| SystemState::new(world));
+        let (script_systems, mut script_env) = state.get_mut(world);
+        for system in script_systems.in_phase(&phase) {
+            let should_run = system.run_conditions.iter().all(|condition| {
+                match script_env.call_if_instantiated_0::<i32>(&system.script, condition) {
+                    Ok(result) => result != 0,
+                    Err(err) => {
+                        bevy::log::error!("Failed to check run condition {}: {}", condition, err);
+                        false
+                    }
+                }
+            });
+            if !should_run {
+                continue;
+            }
+            if let Err(err) =
+                script_env.call_if_instantiated_0::<()>(&system.script, &system.export)
+            {
+                bevy::log::error!("Failed to run script system {}: {}", system.export, err);
+            }
+        }
+    }
+}
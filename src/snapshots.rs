@@ -0,0 +1,277 @@
+use bevy::{asset::HandleId, ecs::system::SystemState, prelude::*, utils::HashMap};
+use serde::{Deserialize, Serialize};
+use wasmer::{Extern, Instance, Memory, Mutability, Pages, Store, Value, WASM_PAGE_SIZE};
+
+use crate::{
+    functions::ScriptCallContext,
+    metrics::{WasmScriptMetrics, FUEL_GLOBAL},
+    stores::{exported_function, instantiated},
+    watchdog::DEADLINE_GLOBAL,
+    GeneralWasmScriptEnv, WasmDeterminism, WasmScript, WasmScriptEnv, WasmWatchdog,
+};
+
+/** The hook exports which, when exported, replace the default snapshot of memory and globals. */
+const SERIALIZE_EXPORT: &str = "serialize";
+const DESERIALIZE_ALLOC_EXPORT: &str = "deserialize_alloc";
+const DESERIALIZE_EXPORT: &str = "deserialize";
+
+/** The value of a global in a `ScriptSnapshot`. */
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum SnapshotValue {
+    I32(i32),
+    I64(i64),
+    F32(f32),
+    F64(f64),
+}
+
+impl SnapshotValue {
+    pub(crate) fn from_value(value: Value) -> Option<Self> {
+        match value {
+            Value::I32(value) => Some(Self::I32(value)),
+            Value::I64(value) => Some(Self::I64(value)),
+            Value::F32(value) => Some(Self::F32(value)),
+            Value::F64(value) => Some(Self::F64(value)),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn to_value(self) -> Value {
+        match self {
+            Self::I32(value) => Value::I32(value),
+            Self::I64(value) => Value::I64(value),
+            Self::F32(value) => Value::F32(value),
+            Self::F64(value) => Value::F64(value),
+        }
+    }
+}
+
+/**
+A `ScriptSnapshot` is the state of a script instance, which can be saved with a game and restored
+onto a fresh instance of the same script.
+
+If the script exports `serialize() -> (i32, i32)`, returning the address and length of its state in
+its memory, the snapshot holds only those bytes. They are restored by calling
+`deserialize_alloc(len: i32) -> i32` for an address to copy them to, then `deserialize(ptr: i32,
+len: i32)`. Otherwise, the snapshot holds the whole exported `memory`, and the values of every
+mutable exported global.
+
+```ignore
+let snapshot = ScriptSnapshot::take(&mut script_env, &handle)?;
+let saved = ron::to_string(&snapshot)?;
+// ...and once loaded again:
+snapshot.restore(&mut script_env, &handle)?;
+```
+*/
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ScriptSnapshot {
+    /** The state returned by the script's `serialize` export. */
+    Serialized(Vec<u8>),
+    /** The script's exported memory, if any, and its mutable exported globals by name. */
+    Raw {
+        memory: Option<Vec<u8>>,
+        globals: Vec<(String, SnapshotValue)>,
+    },
+}
+
+impl ScriptSnapshot {
+    /** Snapshot the instance of an instantiated script. */
+    pub fn take(
+        script_env: &mut impl GeneralWasmScriptEnv,
+        handle: &Handle<WasmScript>,
+    ) -> Result<Self, anyhow::Error> {
+        let mut context = script_env.call_context();
+        let instance = instantiated(context.assets, handle)?;
+        let (watchdog, determinism, metrics) =
+            (context.watchdog, context.determinism, context.metrics);
+        let mut store = context.stores.lock(handle)?;
+        if instance.exports.get_function(SERIALIZE_EXPORT).is_ok() {
+            serialize(&mut store, instance, watchdog, determinism, metrics).map(Self::Serialized)
+        } else {
+            Ok(snapshot_raw(&mut store, instance))
+        }
+    }
+
+    /** Restore this snapshot onto the instance of an instantiated script. */
+    pub fn restore(
+        &self,
+        script_env: &mut impl GeneralWasmScriptEnv,
+        handle: &Handle<WasmScript>,
+    ) -> Result<(), anyhow::Error> {
+        restore(self, &mut script_env.call_context(), handle)
+    }
+}
+
+fn serialize(
+    store: &mut Store,
+    instance: &Instance,
+    watchdog: &WasmWatchdog,
+    determinism: Option<&WasmDeterminism>,
+    metrics: &WasmScriptMetrics,
+) -> Result<Vec<u8>, anyhow::Error> {
+    let serialize =
+        exported_function(instance, store, SERIALIZE_EXPORT)?.typed::<(), (i32, i32)>(store)?;
+    let (ptr, len) = metrics.measure(store, instance, SERIALIZE_EXPORT, |store| {
+        watchdog.guard(determinism, store, instance, SERIALIZE_EXPORT, |store| {
+            serialize.call(store)
+        })
+    })?;
+    let memory = instance.exports.get_memory("memory")?;
+    let view = memory.view(store);
+    let (offset, len) = (u64::try_from(ptr)?, u64::try_from(len)?);
+    // The length comes from the script, so check it before allocating anything for it.
+    let end = offset
+        .checked_add(len)
+        .filter(|end| *end <= view.data_size());
+    if end.is_none() {
+        return Err(anyhow::Error::msg(format!(
+            "{} returned {} bytes at {}, past the end of the script's memory",
+            SERIALIZE_EXPORT, len, offset
+        )));
+    }
+    let mut bytes = vec![0; usize::try_from(len)?];
+    view.read(offset, &mut bytes)?;
+    Ok(bytes)
+}
+
+pub(crate) fn snapshot_raw(store: &mut Store, instance: &Instance) -> ScriptSnapshot {
+    let memory = instance.exports.get_memory("memory").ok().map(|memory| {
+        let view = memory.view(store);
+        let mut bytes = vec![0; view.data_size() as usize];
+        // The whole of the memory is always readable.
+        let _ = view.read(0, &mut bytes);
+        bytes
+    });
+    let globals = mutable_globals(store, instance);
+    ScriptSnapshot::Raw { memory, globals }
+}
+
+/** The values of the instance's mutable exported globals, by name. */
+pub(crate) fn mutable_globals(
+    store: &mut Store,
+    instance: &Instance,
+) -> Vec<(String, SnapshotValue)> {
+    instance
+        .exports
+        .iter()
+        .filter(|(name, _)| ![DEADLINE_GLOBAL, FUEL_GLOBAL].contains(&name.as_str()))
+        .filter_map(|(name, export)| match export {
+            Extern::Global(global) if global.ty(store).mutability == Mutability::Var => {
+                SnapshotValue::from_value(global.get(store)).map(|value| (name.clone(), value))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+pub(crate) fn set_globals(
+    store: &mut Store,
+    instance: &Instance,
+    globals: &[(String, SnapshotValue)],
+) -> Result<(), anyhow::Error> {
+    for (name, value) in globals {
+        instance
+            .exports
+            .get_global(name)?
+            .set(store, value.to_value())?;
+    }
+    Ok(())
+}
+
+/** Grow `memory` until it holds at least `len` bytes. Memories never shrink. */
+pub(crate) fn grow_memory_to(
+    store: &mut Store,
+    memory: &Memory,
+    len: usize,
+) -> Result<(), anyhow::Error> {
+    let pages = (len as u64).div_ceil(WASM_PAGE_SIZE as u64) as u32;
+    let current = memory.view(store).size();
+    if Pages(pages) > current {
+        memory.grow(store, Pages(pages - current.0))?;
+    }
+    Ok(())
+}
+
+fn restore(
+    snapshot: &ScriptSnapshot,
+    context: &mut ScriptCallContext,
+    handle: &Handle<WasmScript>,
+) -> Result<(), anyhow::Error> {
+    let instance = instantiated(context.assets, handle)?;
+    let (watchdog, determinism, metrics) = (context.watchdog, context.determinism, context.metrics);
+    let mut store = context.stores.lock(handle)?;
+    let store = &mut *store;
+    match snapshot {
+        ScriptSnapshot::Serialized(bytes) => {
+            let len = i32::try_from(bytes.len())?;
+            let alloc = exported_function(instance, store, DESERIALIZE_ALLOC_EXPORT)?
+                .typed::<i32, i32>(store)?;
+            let deserialize = exported_function(instance, store, DESERIALIZE_EXPORT)?
+                .typed::<(i32, i32), ()>(store)?;
+            let ptr = metrics.measure(store, instance, DESERIALIZE_ALLOC_EXPORT, |store| {
+                watchdog.guard(
+                    determinism,
+                    store,
+                    instance,
+                    DESERIALIZE_ALLOC_EXPORT,
+                    |store| alloc.call(store, len),
+                )
+            })?;
+            let memory = instance.exports.get_memory("memory")?;
+            memory.view(store).write(u64::try_from(ptr)?, bytes)?;
+            metrics.measure(store, instance, DESERIALIZE_EXPORT, |store| {
+                watchdog.guard(determinism, store, instance, DESERIALIZE_EXPORT, |store| {
+                    deserialize.call(store, ptr, len)
+                })
+            })
+        }
+        ScriptSnapshot::Raw { memory, globals } => restore_raw(store, instance, memory, globals),
+    }
+}
+
+pub(crate) fn restore_raw(
+    store: &mut Store,
+    instance: &Instance,
+    memory: &Option<Vec<u8>>,
+    globals: &[(String, SnapshotValue)],
+) -> Result<(), anyhow::Error> {
+    if let Some(bytes) = memory {
+        let memory = instance.exports.get_memory("memory")?;
+        grow_memory_to(store, memory, bytes.len())?;
+        memory.view(store).write(0, bytes)?;
+    }
+    set_globals(store, instance, globals)
+}
+
+/**
+`WasmScriptSnapshots` holds snapshots to restore onto scripts as soon as they are instantiated, such
+as when loading a saved game before its scripts have loaded.
+*/
+#[derive(Resource, Default)]
+pub struct WasmScriptSnapshots {
+    pending: HashMap<HandleId, ScriptSnapshot>,
+}
+
+impl WasmScriptSnapshots {
+    /** Restore `snapshot` the next time the script is instantiated. */
+    pub fn restore_on_instantiate(
+        &mut self,
+        handle: &Handle<WasmScript>,
+        snapshot: ScriptSnapshot,
+    ) {
+        self.pending.insert(handle.id(), snapshot);
+    }
+}
+
+pub(crate) fn restore_pending_snapshot(world: &mut World, handle: &Handle<WasmScript>) {
+    let Some(snapshot) = world
+        .get_resource_mut::<WasmScriptSnapshots>()
+        .and_then(|mut snapshots| snapshots.pending.remove(&handle.id()))
+    else {
+        return;
+    };
+    let mut state = SystemState::<WasmScriptEnv>::new(world);
+    let mut script_env = state.get_mut(world);
+    if let Err(err) = snapshot.restore(&mut script_env, handle) {
+        bevy::log::error!("Failed to restore a script snapshot: {}", err);
+    }
+}
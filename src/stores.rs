@@ -0,0 +1,257 @@
+use std::{
+    any::TypeId,
+    cell::{Cell, RefCell},
+    ops::{Deref, DerefMut},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use bevy::{asset::HandleId, prelude::*, utils::HashMap};
+use wasmer::{Function, Instance, Store};
+
+use crate::{
+    queries::{CallerQueryAccess, EntityFilter},
+    WasmScript, WasmerStore,
+};
+
+type SharedStore = Arc<Mutex<WasmerStore>>;
+
+thread_local! {
+    static CURRENT_SCRIPT: RefCell<Option<Handle<WasmScript>>> = const { RefCell::new(None) };
+    static CALLER_QUERY_ACCESS: Cell<Option<(TypeId, *const dyn EntityFilter)>> = const { Cell::new(None) };
+}
+
+/**
+The script being called on this thread, for host imports which act on behalf of their caller. Set for
+as long as the script's store is locked.
+*/
+pub(crate) fn current_script() -> Option<Handle<WasmScript>> {
+    CURRENT_SCRIPT.with(|current| current.borrow().clone())
+}
+
+/**
+The query access held by the system calling a script on this thread, if it holds any. Set alongside
+`current_script`.
+*/
+pub(crate) fn caller_query_access() -> Option<CallerQueryAccess<'static>> {
+    CALLER_QUERY_ACCESS
+        .with(Cell::get)
+        .map(|(group, entities)| CallerQueryAccess {
+            group,
+            // SAFETY: The filter is borrowed by the `ScriptStoreAccess` which set it, and the
+            // `ScriptStoreGuard` unsets it before that borrow ends. Host imports only use it during the
+            // call, and never keep it.
+            entities: unsafe { &*entities },
+        })
+}
+
+/**
+`WasmScriptStores` holds a separate wasmer store for each script type registered with
+`add_wasm_script_component` or `add_wasm_script_resource`, or instantiated with
+`instantiate_resource_script`. Scripts are instantiated into the store of the type which instantiated
+them, and each call locks only that store.
+
+`WasmScriptComponentEnv` and `WasmScriptResourceEnv` only read this resource, so systems using them
+run in parallel with each other, and only wait on each other while calling scripts of the same type.
+`WasmScriptEnv` can also call scripts instantiated directly into the `WasmerStore`, so systems using
+it still run one at a time. Scripts instantiated with `WasmScript::instantiate_if_compiled` can only
+be called through `WasmScriptEnv`; the other envs return an error, and log it once.
+
+Systems calling scripts through the env of their script type run in parallel soundly because host
+imports only reach into the world for what that env holds:
+* The imports this crate provides keep their own state behind locks, such as buffered events and
+  timers, and only read components through the `query` imports while the calling env holds access
+  to them. See `ScriptQueryRegistry`.
+* The imports of a script type may only use what it lists in `ImportQueriedComponents` and
+  `ImportResources`, which the env of that type holds, and the command queue behind
+  `WorldPointer::commands` for that type, which the env holds too. See `WasmScriptComponent`.
+
+So two systems whose script imports touch the same components or resources conflict through their
+envs, and the scheduler runs them one at a time.
+
+`WasmScriptEnv` holds none of those, yet can call scripts of any type, running their imports. The
+systems of this crate calling scripts through it, which run script systems, timers and coroutines,
+are exclusive systems for this reason. Your own systems should only call scripts of a type with
+world-touching imports through `WasmScriptEnv` from an exclusive system, using a `SystemState`.
+
+Every store shares the engine of the `WasmerStore`, so modules compiled once can be instantiated in
+any of them.
+*/
+#[derive(Resource, Default)]
+pub struct WasmScriptStores {
+    groups: HashMap<TypeId, SharedStore>,
+    scripts: HashMap<HandleId, SharedStore>,
+}
+
+impl WasmScriptStores {
+    /** Whether the script was last instantiated into the store of a script type. */
+    pub fn contains(&self, handle: &Handle<WasmScript>) -> bool {
+        self.scripts.contains_key(&handle.id())
+    }
+}
+
+/**
+The store for scripts of the type `group`, created alongside the `WasmerStore` on first use.
+*/
+pub(crate) fn group_store(world: &mut World, group: TypeId) -> SharedStore {
+    if let Some(store) = world
+        .get_resource_or_insert_with(WasmScriptStores::default)
+        .groups
+        .get(&group)
+    {
+        return store.clone();
+    }
+    let store = world.resource_scope(|world, main: Mut<WasmerStore>| main.sibling(world));
+    let store = Arc::new(Mutex::new(store));
+    world
+        .resource_mut::<WasmScriptStores>()
+        .groups
+        .insert(group, store.clone());
+    store
+}
+
+/**
+Record that the script was just instantiated into the store of `group`, so that calls lock that store.
+*/
+pub(crate) fn assign_group_store(world: &mut World, handle: &Handle<WasmScript>, group: TypeId) {
+    let mut stores = world.get_resource_or_insert_with(WasmScriptStores::default);
+    if let Some(store) = stores.groups.get(&group).cloned() {
+        stores.scripts.insert(handle.id(), store);
+    }
+}
+
+/**
+The stores a script environment may call into. Only `WasmScriptEnv` has the `WasmerStore` as well,
+and only `WasmScriptComponentEnv` and `WasmScriptResourceEnv` hold query access for their script type.
+*/
+pub struct ScriptStoreAccess<'a> {
+    stores: &'a WasmScriptStores,
+    main: Option<&'a mut WasmerStore>,
+    queries: Option<CallerQueryAccess<'a>>,
+}
+
+impl<'a> ScriptStoreAccess<'a> {
+    pub(crate) fn new(
+        stores: &'a WasmScriptStores,
+        main: Option<&'a mut WasmerStore>,
+        queries: Option<CallerQueryAccess<'a>>,
+    ) -> Self {
+        Self {
+            stores,
+            main,
+            queries,
+        }
+    }
+
+    /** Lock the store the script was instantiated into. */
+    pub(crate) fn lock(
+        &mut self,
+        handle: &Handle<WasmScript>,
+    ) -> Result<ScriptStoreGuard<'_>, anyhow::Error> {
+        let store = match (self.stores.scripts.get(&handle.id()), &mut self.main) {
+            (Some(store), _) => store
+                .lock()
+                .map(LockedStore::Shared)
+                .map_err(|_| anyhow::Error::msg("Script store poisoned by an earlier panic."))?,
+            (None, Some(main)) => LockedStore::Main(main),
+            (None, None) => {
+                static LOGGED: std::sync::Once = std::sync::Once::new();
+                LOGGED.call_once(|| {
+                    bevy::log::error!(
+                        "A script instantiated directly into the WasmerStore was called through a \
+                        WasmScriptComponentEnv or WasmScriptResourceEnv, which can only call scripts \
+                        instantiated by their script type. Call it through WasmScriptEnv."
+                    )
+                });
+                return Err(anyhow::Error::msg(
+                    "Script was instantiated directly into the WasmerStore; call it through WasmScriptEnv.",
+                ));
+            }
+        };
+        let previous = CURRENT_SCRIPT.with(|current| current.replace(Some(handle.clone_weak())));
+        let queries = self.queries.map(|access| {
+            let entities: *const (dyn EntityFilter + 'a) = access.entities;
+            // SAFETY: Only extends the lifetime of the pointer. See `caller_query_access`.
+            let entities: *const dyn EntityFilter = unsafe { std::mem::transmute(entities) };
+            (access.group, entities)
+        });
+        let previous_queries = CALLER_QUERY_ACCESS.with(|current| current.replace(queries));
+        Ok(ScriptStoreGuard {
+            store,
+            previous,
+            previous_queries,
+        })
+    }
+}
+
+enum LockedStore<'a> {
+    Shared(MutexGuard<'a, WasmerStore>),
+    Main(&'a mut WasmerStore),
+}
+
+pub(crate) struct ScriptStoreGuard<'a> {
+    store: LockedStore<'a>,
+    previous: Option<Handle<WasmScript>>,
+    previous_queries: Option<(TypeId, *const dyn EntityFilter)>,
+}
+
+impl Drop for ScriptStoreGuard<'_> {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        CURRENT_SCRIPT.with(|current| *current.borrow_mut() = previous);
+        CALLER_QUERY_ACCESS.with(|current| current.set(self.previous_queries));
+    }
+}
+
+impl Deref for ScriptStoreGuard<'_> {
+    type Target = Store;
+
+    fn deref(&self) -> &Store {
+        match &self.store {
+            LockedStore::Shared(store) => &store.0,
+            LockedStore::Main(store) => &store.0,
+        }
+    }
+}
+
+impl DerefMut for ScriptStoreGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Store {
+        match &mut self.store {
+            LockedStore::Shared(store) => &mut store.0,
+            LockedStore::Main(store) => &mut store.0,
+        }
+    }
+}
+
+/** The instance of a script, if it has been instantiated. */
+pub(crate) fn instantiated<'a>(
+    assets: &'a Assets<WasmScript>,
+    handle: &Handle<WasmScript>,
+) -> Result<&'a Instance, anyhow::Error> {
+    match assets.get(handle) {
+        Some(WasmScript::Instantiated(_, instance)) => Ok(instance),
+        Some(_) => Err(anyhow::Error::msg("Script not instantiated yet.")),
+        None => Err(anyhow::Error::msg("Asset not loaded")),
+    }
+}
+
+/**
+Look up an exported function, checking that it belongs to `store`. A script instantiated directly into
+the `WasmerStore` after it was instantiated by a script type is still recorded with that type's store.
+*/
+pub(crate) fn exported_function<'a>(
+    instance: &'a Instance,
+    store: &Store,
+    function_name: &str,
+) -> Result<&'a Function, anyhow::Error> {
+    let function = instance
+        .exports
+        .get_function(function_name)
+        .map_err(anyhow::Error::new)?;
+    if function.is_from_store(store) {
+        Ok(function)
+    } else {
+        Err(anyhow::Error::msg(
+            "Script was instantiated into a different store than it is recorded with.",
+        ))
+    }
+}
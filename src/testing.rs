@@ -0,0 +1,210 @@
+use bevy::{
+    asset::AssetPlugin,
+    ecs::{event::ManualEventReader, system::SystemState},
+    prelude::*,
+};
+use wasmer::Value;
+
+use crate::{
+    GeneralWasmScriptEnv, IntoScriptArgs, WasmPlugin, WasmScript, WasmScriptAdder,
+    WasmScriptComponent, WasmScriptEnv, WasmScriptError,
+};
+
+/**
+A script for a `WasmTestApp`, with only the host imports provided by this crate. Scripts which need
+the imports of a gameplay script type should be spawned with that component instead.
+*/
+#[derive(Component, Debug, Clone)]
+pub struct WasmTestScript(pub Handle<WasmScript>);
+
+impl WasmScriptComponent for WasmTestScript {
+    type ImportQueriedComponents = ();
+    type ImportResources = ();
+
+    fn get_wasm_script_handle(&self) -> &Handle<WasmScript> {
+        &self.0
+    }
+}
+
+/**
+`WasmTestApp` runs scripts in a headless `App`, with no window or asset folder, so they can be tested
+with `cargo test`, from a `#[test]` function like the one in `tests/testing.rs`:
+```
+# use bevy_wasm_scripting::*;
+# use wasmer::Value;
+# fn main() -> Result<(), anyhow::Error> {
+let mut test = WasmTestApp::new();
+let script = test.add_wat("add_one", include_str!("../assets/add_one.wat"))?;
+test.spawn(WasmTestScript(script.clone()));
+test.run_until_instantiated(&script)?;
+test.assert_returns(&script, "add_one", (41,), &[Value::I32(42)]);
+# Ok(())
+# }
+```
+
+The app has the `MinimalPlugins`, the `AssetPlugin` and the `WasmPlugin`. Gameplay script types, and
+the resources their imports use, can be added to it through `app`.
+*/
+pub struct WasmTestApp {
+    app: App,
+    errors: ManualEventReader<WasmScriptError>,
+}
+
+impl Default for WasmTestApp {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WasmTestApp {
+    /** The most updates `run_until_instantiated` waits for a script. */
+    pub const MAX_UPDATES: usize = 10;
+
+    pub fn new() -> Self {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(AssetPlugin::default())
+            .add_plugin(WasmPlugin)
+            .add_wasm_script_component::<WasmTestScript>();
+        Self {
+            app,
+            errors: ManualEventReader::default(),
+        }
+    }
+
+    pub fn app(&mut self) -> &mut App {
+        &mut self.app
+    }
+
+    pub fn world(&mut self) -> &mut World {
+        &mut self.app.world
+    }
+
+    pub fn update(&mut self) {
+        self.app.update();
+    }
+
+    /** Add a script from its `.wasm` bytes. It is compiled on the next update. */
+    pub fn add_wasm(&mut self, name: &str, wasm: &[u8]) -> Handle<WasmScript> {
+        self.app
+            .world
+            .resource_mut::<Assets<WasmScript>>()
+            .add(WasmScript::from_bytes(name, wasm))
+    }
+
+    /** Add a script from its `.wat` source. It is compiled on the next update. */
+    pub fn add_wat(&mut self, name: &str, wat: &str) -> Result<Handle<WasmScript>, anyhow::Error> {
+        let script = WasmScript::from_wat_str(name, wat)?;
+        Ok(self
+            .app
+            .world
+            .resource_mut::<Assets<WasmScript>>()
+            .add(script))
+    }
+
+    pub fn spawn(&mut self, bundle: impl Bundle) -> Entity {
+        self.app.world.spawn(bundle).id()
+    }
+
+    /**
+    Update until `handle` is instantiated, failing with any `WasmScriptError`s sent meanwhile if it
+    isn't after `MAX_UPDATES`. Scripts are only instantiated once a script component holds them.
+    */
+    pub fn run_until_instantiated(
+        &mut self,
+        handle: &Handle<WasmScript>,
+    ) -> Result<(), anyhow::Error> {
+        let mut errors = Vec::new();
+        for _ in 0..Self::MAX_UPDATES {
+            self.app.update();
+            let events = self.app.world.resource::<Events<WasmScriptError>>();
+            errors.extend(self.errors.iter(events).cloned());
+            if let Some(WasmScript::Instantiated(..)) = self.script(handle) {
+                return Ok(());
+            }
+        }
+        let state = match self.script(handle) {
+            None => "was never added",
+            Some(WasmScript::Loaded(..)) => "could not be compiled",
+            Some(WasmScript::Compiled(_)) => "was compiled, but not instantiated",
+            Some(WasmScript::Instantiated(..)) => unreachable!(),
+        };
+        Err(anyhow::Error::msg(format!(
+            "Script {} after {} updates. Errors: {:?}",
+            state,
+            Self::MAX_UPDATES,
+            errors
+        )))
+    }
+
+    pub fn script(&self, handle: &Handle<WasmScript>) -> Option<&WasmScript> {
+        self.app.world.resource::<Assets<WasmScript>>().get(handle)
+    }
+
+    /** The names of every export of an instantiated script. */
+    pub fn exports(&self, handle: &Handle<WasmScript>) -> Vec<String> {
+        match self.script(handle) {
+            Some(WasmScript::Instantiated(_, instance)) => instance
+                .module()
+                .exports()
+                .map(|export| export.name().to_string())
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /** Call an export of an instantiated script, as a system would through a `WasmScriptEnv`. */
+    pub fn call(
+        &mut self,
+        handle: &Handle<WasmScript>,
+        export: &str,
+        args: impl IntoScriptArgs,
+    ) -> Result<Box<[Value]>, anyhow::Error> {
+        let mut state = SystemState::<WasmScriptEnv>::new(&mut self.app.world);
+        let mut script_env = state.get_mut(&mut self.app.world);
+        let results =
+            script_env.call_if_instantiated_with_values(handle, export, &args.into_script_args());
+        state.apply(&mut self.app.world);
+        results
+    }
+
+    pub fn assert_exports(&self, handle: &Handle<WasmScript>, export: &str) {
+        let exports = self.exports(handle);
+        assert!(
+            exports.iter().any(|name| name == export),
+            "Script does not export {}. Exports: {:?}",
+            export,
+            exports
+        );
+    }
+
+    pub fn assert_returns(
+        &mut self,
+        handle: &Handle<WasmScript>,
+        export: &str,
+        args: impl IntoScriptArgs,
+        expected: &[Value],
+    ) {
+        match self.call(handle, export, args) {
+            Ok(results) => assert_eq!(
+                &*results, expected,
+                "{} returned {:?}, not {:?}",
+                export, results, expected
+            ),
+            Err(err) => panic!("{} failed: {}", export, err),
+        }
+    }
+
+    /** Assert that calling `export` fails, such as with a trap, returning the error. */
+    pub fn assert_traps(
+        &mut self,
+        handle: &Handle<WasmScript>,
+        export: &str,
+        args: impl IntoScriptArgs,
+    ) -> anyhow::Error {
+        match self.call(handle, export, args) {
+            Ok(results) => panic!("{} returned {:?}, but should have failed", export, results),
+            Err(err) => err,
+        }
+    }
+}
@@ -0,0 +1,251 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use bevy::{ecs::system::SystemState, prelude::*};
+use wasmer::{imports, Function, FunctionEnv, FunctionEnvMut, Imports, Store};
+
+use crate::{
+    host::HostEnv, stores::current_script, EntityId, EntityIdTrait, GeneralWasmScriptEnv,
+    ScriptGenerations, WasmScript, WasmScriptEnv,
+};
+
+/** The export called when a timer fires. */
+const TIMER_EXPORT: &str = "on_timer";
+
+/** What happens to a script's timers when it is reloaded. */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimerReloadPolicy {
+    /** Drop the timers, leaving the reloaded script to set its own. */
+    #[default]
+    Drop,
+    /** Keep the timers, which then call into the reloaded script. */
+    Keep,
+}
+
+#[derive(Debug, Clone)]
+struct ScriptTimer {
+    id: i32,
+    script: Handle<WasmScript>,
+    entity: Entity,
+    callback_id: i32,
+    remaining: Duration,
+    interval: Option<Duration>,
+    // Filled in on the first tick after the timer is set.
+    generation: Option<u64>,
+}
+
+#[derive(Debug, Default)]
+struct Timers {
+    next_id: i32,
+    reload_policy: TimerReloadPolicy,
+    timers: Vec<ScriptTimer>,
+}
+
+/** The timers set at some point, which can be put back for rollback. */
+#[derive(Debug, Clone)]
+pub(crate) struct SavedTimers {
+    next_id: i32,
+    timers: Vec<ScriptTimer>,
+}
+
+/**
+`WasmScriptTimers` holds the timers set by scripts, which call back into the script that set them on
+a later frame, as time passes according to Bevy's `Time`.
+
+Scripts set timers through the `timers` import namespace:
+* `set_timeout(entity: f64, ms: i32, callback_id: i32) -> i32` calls the script's
+  `on_timer(entity: f64, callback_id: i32)` export once, after `ms` milliseconds. Returns the id of
+  the timer, or -1 when not called from a script call.
+* `set_interval(entity: f64, ms: i32, callback_id: i32) -> i32` calls `on_timer` every `ms`
+  milliseconds, at most once per frame, until cleared.
+* `clear_timer(timer: i32) -> i32` cancels a timer. Returns 0, or -1 for an unknown timer.
+
+The entity is by convention the `EntityId` the script was called with. Timers are dropped when their
+entity is despawned or their script is removed, and on hot reload according to the
+`TimerReloadPolicy`.
+*/
+#[derive(Resource, Debug, Clone, Default)]
+pub struct WasmScriptTimers(Arc<Mutex<Timers>>);
+
+impl WasmScriptTimers {
+    pub fn with_reload_policy(reload_policy: TimerReloadPolicy) -> Self {
+        let timers = Self::default();
+        timers.set_reload_policy(reload_policy);
+        timers
+    }
+
+    pub fn set_reload_policy(&self, reload_policy: TimerReloadPolicy) {
+        if let Ok(mut timers) = self.0.lock() {
+            timers.reload_policy = reload_policy;
+        }
+    }
+
+    /** How many timers are set for `entity`. */
+    pub fn count(&self, entity: Entity) -> usize {
+        self.0.lock().map_or(0, |timers| {
+            timers
+                .timers
+                .iter()
+                .filter(|timer| timer.entity == entity)
+                .count()
+        })
+    }
+
+    /** Whether no timer is set, for any entity. */
+    pub fn is_empty(&self) -> bool {
+        self.0
+            .lock()
+            .map_or(true, |timers| timers.timers.is_empty())
+    }
+
+    /** Cancel every timer set for `entity`. */
+    pub fn clear_entity(&self, entity: Entity) {
+        if let Ok(mut timers) = self.0.lock() {
+            timers.timers.retain(|timer| timer.entity != entity);
+        }
+    }
+
+    pub(crate) fn save(&self) -> Result<SavedTimers, anyhow::Error> {
+        let timers = self
+            .0
+            .lock()
+            .map_err(|_| anyhow::Error::msg("The script timers are poisoned."))?;
+        Ok(SavedTimers {
+            next_id: timers.next_id,
+            timers: timers.timers.clone(),
+        })
+    }
+
+    /** Replace every timer with the saved ones. */
+    pub(crate) fn restore(&self, saved: &SavedTimers) -> Result<(), anyhow::Error> {
+        let mut timers = self
+            .0
+            .lock()
+            .map_err(|_| anyhow::Error::msg("The script timers are poisoned."))?;
+        timers.next_id = saved.next_id;
+        timers.timers = saved.timers.clone();
+        Ok(())
+    }
+
+    fn set(&self, entity: EntityId, ms: i32, callback_id: i32, repeat: bool) -> i32 {
+        let (Some(script), Ok(mut timers)) = (current_script(), self.0.lock()) else {
+            return -1;
+        };
+        let id = timers.next_id;
+        timers.next_id = timers.next_id.wrapping_add(1).max(0);
+        let duration = Duration::from_millis(ms.max(0) as u64);
+        timers.timers.push(ScriptTimer {
+            id,
+            script,
+            entity: entity.to_entity(),
+            callback_id,
+            remaining: duration,
+            interval: repeat.then_some(duration),
+            generation: None,
+        });
+        id
+    }
+}
+
+fn set_timeout(env: FunctionEnvMut<HostEnv>, entity: EntityId, ms: i32, callback_id: i32) -> i32 {
+    env.data().timers.set(entity, ms, callback_id, false)
+}
+
+fn set_interval(env: FunctionEnvMut<HostEnv>, entity: EntityId, ms: i32, callback_id: i32) -> i32 {
+    env.data().timers.set(entity, ms, callback_id, true)
+}
+
+fn clear_timer(env: FunctionEnvMut<HostEnv>, timer: i32) -> i32 {
+    let Ok(mut timers) = env.data().timers.0.lock() else {
+        return -1;
+    };
+    let count = timers.timers.len();
+    timers.timers.retain(|existing| existing.id != timer);
+    if timers.timers.len() < count {
+        0
+    } else {
+        -1
+    }
+}
+
+pub(crate) fn script_timer_imports(store: &mut Store, env: &FunctionEnv<HostEnv>) -> Imports {
+    imports! {
+        "timers" => {
+            "set_timeout" => Function::new_typed_with_env(store, env, set_timeout),
+            "set_interval" => Function::new_typed_with_env(store, env, set_interval),
+            "clear_timer" => Function::new_typed_with_env(store, env, clear_timer),
+        }
+    }
+}
+
+type TickState = SystemState<(
+    Res<'static, WasmScriptTimers>,
+    Res<'static, Time>,
+    Res<'static, ScriptGenerations>,
+    Res<'static, Assets<WasmScript>>,
+    Query<'static, 'static, Entity>,
+    WasmScriptEnv<'static, 'static>,
+)>;
+
+// Exclusive, like every crate system calling scripts through `WasmScriptEnv`. See `WasmScriptStores`.
+pub(crate) fn tick_script_timers(world: &mut World, state: &mut TickState) {
+    let (timers, time, generations, assets, entities, mut script_env) = state.get_mut(world);
+    let delta = time.delta();
+    let due: Vec<(Handle<WasmScript>, Entity, i32)> = {
+        let Ok(mut timers) = timers.0.lock() else {
+            return;
+        };
+        let reload_policy = timers.reload_policy;
+        let mut due = Vec::new();
+        timers.timers.retain_mut(|timer| {
+            if !entities.contains(timer.entity) {
+                return false;
+            }
+            let instantiated = match assets.get(&timer.script) {
+                None => return false,
+                Some(script) => matches!(script, WasmScript::Instantiated(..)),
+            };
+            let generation = generations.generation(&timer.script);
+            match timer.generation {
+                None => timer.generation = Some(generation),
+                Some(set_in) if set_in != generation => match reload_policy {
+                    TimerReloadPolicy::Drop => return false,
+                    TimerReloadPolicy::Keep => timer.generation = Some(generation),
+                },
+                Some(_) => {}
+            }
+            timer.remaining = timer.remaining.saturating_sub(delta);
+            // Timers wait while their script is being reloaded.
+            if !timer.remaining.is_zero() || !instantiated {
+                return true;
+            }
+            due.push((timer.script.clone(), timer.entity, timer.callback_id));
+            match timer.interval {
+                Some(interval) => {
+                    timer.remaining = interval;
+                    true
+                }
+                None => false,
+            }
+        });
+        due
+    };
+    // Called without the lock held, so callbacks may set and clear timers.
+    for (script, entity, callback_id) in due {
+        if let Err(err) = script_env.call_if_instantiated_2::<EntityId, i32, ()>(
+            &script,
+            TIMER_EXPORT,
+            EntityId::from_entity(entity),
+            callback_id,
+        ) {
+            bevy::log::error!(
+                "Failed to run timer {} for {:?}: {}",
+                callback_id,
+                entity,
+                err
+            );
+        }
+    }
+}
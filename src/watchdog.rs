@@ -0,0 +1,466 @@
+use std::{
+    fmt::Display,
+    sync::{
+        atomic::{AtomicBool, AtomicI32, Ordering},
+        Arc, Condvar, Mutex, Once,
+    },
+    time::{Duration, Instant},
+};
+
+use bevy::prelude::*;
+use wasmer::{Instance, RuntimeError, Store};
+
+use crate::WasmDeterminism;
+
+/**
+The name of the exported global which every compiled module checks at function entry and at the top
+of every loop. The watchdog sets it to interrupt a script.
+*/
+pub(crate) const DEADLINE_GLOBAL: &str = "__bevy_wasm_deadline";
+
+/**
+Returned, wrapped in an `anyhow::Error`, when a script call is interrupted by the `WasmWatchdog`.
+*/
+#[derive(Debug, Clone)]
+pub struct ScriptTimeout {
+    pub function: String,
+    pub timeout: Duration,
+}
+
+impl Display for ScriptTimeout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} was interrupted after running longer than {:?}",
+            self.function, self.timeout
+        )
+    }
+}
+
+impl std::error::Error for ScriptTimeout {}
+
+struct Armed {
+    generation: u64,
+    deadline: Instant,
+    // The address of the deadline global, from `deadline_flag`. Only written through `set_flag`.
+    flag: usize,
+}
+
+#[derive(Default)]
+struct WatchdogState {
+    generation: u64,
+    // Calls may be guarded from several threads at once, when their scripts use different stores.
+    armed: Vec<Armed>,
+    fired: Vec<u64>,
+    shutdown: bool,
+}
+
+#[derive(Default)]
+struct WatchdogShared {
+    state: Mutex<WatchdogState>,
+    wake: Condvar,
+}
+
+/**
+The `WasmWatchdog` interrupts script calls which run longer than `timeout`. Calls through
+`GeneralWasmScriptEnv` are guarded automatically, and return a `ScriptTimeout` error when
+interrupted. The script is left instantiated, and may be called again.
+
+With the `non-js` feature, every compiled module checks a flag at function entry and at the top of
+every loop, so scripts don't need to be built with any metering. A single background thread sets the
+flag when the deadline passes, for any number of calls running in parallel. No timeout is set by
+default. Timeouts are not enforced with the `js` feature, nor if the background thread can't be
+started.
+
+Whether a call runs past a wall-clock deadline depends on the machine, so calls are not timed out in
+deterministic mode, when `WasmDeterminism` is enabled, whatever the timeout is set to. The fuel
+counted by `WasmScriptMetrics` is the same on every machine, and can be checked against a budget
+instead.
+*/
+#[derive(Resource)]
+pub struct WasmWatchdog {
+    timeout: Option<Duration>,
+    shared: Arc<WatchdogShared>,
+    started: Once,
+    running: AtomicBool,
+    warned_deterministic: Once,
+}
+
+impl Default for WasmWatchdog {
+    fn default() -> Self {
+        Self {
+            timeout: None,
+            shared: Default::default(),
+            started: Once::new(),
+            running: AtomicBool::new(false),
+            warned_deterministic: Once::new(),
+        }
+    }
+}
+
+impl WasmWatchdog {
+    pub fn with_timeout(timeout: Duration) -> Self {
+        let mut watchdog = Self::default();
+        watchdog.timeout = Some(timeout);
+        watchdog
+    }
+
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /** Set the timeout. It is not enforced in deterministic mode. */
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    /** The timeout calls are guarded with, which is none in deterministic mode. */
+    fn enforced_timeout(&self, determinism: Option<&WasmDeterminism>) -> Option<Duration> {
+        let timeout = self.timeout?;
+        if determinism.is_some_and(WasmDeterminism::is_enabled) {
+            self.warned_deterministic.call_once(|| {
+                warn!("Script timeouts are not enforced in deterministic mode.");
+            });
+            return None;
+        }
+        Some(timeout)
+    }
+
+    /**
+    Run `call`, interrupting it if it runs past the timeout. Scripts compiled without the deadline
+    check (as with the `js` feature) run unguarded, as does every call in deterministic mode.
+    */
+    pub(crate) fn guard<R>(
+        &self,
+        determinism: Option<&WasmDeterminism>,
+        store: &mut Store,
+        instance: &Instance,
+        function_name: &str,
+        call: impl FnOnce(&mut Store) -> Result<R, RuntimeError>,
+    ) -> Result<R, anyhow::Error> {
+        self.batch(determinism, store, instance)
+            .guard(store, function_name, call)
+    }
+
+    /**
+    Prepare the watchdog once for a batch of calls into `instance`, each of which is then guarded by
+    `BatchGuard::guard` with its own deadline.
+    */
+    pub(crate) fn batch<'a>(
+        &'a self,
+        determinism: Option<&WasmDeterminism>,
+        store: &mut Store,
+        instance: &'a Instance,
+    ) -> BatchGuard<'a> {
+        let armed = match (
+            self.enforced_timeout(determinism),
+            deadline_flag(store, instance),
+        ) {
+            (Some(timeout), Some(flag)) if self.start() => {
+                let mut state = self.shared.state.lock().unwrap();
+                state.generation += 1;
+                Some(ArmedBatch {
+                    timeout,
+                    flag,
+                    generation: state.generation,
+                })
+            }
+            _ => None,
+        };
+        BatchGuard {
+            shared: &self.shared,
+            _instance: instance,
+            armed,
+        }
+    }
+
+    /** Start the background thread, if it isn't already. False if it could not be started. */
+    fn start(&self) -> bool {
+        let shared = self.shared.clone();
+        self.started.call_once(|| {
+            match std::thread::Builder::new()
+                .name("wasm-script-watchdog".to_string())
+                .spawn(move || watch(shared))
+            {
+                Ok(_) => self.running.store(true, Ordering::Release),
+                Err(err) => bevy::log::error!(
+                    "Could not start the wasm script watchdog, so script timeouts are disabled: {}",
+                    err
+                ),
+            }
+        });
+        self.running.load(Ordering::Acquire)
+    }
+}
+
+impl Drop for WasmWatchdog {
+    fn drop(&mut self) {
+        if let Ok(mut state) = self.shared.state.lock() {
+            state.shutdown = true;
+        }
+        self.shared.wake.notify_one();
+    }
+}
+
+fn watch(shared: Arc<WatchdogShared>) {
+    let mut state = shared.state.lock().unwrap();
+    while !state.shutdown {
+        let now = Instant::now();
+        let state_ref = &mut *state;
+        state_ref.armed.retain(|armed| {
+            if now >= armed.deadline {
+                set_flag(armed.flag, 1);
+                state_ref.fired.push(armed.generation);
+                false
+            } else {
+                true
+            }
+        });
+        match state.armed.iter().map(|armed| armed.deadline).min() {
+            None => state = shared.wake.wait(state).unwrap(),
+            Some(deadline) => {
+                state = shared.wake.wait_timeout(state, deadline - now).unwrap().0;
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct ArmedBatch {
+    timeout: Duration,
+    flag: usize,
+    generation: u64,
+}
+
+/**
+The watchdog, armed for calls into one instance. It is armed by the first call, and each later call
+moves the deadline forward, which doesn't need to wake the watchdog thread, since the deadline only
+moves later. Disarmed when dropped.
+*/
+pub(crate) struct BatchGuard<'a> {
+    shared: &'a WatchdogShared,
+    _instance: &'a Instance,
+    armed: Option<ArmedBatch>,
+}
+
+impl BatchGuard<'_> {
+    /** Run `call`, interrupting it if it runs past the timeout. */
+    pub(crate) fn guard<R>(
+        &self,
+        store: &mut Store,
+        function_name: &str,
+        call: impl FnOnce(&mut Store) -> Result<R, RuntimeError>,
+    ) -> Result<R, anyhow::Error> {
+        let Some(ArmedBatch {
+            timeout,
+            flag,
+            generation,
+        }) = self.armed
+        else {
+            return call(store).map_err(anyhow::Error::new);
+        };
+        let rearmed = {
+            let mut state = self.shared.state.lock().unwrap();
+            let deadline = Instant::now() + timeout;
+            match state
+                .armed
+                .iter_mut()
+                .find(|armed| armed.generation == generation)
+            {
+                Some(armed) => {
+                    armed.deadline = deadline;
+                    false
+                }
+                None => {
+                    // Either this is the first call, or the deadline of an earlier call passed.
+                    state.fired.retain(|fired| *fired != generation);
+                    set_flag(flag, 0);
+                    state.armed.push(Armed {
+                        generation,
+                        deadline,
+                        flag,
+                    });
+                    true
+                }
+            }
+        };
+        if rearmed {
+            self.shared.wake.notify_one();
+        }
+        let result = call(store);
+        let fired = self
+            .shared
+            .state
+            .lock()
+            .unwrap()
+            .fired
+            .contains(&generation);
+        // The deadline may pass just after the call finished, in which case its result stands.
+        if fired && result.is_err() {
+            Err(anyhow::Error::new(ScriptTimeout {
+                function: function_name.to_string(),
+                timeout,
+            }))
+        } else {
+            result.map_err(anyhow::Error::new)
+        }
+    }
+}
+
+impl Drop for BatchGuard<'_> {
+    fn drop(&mut self) {
+        let Some(armed) = self.armed else {
+            return;
+        };
+        if let Ok(mut state) = self.shared.state.lock() {
+            state
+                .armed
+                .retain(|other| other.generation != armed.generation);
+            state.fired.retain(|fired| *fired != armed.generation);
+        }
+        set_flag(armed.flag, 0);
+    }
+}
+
+/**
+Write the deadline global at `flag`, an address from `deadline_flag`.
+
+The address is only written while it is valid:
+* It points at the `VMGlobalDefinition` of an instance's deadline global, which is 16-byte aligned and
+  starts with the global's value, so it is a properly aligned `i32`.
+* That definition is owned by the store, and doesn't move or get freed while the instance lives. A
+  `BatchGuard` borrows the instance, and its caller holds the store, for as long as it is armed.
+* The watchdog thread only writes flags which are armed, under the state lock. A `BatchGuard`
+  disarms its flag under that lock when dropped, so nothing writes the flag once its calls are done.
+
+Wasm reads the global with plain loads while the watchdog thread writes it. Writing through an
+`AtomicI32` keeps that write from tearing.
+*/
+fn set_flag(flag: usize, value: i32) {
+    // SAFETY: See above. `flag` is a live, aligned i32 for as long as it may be written.
+    unsafe { (*(flag as *const AtomicI32)).store(value, Ordering::SeqCst) }
+}
+
+/** The address of the instance's deadline global, as written by `set_flag`. */
+#[cfg(feature = "non-js")]
+fn deadline_flag(store: &mut Store, instance: &Instance) -> Option<usize> {
+    use wasmer::{AsStoreMut, Extern};
+    use wasmer_vm::VMExtern;
+
+    let global = instance.exports.get_global(DEADLINE_GLOBAL).ok()?;
+    match Extern::Global(global.clone()).to_vm_extern() {
+        VMExtern::Global(handle) => {
+            Some(handle.get(store.objects_mut()).vmglobal().as_ptr() as usize)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(not(feature = "non-js"))]
+fn deadline_flag(_store: &mut Store, _instance: &Instance) -> Option<usize> {
+    None
+}
+
+#[cfg(feature = "non-js")]
+pub(crate) use middleware::DeadlineMiddleware;
+
+#[cfg(feature = "non-js")]
+mod middleware {
+    use std::sync::Mutex;
+
+    use wasmer::{
+        wasmparser::{Operator, Type as WpType, TypeOrFuncType},
+        FunctionMiddleware, GlobalInit, LocalFunctionIndex, MiddlewareError, MiddlewareReaderState,
+        ModuleMiddleware, Mutability, Type,
+    };
+    use wasmer_types::{ExportIndex, GlobalIndex, GlobalType, ModuleInfo};
+
+    use super::DEADLINE_GLOBAL;
+
+    /**
+    Adds the deadline global to every module, and a check of it at the start of every function and
+    loop which traps once it is set.
+
+    The index of the global differs between modules, but `generate_function_middleware` is not told
+    which module it is for. Each `DeadlineMiddleware` is created for, and owned by, the compiler of a
+    single engine. Stores made by `WasmerStore::sibling` share that engine, but wasmer holds the
+    engine's lock for the whole of a `Module::new`, from `transform_module_info` until every function
+    is compiled. So modules are compiled one at a time, and the index stored here is always the one
+    for the module being compiled. The middleware must never be shared with another engine.
+    */
+    #[derive(Debug, Default)]
+    pub(crate) struct DeadlineMiddleware {
+        global_index: Mutex<Option<GlobalIndex>>,
+    }
+
+    impl ModuleMiddleware for DeadlineMiddleware {
+        fn generate_function_middleware(
+            &self,
+            _local_function_index: LocalFunctionIndex,
+        ) -> Box<dyn FunctionMiddleware> {
+            let global_index = self
+                .global_index
+                .lock()
+                .unwrap()
+                .expect("Deadline global not added to module");
+            Box::new(FunctionDeadline {
+                global_index: global_index.as_u32(),
+                entered: false,
+            })
+        }
+
+        fn transform_module_info(&self, module_info: &mut ModuleInfo) {
+            let global_index = module_info
+                .globals
+                .push(GlobalType::new(Type::I32, Mutability::Var));
+            module_info
+                .global_initializers
+                .push(GlobalInit::I32Const(0));
+            module_info.exports.insert(
+                DEADLINE_GLOBAL.to_string(),
+                ExportIndex::Global(global_index),
+            );
+            *self.global_index.lock().unwrap() = Some(global_index);
+        }
+    }
+
+    #[derive(Debug)]
+    struct FunctionDeadline {
+        global_index: u32,
+        entered: bool,
+    }
+
+    impl FunctionDeadline {
+        fn check(&self) -> [Operator<'static>; 4] {
+            [
+                Operator::GlobalGet {
+                    global_index: self.global_index,
+                },
+                Operator::If {
+                    ty: TypeOrFuncType::Type(WpType::EmptyBlockType),
+                },
+                Operator::Unreachable,
+                Operator::End,
+            ]
+        }
+    }
+
+    impl FunctionMiddleware for FunctionDeadline {
+        fn feed<'a>(
+            &mut self,
+            operator: Operator<'a>,
+            state: &mut MiddlewareReaderState<'a>,
+        ) -> Result<(), MiddlewareError> {
+            if !self.entered {
+                self.entered = true;
+                state.extend(self.check());
+            }
+            let is_loop = matches!(operator, Operator::Loop { .. });
+            state.push_operator(operator);
+            if is_loop {
+                state.extend(self.check());
+            }
+            Ok(())
+        }
+    }
+}
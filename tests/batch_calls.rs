@@ -0,0 +1,76 @@
+use std::time::Duration;
+
+use bevy::{ecs::system::SystemState, prelude::Handle};
+use bevy_wasm_scripting::*;
+
+const COUNTER: &str = r#"
+(module
+  (global $count (mut i32) (i32.const 0))
+  (func (export "tick") (result i32)
+    (global.set $count (i32.add (global.get $count) (i32.const 1)))
+    (global.get $count)))
+"#;
+
+fn call_batch<Rets: wasmer::WasmTypeList>(
+    test: &mut WasmTestApp,
+    call: impl FnOnce(&mut WasmScriptEnv) -> Result<BatchResults<u32, Rets>, anyhow::Error>,
+) -> Result<BatchResults<u32, Rets>, anyhow::Error> {
+    let mut state = SystemState::<WasmScriptEnv>::new(test.world());
+    let mut env = state.get_mut(test.world());
+    call(&mut env)
+}
+
+fn instantiated(test: &mut WasmTestApp, name: &str, wat: &str) -> Handle<WasmScript> {
+    let script = test.add_wat(name, wat).unwrap();
+    test.spawn(WasmTestScript(script.clone()));
+    test.run_until_instantiated(&script).unwrap();
+    script
+}
+
+#[test]
+fn batches_without_arguments_call_once_per_key() -> Result<(), anyhow::Error> {
+    let mut test = WasmTestApp::new();
+    let script = instantiated(&mut test, "counter", COUNTER);
+    let results = call_batch::<i32>(&mut test, |env| {
+        env.call_batch_if_instantiated_0(&script, "tick", (0..3).map(|key| (key, ())))
+    })?;
+    let counts: Vec<(u32, i32)> = results
+        .into_iter()
+        .map(|(key, result)| (key, result.unwrap()))
+        .collect();
+    assert_eq!(counts, [(0, 1), (1, 2), (2, 3)]);
+    let metrics = test
+        .world()
+        .resource::<WasmScriptMetrics>()
+        .get("counter")
+        .unwrap();
+    assert_eq!(metrics.calls, 3);
+    Ok(())
+}
+
+#[test]
+fn each_call_of_a_batch_gets_its_own_deadline() -> Result<(), anyhow::Error> {
+    let mut test = WasmTestApp::new();
+    test.world()
+        .resource_mut::<WasmWatchdog>()
+        .set_timeout(Some(Duration::from_millis(50)));
+    let script = instantiated(
+        &mut test,
+        "spin",
+        include_str!("../assets/spin_forever.wat"),
+    );
+    let results = call_batch::<i32>(&mut test, |env| {
+        env.call_batch_if_instantiated_1(&script, "main", [(0, (3,)), (1, (-1,)), (2, (5,))])
+    })?;
+    assert!(matches!(results[0], (0, Ok(3))));
+    assert!(results[1].1.as_ref().unwrap_err().is::<ScriptTimeout>());
+    // The timeout of one call doesn't interrupt the next.
+    assert!(matches!(results[2], (2, Ok(5))));
+    let metrics = test
+        .world()
+        .resource::<WasmScriptMetrics>()
+        .get("spin")
+        .unwrap();
+    assert_eq!((metrics.calls, metrics.errors), (3, 1));
+    Ok(())
+}
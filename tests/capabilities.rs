@@ -0,0 +1,123 @@
+use bevy::{
+    ecs::event::ManualEventReader,
+    prelude::{Component, Events, Handle},
+};
+use bevy_wasm_scripting::*;
+use wasmer::{imports, Function, Global, Imports, Value};
+
+const PLAY_SOUND: &str = r#"
+(module
+  (import "audio" "play_sound" (func $play_sound (param i32)))
+  (func (export "main") (param i32) (result i32)
+    local.get 0
+    call $play_sound
+    local.get 0))
+"#;
+
+const READ_VOLUME: &str = r#"
+(module
+  (import "audio" "volume" (global $volume i32))
+  (func (export "main") (result i32)
+    global.get $volume))
+"#;
+
+fn audio_imports(wasmer_store: &mut WasmerStore) -> Imports {
+    imports! {
+        "audio" => {
+            "play_sound" => Function::new_typed(&mut wasmer_store.0, |_sound: i32| {}),
+            "volume" => Global::new(&mut wasmer_store.0, Value::I32(11)),
+        }
+    }
+}
+
+#[derive(Component)]
+struct ModScript(Handle<WasmScript>);
+
+impl WasmScriptComponent for ModScript {
+    type ImportQueriedComponents = ();
+    type ImportResources = ();
+
+    fn get_wasm_script_handle(&self) -> &Handle<WasmScript> {
+        &self.0
+    }
+
+    fn get_imports_from_world(wasmer_store: &mut WasmerStore, _world: &WorldPointer) -> Imports {
+        audio_imports(wasmer_store)
+    }
+}
+
+#[derive(Component)]
+struct TrustedScript(Handle<WasmScript>);
+
+impl WasmScriptComponent for TrustedScript {
+    type ImportQueriedComponents = ();
+    type ImportResources = ();
+
+    fn get_wasm_script_handle(&self) -> &Handle<WasmScript> {
+        &self.0
+    }
+
+    fn get_imports_from_world(wasmer_store: &mut WasmerStore, _world: &WorldPointer) -> Imports {
+        audio_imports(wasmer_store)
+    }
+
+    fn capabilities() -> ScriptCapabilities {
+        ScriptCapabilities::only(["audio"])
+    }
+}
+
+fn audio_app() -> WasmTestApp {
+    let mut test = WasmTestApp::new();
+    test.app()
+        .add_wasm_capability("audio", &["audio"])
+        .add_wasm_script_component::<ModScript>()
+        .add_wasm_script_component::<TrustedScript>();
+    test
+}
+
+fn denied(test: &mut WasmTestApp, reader: &mut ManualEventReader<WasmScriptError>) -> usize {
+    let events = test.world().resource::<Events<WasmScriptError>>();
+    reader
+        .iter(events)
+        .filter(|error| matches!(error, WasmScriptError::CapabilityDenied { .. }))
+        .count()
+}
+
+#[test]
+fn ungranted_functions_are_stubbed() -> Result<(), anyhow::Error> {
+    let mut test = audio_app();
+    let mut reader = ManualEventReader::default();
+    let script = test.add_wat("play_sound", PLAY_SOUND)?;
+    test.spawn(ModScript(script.clone()));
+    test.run_until_instantiated(&script)?;
+    test.assert_traps(&script, "main", (1,));
+    test.update();
+    assert_eq!(denied(&mut test, &mut reader), 1);
+    Ok(())
+}
+
+#[test]
+fn granted_functions_are_called() -> Result<(), anyhow::Error> {
+    let mut test = audio_app();
+    let script = test.add_wat("play_sound", PLAY_SOUND)?;
+    test.spawn(TrustedScript(script.clone()));
+    test.run_until_instantiated(&script)?;
+    test.assert_returns(&script, "main", (1,), &[Value::I32(1)]);
+    Ok(())
+}
+
+#[test]
+fn ungranted_globals_are_left_out() -> Result<(), anyhow::Error> {
+    let mut test = audio_app();
+    let mut reader = ManualEventReader::default();
+    let script = test.add_wat("read_volume", READ_VOLUME)?;
+    test.spawn(ModScript(script.clone()));
+    assert!(test.run_until_instantiated(&script).is_err());
+    assert!(denied(&mut test, &mut reader) > 0);
+
+    let trusted = test.add_wat("read_volume", READ_VOLUME)?;
+    test.spawn(TrustedScript(trusted.clone()));
+    test.run_until_instantiated(&trusted)?;
+    test.assert_returns(&trusted, "main", (), &[Value::I32(11)]);
+    Ok(())
+}
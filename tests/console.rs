@@ -0,0 +1,54 @@
+use bevy_wasm_scripting::*;
+
+fn console_app() -> WasmTestApp {
+    let mut test = WasmTestApp::new();
+    test.app().add_plugin(WasmConsolePlugin);
+    test
+}
+
+fn run(test: &mut WasmTestApp, command: &str) -> Vec<String> {
+    test.world().resource_mut::<WasmConsole>().run(command);
+    test.update();
+    test.world().resource_mut::<WasmConsole>().take_output()
+}
+
+#[test]
+fn entities_are_called_by_index() -> Result<(), anyhow::Error> {
+    let mut test = console_app();
+    let script = test.add_wat("add_one", include_str!("../assets/add_one.wat"))?;
+    let entity = test.spawn(WasmTestScript(script.clone()));
+    test.run_until_instantiated(&script)?;
+    let output = run(&mut test, &format!("call {:?} add_one 41", entity));
+    assert!(output[1].ends_with("returned [I32(42)]"), "{:?}", output);
+    // Freed indices name no entity.
+    test.world().despawn(entity);
+    let output = run(&mut test, &format!("call {} add_one 41", entity.index()));
+    assert!(output[1].starts_with("error: No entity"), "{:?}", output);
+    Ok(())
+}
+
+#[test]
+fn targets_fall_back_to_script_names() -> Result<(), anyhow::Error> {
+    let mut test = console_app();
+    // An entity without scripts, whose index is also the name of a script.
+    let entity = test.spawn(());
+    let numbered = test.add_wat(
+        &entity.index().to_string(),
+        include_str!("../assets/add_one.wat"),
+    )?;
+    let versioned = test.add_wat("1v2", include_str!("../assets/add_one.wat"))?;
+    test.spawn(WasmTestScript(numbered.clone()));
+    test.spawn(WasmTestScript(versioned.clone()));
+    test.run_until_instantiated(&numbered)?;
+    test.run_until_instantiated(&versioned)?;
+    for target in [entity.index().to_string(), "1v2".to_string()] {
+        let output = run(&mut test, &format!("call {} add_one 41", target));
+        assert_eq!(
+            output[1],
+            format!("{}.add_one returned [I32(42)]", target),
+            "{:?}",
+            output
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,109 @@
+use bevy::{
+    ecs::system::SystemState,
+    prelude::{Entity, Handle, Mut},
+};
+use bevy_wasm_scripting::*;
+use wasmer::Value;
+
+// Stands in for a module transformed by asyncify: the exports only track the state asyncify would
+// be in, which is all the host looks at.
+const FAKE_ASYNCIFY: &str = r#"
+(module
+  (import "coroutine" "wait_frames" (func $wait_frames (param i32)))
+  (global $state (mut i32) (i32.const 0))
+  (global $freed (mut i32) (i32.const 0))
+  (func (export "asyncify_start_unwind") (param i32) (global.set $state (i32.const 1)))
+  (func (export "asyncify_stop_unwind") (global.set $state (i32.const 0)))
+  (func (export "asyncify_start_rewind") (param i32) (global.set $state (i32.const 2)))
+  (func (export "asyncify_stop_rewind") (global.set $state (i32.const 0)))
+  (func (export "asyncify_get_state") (result i32) (global.get $state))
+  (func (export "coroutine_stack_alloc") (result i32) (i32.const 1024))
+  (func (export "coroutine_stack_free") (param i32)
+    (global.set $freed (i32.add (global.get $freed) (i32.const 1))))
+  (func (export "state") (result i32) (global.get $state))
+  (func (export "freed") (result i32) (global.get $freed))
+  ;; Waits for a frame, then traps when resumed.
+  (func (export "trap_when_resumed")
+    (if (i32.eq (global.get $state) (i32.const 2)) (then unreachable))
+    (call $wait_frames (i32.const 1)))
+  ;; Waits for a long time, then finishes.
+  (func (export "wait_long")
+    (call $wait_frames (i32.const 1000))))
+"#;
+
+fn start(
+    test: &mut WasmTestApp,
+    entity: Entity,
+    script: &Handle<WasmScript>,
+    export: &str,
+) -> Result<bool, anyhow::Error> {
+    let mut state = SystemState::<WasmScriptEnv>::new(test.world());
+    test.world()
+        .resource_scope(|world, mut coroutines: Mut<WasmCoroutines>| {
+            let mut env = state.get_mut(world);
+            coroutines.start(&mut env, entity, script, export, ())
+        })
+}
+
+fn coroutine_app() -> Result<(WasmTestApp, Entity, Handle<WasmScript>), anyhow::Error> {
+    let mut test = WasmTestApp::new();
+    let script = test.add_wat("fake_asyncify", FAKE_ASYNCIFY)?;
+    let entity = test.spawn(WasmTestScript(script.clone()));
+    test.run_until_instantiated(&script)?;
+    Ok((test, entity, script))
+}
+
+#[test]
+fn failed_resumes_stop_rewinding() -> Result<(), anyhow::Error> {
+    let (mut test, entity, script) = coroutine_app()?;
+    assert!(start(&mut test, entity, &script, "trap_when_resumed")?);
+    test.update();
+    test.update();
+    assert!(!test
+        .world()
+        .resource::<WasmCoroutines>()
+        .is_suspended(entity));
+    test.assert_returns(&script, "state", (), &[Value::I32(0)]);
+    test.assert_returns(&script, "freed", (), &[Value::I32(1)]);
+    Ok(())
+}
+
+#[test]
+fn despawned_entities_free_their_coroutines() -> Result<(), anyhow::Error> {
+    let (mut test, entity, script) = coroutine_app()?;
+    let other = test.spawn(());
+    assert!(start(&mut test, other, &script, "wait_long")?);
+    test.world().despawn(other);
+    test.update();
+    assert!(!test
+        .world()
+        .resource::<WasmCoroutines>()
+        .is_suspended(other));
+    test.assert_returns(&script, "freed", (), &[Value::I32(1)]);
+    // The script's own entity is unaffected.
+    assert!(start(&mut test, entity, &script, "wait_long")?);
+    test.update();
+    assert!(test
+        .world()
+        .resource::<WasmCoroutines>()
+        .is_suspended(entity));
+    Ok(())
+}
+
+#[test]
+fn cancelled_coroutines_are_freed() -> Result<(), anyhow::Error> {
+    let (mut test, entity, script) = coroutine_app()?;
+    assert!(start(&mut test, entity, &script, "wait_long")?);
+    let mut state = SystemState::<WasmScriptEnv>::new(test.world());
+    test.world()
+        .resource_scope(|world, mut coroutines: Mut<WasmCoroutines>| {
+            let mut env = state.get_mut(world);
+            coroutines.cancel(&mut env, entity);
+        });
+    assert!(!test
+        .world()
+        .resource::<WasmCoroutines>()
+        .is_suspended(entity));
+    test.assert_returns(&script, "freed", (), &[Value::I32(1)]);
+    Ok(())
+}
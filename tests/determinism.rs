@@ -0,0 +1,50 @@
+use std::time::Duration;
+
+use bevy::{asset::AssetPlugin, ecs::system::SystemState, prelude::*};
+use bevy_wasm_scripting::*;
+
+// Long enough to run past the watchdog's timeout.
+const SPINS: i32 = 200_000_000;
+
+fn deterministic_app(determinism: WasmDeterminism) -> App {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_plugin(AssetPlugin::default())
+        .insert_resource(determinism)
+        .add_plugin(WasmPlugin)
+        .add_wasm_script_component::<WasmTestScript>();
+    app
+}
+
+/** Spin in a script, after a watchdog with a short timeout was inserted over the plugin's. */
+fn spin(determinism: WasmDeterminism) -> Result<Result<i32, anyhow::Error>, anyhow::Error> {
+    let mut app = deterministic_app(determinism);
+    app.insert_resource(WasmWatchdog::with_timeout(Duration::from_millis(20)));
+    let script = app
+        .world
+        .resource_mut::<Assets<WasmScript>>()
+        .add(WasmScript::from_wat_str(
+            "spin",
+            include_str!("../assets/spin_forever.wat"),
+        )?);
+    app.world.spawn(WasmTestScript(script.clone()));
+    for _ in 0..WasmTestApp::MAX_UPDATES {
+        app.update();
+    }
+    let mut state = SystemState::<WasmScriptEnv>::new(&mut app.world);
+    let mut env = state.get_mut(&mut app.world);
+    Ok(env.call_if_instantiated_1::<i32, i32>(&script, "main", SPINS))
+}
+
+#[test]
+fn deterministic_mode_does_not_time_out_calls() -> Result<(), anyhow::Error> {
+    assert_eq!(spin(WasmDeterminism::enabled())??, SPINS);
+    Ok(())
+}
+
+#[test]
+fn calls_time_out_otherwise() -> Result<(), anyhow::Error> {
+    let err = spin(WasmDeterminism::default())?.unwrap_err();
+    assert!(err.is::<ScriptTimeout>(), "{}", err);
+    Ok(())
+}
@@ -0,0 +1,61 @@
+use bevy::{ecs::event::ManualEventReader, prelude::Events};
+use bevy_wasm_scripting::*;
+use serde::Deserialize;
+use wasmer::Value;
+
+#[derive(Debug, Deserialize)]
+struct ScoreEvent {
+    points: u32,
+}
+
+const OUT_OF_BOUNDS: &str = r#"
+(module
+  (import "events" "event_type_id" (func $event_type_id (param i32 i32) (result i32)))
+  (import "events" "send_event" (func $send_event (param i32 i32 i32) (result i32)))
+  (memory 1)
+  (data (i32.const 0) "score")
+  (func (export "huge_name") (result i32)
+    (call $event_type_id (i32.const 0) (i32.const 2147483647)))
+  (func (export "huge_payload") (result i32)
+    (call $send_event
+      (call $event_type_id (i32.const 0) (i32.const 5))
+      (i32.const 16)
+      (i32.const 2147483647)))
+  (func (export "past_the_end") (result i32)
+    (call $event_type_id (i32.const 65535) (i32.const 5)))
+  (export "memory" (memory 0)))
+"#;
+
+fn scoring_app() -> WasmTestApp {
+    let mut test = WasmTestApp::new();
+    test.app().add_wasm_script_event::<ScoreEvent>("score");
+    test
+}
+
+#[test]
+fn scripts_send_registered_events() -> Result<(), anyhow::Error> {
+    let mut test = scoring_app();
+    let mut reader = ManualEventReader::<ScoreEvent>::default();
+    let script = test.add_wat("send_score", include_str!("../assets/send_score.wat"))?;
+    test.spawn(WasmTestScript(script.clone()));
+    test.run_until_instantiated(&script)?;
+    test.assert_returns(&script, "main", (), &[Value::I32(0)]);
+    test.update();
+    let events = test.world().resource::<Events<ScoreEvent>>();
+    let points: Vec<u32> = reader.iter(events).map(|event| event.points).collect();
+    assert_eq!(points, [10]);
+    Ok(())
+}
+
+#[test]
+fn out_of_bounds_reads_are_refused() -> Result<(), anyhow::Error> {
+    let mut test = scoring_app();
+    let script = test.add_wat("out_of_bounds", OUT_OF_BOUNDS)?;
+    test.spawn(WasmTestScript(script.clone()));
+    test.run_until_instantiated(&script)?;
+    // A length of i32::MAX is refused before the host allocates anything for it.
+    test.assert_returns(&script, "huge_name", (), &[Value::I32(-1)]);
+    test.assert_returns(&script, "huge_payload", (), &[Value::I32(-2)]);
+    test.assert_returns(&script, "past_the_end", (), &[Value::I32(-1)]);
+    Ok(())
+}
@@ -0,0 +1,51 @@
+use bevy::{ecs::event::ManualEventReader, prelude::Events};
+use bevy_wasm_scripting::*;
+use wasmer::Value;
+
+const GROWS: &str = r#"
+(module
+  (memory (export "memory") 1)
+  (func (export "grow") (param i32) (result i32)
+    (memory.grow (local.get 0))))
+"#;
+
+fn limited_app(pages: u32) -> WasmTestApp {
+    let mut test = WasmTestApp::new();
+    test.world()
+        .resource::<WasmMemoryLimits>()
+        .set_default_limit(Some(pages));
+    test
+}
+
+#[test]
+fn growing_past_the_limit_fails() -> Result<(), anyhow::Error> {
+    let mut test = limited_app(2);
+    let mut reader = ManualEventReader::<WasmScriptError>::default();
+    let script = test.add_wat("grows", GROWS)?;
+    test.spawn(WasmTestScript(script.clone()));
+    test.run_until_instantiated(&script)?;
+    test.assert_returns(&script, "grow", (1,), &[Value::I32(1)]);
+    test.assert_returns(&script, "grow", (1,), &[Value::I32(-1)]);
+    test.update();
+    let events = test.world().resource::<Events<WasmScriptError>>();
+    let exceeded: Vec<_> = reader
+        .iter(events)
+        .filter_map(|error| match error {
+            WasmScriptError::MemoryLimitExceeded {
+                limit, requested, ..
+            } => Some((*limit, *requested)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(exceeded, [(2, 3)]);
+    Ok(())
+}
+
+#[test]
+fn minimum_memory_over_the_limit_is_not_instantiated() -> Result<(), anyhow::Error> {
+    let mut test = limited_app(1);
+    let script = test.add_wat("too_big", r#"(module (memory (export "memory") 2))"#)?;
+    test.spawn(WasmTestScript(script.clone()));
+    assert!(test.run_until_instantiated(&script).is_err());
+    Ok(())
+}
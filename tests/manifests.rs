@@ -0,0 +1,103 @@
+use bevy::{
+    asset::HandleId,
+    ecs::event::ManualEventReader,
+    prelude::{Assets, Events, Handle},
+};
+use bevy_wasm_scripting::*;
+use wasmer::Value;
+
+const ADD_ONE: &str = include_str!("../assets/add_one.wat");
+
+fn manifest(script: &Handle<WasmScript>, entry_point: &str) -> WasmScriptManifest {
+    WasmScriptManifest {
+        name: "add_one".to_string(),
+        version: "1.0.0".to_string(),
+        script: script.clone(),
+        entry_points: vec![ScriptEntryPoint {
+            name: entry_point.to_string(),
+            params: vec![ScriptValueType::I32],
+            results: vec![ScriptValueType::I32],
+        }],
+        imports: Vec::new(),
+        capabilities: Vec::new(),
+        memory_limit: None,
+    }
+}
+
+fn add_manifest(
+    test: &mut WasmTestApp,
+    manifest: WasmScriptManifest,
+) -> Handle<WasmScriptManifest> {
+    test.world()
+        .resource_mut::<Assets<WasmScriptManifest>>()
+        .add(manifest)
+}
+
+fn mismatches(test: &mut WasmTestApp, reader: &mut ManualEventReader<WasmScriptError>) -> usize {
+    let events = test.world().resource::<Events<WasmScriptError>>();
+    reader
+        .iter(events)
+        .filter(|error| matches!(error, WasmScriptError::ManifestMismatch { .. }))
+        .count()
+}
+
+#[test]
+fn matching_manifest_instantiates() -> Result<(), anyhow::Error> {
+    let mut test = WasmTestApp::new();
+    let script = test.add_wat("add_one", ADD_ONE)?;
+    let _manifest = add_manifest(&mut test, manifest(&script, "add_one"));
+    test.spawn(WasmTestScript(script.clone()));
+    test.run_until_instantiated(&script)?;
+    test.assert_returns(&script, "add_one", (1,), &[Value::I32(2)]);
+    Ok(())
+}
+
+#[test]
+fn manifest_loaded_before_script_rejects_it() -> Result<(), anyhow::Error> {
+    let mut test = WasmTestApp::new();
+    let mut reader = ManualEventReader::default();
+    let script = test
+        .world()
+        .resource_mut::<Assets<WasmScript>>()
+        .get_handle(HandleId::random::<WasmScript>());
+    let _manifest = add_manifest(&mut test, manifest(&script, "missing"));
+    test.update();
+    let _ = test.world().resource_mut::<Assets<WasmScript>>().set(
+        script.clone(),
+        WasmScript::from_wat_str("add_one", ADD_ONE)?,
+    );
+    test.spawn(WasmTestScript(script.clone()));
+    assert!(test.run_until_instantiated(&script).is_err());
+    assert!(mismatches(&mut test, &mut reader) > 0);
+    Ok(())
+}
+
+#[test]
+fn manifest_loaded_after_script_drops_it() -> Result<(), anyhow::Error> {
+    let mut test = WasmTestApp::new();
+    let mut reader = ManualEventReader::default();
+    let script = test.add_wat("add_one", ADD_ONE)?;
+    test.spawn(WasmTestScript(script.clone()));
+    test.run_until_instantiated(&script)?;
+
+    let bad = add_manifest(&mut test, manifest(&script, "missing"));
+    let mut found = 0;
+    for _ in 0..3 {
+        test.update();
+        found += mismatches(&mut test, &mut reader);
+    }
+    assert!(matches!(
+        test.script(&script),
+        Some(WasmScript::Compiled(_))
+    ));
+    assert_eq!(found, 1);
+
+    // Fixing the manifest lets the script be instantiated again.
+    let _ = test
+        .world()
+        .resource_mut::<Assets<WasmScriptManifest>>()
+        .set(bad, manifest(&script, "add_one"));
+    test.run_until_instantiated(&script)?;
+    test.assert_returns(&script, "add_one", (1,), &[Value::I32(2)]);
+    Ok(())
+}
@@ -0,0 +1,145 @@
+use bevy::{
+    ecs::{event::ManualEventReader, system::SystemState},
+    prelude::{Component, Events, Handle, Without},
+};
+use bevy_wasm_scripting::*;
+use wasmer::{Imports, Value};
+
+const OPEN_QUERIES: &str = r#"
+(module
+  (import "query" "component_id" (func $component_id (param i32 i32) (result i32)))
+  (import "query" "query" (func $query (param i32 i32) (result i32)))
+  (import "query" "query_next" (func $query_next (param i32 i32 i32) (result i32)))
+  (memory 1)
+  (data (i32.const 0) "health")
+  (func (export "empty") (result i32)
+    (i32.store (i32.const 32) (call $component_id (i32.const 0) (i32.const 6)))
+    (call $query (i32.const 32) (i32.const 0)))
+  ;; Opens queries until one fails, returning the failure.
+  (func (export "open_all") (result i32)
+    (local $handle i32)
+    (i32.store (i32.const 32) (call $component_id (i32.const 0) (i32.const 6)))
+    (loop $open
+      (local.set $handle (call $query (i32.const 32) (i32.const 1)))
+      (br_if $open (i32.ge_s (local.get $handle) (i32.const 0))))
+    (local.get $handle))
+  ;; Reads from a new query into a buffer too small for a 12 byte row.
+  (func (export "next_small") (result i32)
+    (i32.store (i32.const 32) (call $component_id (i32.const 0) (i32.const 6)))
+    (call $query_next (call $query (i32.const 32) (i32.const 1)) (i32.const 64) (i32.const 4)))
+  (export "memory" (memory 0)))
+"#;
+
+#[derive(Component)]
+struct Health(i32);
+
+#[derive(Component)]
+struct Hidden;
+
+#[derive(Component)]
+struct HealthScript(Handle<WasmScript>);
+
+impl WasmScriptComponent for HealthScript {
+    type ImportQueriedComponents = &'static Health;
+    type ImportResources = ();
+
+    fn get_wasm_script_handle(&self) -> &Handle<WasmScript> {
+        &self.0
+    }
+
+    fn get_imports_from_world(_wasmer_store: &mut WasmerStore, _world: &WorldPointer) -> Imports {
+        Imports::new()
+    }
+}
+
+fn health_app() -> WasmTestApp {
+    let mut test = WasmTestApp::new();
+    test.app()
+        .add_wasm_query_component("health", |health: &Health| health.0.to_le_bytes())
+        .add_wasm_script_component::<HealthScript>();
+    for health in [10, 20, 30] {
+        test.spawn(Health(health));
+    }
+    test.spawn((Health(100), Hidden));
+    test
+}
+
+fn call_as_component<Filter: bevy::ecs::query::ReadOnlyWorldQuery + 'static>(
+    test: &mut WasmTestApp,
+    script: &Handle<WasmScript>,
+    export: &str,
+) -> Result<i32, anyhow::Error> {
+    let mut state = SystemState::<WasmScriptComponentEnv<HealthScript, Filter>>::new(test.world());
+    let mut env = state.get_mut(test.world());
+    env.call_if_instantiated_0::<i32>(script, export)
+}
+
+fn denied(test: &mut WasmTestApp, reader: &mut ManualEventReader<WasmScriptError>) -> usize {
+    let events = test.world().resource::<Events<WasmScriptError>>();
+    reader
+        .iter(events)
+        .filter(|error| matches!(error, WasmScriptError::QueryDenied { .. }))
+        .count()
+}
+
+#[test]
+fn component_env_queries_its_components() -> Result<(), anyhow::Error> {
+    let mut test = health_app();
+    let script = test.add_wat("sum_health", include_str!("../assets/sum_health.wat"))?;
+    test.spawn(HealthScript(script.clone()));
+    test.run_until_instantiated(&script)?;
+    assert_eq!(call_as_component::<()>(&mut test, &script, "main")?, 160);
+    // Entities excluded by the env's filter are left out.
+    assert_eq!(
+        call_as_component::<Without<Hidden>>(&mut test, &script, "main")?,
+        60
+    );
+    Ok(())
+}
+
+#[test]
+fn queries_without_component_access_are_denied() -> Result<(), anyhow::Error> {
+    let mut test = health_app();
+    let mut reader = ManualEventReader::default();
+    let script = test.add_wat("sum_health", include_str!("../assets/sum_health.wat"))?;
+    test.spawn(HealthScript(script.clone()));
+    test.run_until_instantiated(&script)?;
+    // `WasmScriptEnv` holds no component access, so its calls may not query.
+    test.assert_returns(&script, "main", (), &[Value::I32(-1)]);
+    test.update();
+    assert_eq!(denied(&mut test, &mut reader), 1);
+    Ok(())
+}
+
+#[test]
+fn empty_queries_are_refused() -> Result<(), anyhow::Error> {
+    let mut test = health_app();
+    let script = test.add_wat("open_queries", OPEN_QUERIES)?;
+    test.spawn(HealthScript(script.clone()));
+    test.run_until_instantiated(&script)?;
+    assert_eq!(call_as_component::<()>(&mut test, &script, "empty")?, -1);
+    Ok(())
+}
+
+#[test]
+fn open_queries_are_capped() -> Result<(), anyhow::Error> {
+    let mut test = health_app();
+    let script = test.add_wat("open_queries", OPEN_QUERIES)?;
+    test.spawn(HealthScript(script.clone()));
+    test.run_until_instantiated(&script)?;
+    assert_eq!(call_as_component::<()>(&mut test, &script, "open_all")?, -3);
+    Ok(())
+}
+
+#[test]
+fn buffers_too_small_for_a_row_are_refused() -> Result<(), anyhow::Error> {
+    let mut test = health_app();
+    let script = test.add_wat("open_queries", OPEN_QUERIES)?;
+    test.spawn(HealthScript(script.clone()));
+    test.run_until_instantiated(&script)?;
+    assert_eq!(
+        call_as_component::<()>(&mut test, &script, "next_small")?,
+        -1
+    );
+    Ok(())
+}
@@ -0,0 +1,130 @@
+use std::{path::PathBuf, time::Duration};
+
+use bevy::{ecs::system::SystemState, prelude::Mut};
+use bevy_wasm_scripting::*;
+use wasmer::Value;
+
+fn recording_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "bevy_wasm_scripting_{}_{}.rec",
+        name,
+        std::process::id()
+    ))
+}
+
+fn counter_app(
+    recorder: &WasmCallRecorder,
+) -> Result<(WasmTestApp, bevy::prelude::Handle<WasmScript>), anyhow::Error> {
+    let mut test = WasmTestApp::new();
+    test.world().insert_resource(recorder.clone());
+    let script = test.add_wat("counter", include_str!("../assets/counter.wat"))?;
+    test.spawn(WasmTestScript(script.clone()));
+    test.run_until_instantiated(&script)?;
+    Ok((test, script))
+}
+
+fn calls(entries: &[RecordedEntry]) -> Vec<&str> {
+    entries
+        .iter()
+        .filter_map(|entry| match entry {
+            RecordedEntry::Call { export, .. } => Some(export.as_str()),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn replays_start_from_the_recorded_state() -> Result<(), anyhow::Error> {
+    let (first, second) = (recording_path("first"), recording_path("second"));
+    let recorder = WasmCallRecorder::default();
+    recorder.start(&first)?;
+    let (mut test, script) = counter_app(&recorder)?;
+    test.assert_returns(&script, "increment", (), &[Value::I32(1)]);
+    test.assert_returns(&script, "increment", (), &[Value::I32(2)]);
+    // A second recording, started mid-session, begins with the count at 2.
+    recorder.start(&second)?;
+    test.assert_returns(&script, "increment", (), &[Value::I32(3)]);
+    recorder.stop()?;
+    let replay = WasmReplay::load(&second, "counter")?;
+    let report = replay.run(include_bytes!("../assets/counter.wat"))?;
+    assert_eq!(report.calls, 1);
+    assert!(report.matched(), "{:?}", report.mismatches);
+    let _ = std::fs::remove_file(first);
+    let _ = std::fs::remove_file(second);
+    Ok(())
+}
+
+#[test]
+fn scripts_instantiated_while_not_recording_are_not_recorded() -> Result<(), anyhow::Error> {
+    let path = recording_path("not_wrapped");
+    let recorder = WasmCallRecorder::default();
+    let (mut test, script) = counter_app(&recorder)?;
+    recorder.start(&path)?;
+    test.assert_returns(&script, "increment", (), &[Value::I32(1)]);
+    recorder.stop()?;
+    assert!(WasmCallRecorder::load(&path)?.is_empty());
+    let _ = std::fs::remove_file(path);
+    Ok(())
+}
+
+#[test]
+fn coroutine_calls_are_recorded() -> Result<(), anyhow::Error> {
+    let path = recording_path("coroutines");
+    let recorder = WasmCallRecorder::default();
+    recorder.start(&path)?;
+    let mut test = WasmTestApp::new();
+    test.world().insert_resource(recorder.clone());
+    let script = test.add_wat(
+        "finishes",
+        r#"(module
+          (func (export "asyncify_start_unwind") (param i32))
+          (func (export "asyncify_stop_unwind"))
+          (func (export "asyncify_start_rewind") (param i32))
+          (func (export "asyncify_stop_rewind"))
+          (func (export "asyncify_get_state") (result i32) (i32.const 0))
+          (func (export "coroutine_stack_alloc") (result i32) (i32.const 1024))
+          (func (export "coroutine_stack_free") (param i32))
+          (func (export "run")))"#,
+    )?;
+    let entity = test.spawn(WasmTestScript(script.clone()));
+    test.run_until_instantiated(&script)?;
+    let mut state = SystemState::<WasmScriptEnv>::new(test.world());
+    let suspended = test
+        .world()
+        .resource_scope(|world, mut coroutines: Mut<WasmCoroutines>| {
+            let mut env = state.get_mut(world);
+            coroutines.start(&mut env, entity, &script, "run", ())
+        })?;
+    assert!(!suspended);
+    recorder.stop()?;
+    assert_eq!(
+        calls(&WasmCallRecorder::load(&path)?),
+        ["coroutine_stack_alloc", "run", "coroutine_stack_free"]
+    );
+    let _ = std::fs::remove_file(path);
+    Ok(())
+}
+
+#[test]
+fn recorded_timeouts_replay_as_failures() -> Result<(), anyhow::Error> {
+    let path = recording_path("timeout");
+    let recorder = WasmCallRecorder::default();
+    recorder.start(&path)?;
+    let mut test = WasmTestApp::new();
+    test.world().insert_resource(recorder.clone());
+    test.world()
+        .resource_mut::<WasmWatchdog>()
+        .set_timeout(Some(Duration::from_millis(50)));
+    let script = test.add_wat("spin", include_str!("../assets/spin_forever.wat"))?;
+    test.spawn(WasmTestScript(script.clone()));
+    test.run_until_instantiated(&script)?;
+    test.assert_traps(&script, "main", (-1,));
+    recorder.stop()?;
+    let report = WasmReplay::load(&path, "spin")?
+        .with_timeout(Duration::from_millis(50))
+        .run(include_bytes!("../assets/spin_forever.wat"))?;
+    assert_eq!(report.calls, 1);
+    assert!(report.matched(), "{:?}", report.mismatches);
+    let _ = std::fs::remove_file(path);
+    Ok(())
+}
@@ -0,0 +1,109 @@
+use bevy::{ecs::system::SystemState, prelude::Mut};
+use bevy_wasm_scripting::*;
+use wasmer::Value;
+
+const MEMORY: &str = r#"
+(module
+  (global $count (export "count") (mut i32) (i32.const 0))
+  (memory (export "memory") 1)
+  (func (export "increment") (result i32)
+    (global.set $count (i32.add (global.get $count) (i32.const 1)))
+    (i32.store (i32.const 5000) (global.get $count))
+    (global.get $count))
+  (func (export "get_count") (result i32) (global.get $count))
+  (func (export "stored") (result i32) (i32.load (i32.const 5000)))
+  ;; Grows memory by a page and writes to it, returning the new page count.
+  (func (export "grow") (result i32)
+    (drop (memory.grow (i32.const 1)))
+    (i32.store (i32.const 70000) (i32.const 7))
+    (memory.size))
+  (func (export "grown") (result i32) (i32.load (i32.const 70000))))
+"#;
+
+fn save(test: &mut WasmTestApp, frame: i32) -> Result<(), anyhow::Error> {
+    let mut state = SystemState::<(WasmScriptEnv, ScriptHostState)>::new(test.world());
+    test.world()
+        .resource_scope(|world, mut rollback: Mut<WasmRollback>| {
+            let (mut env, host) = state.get_mut(world);
+            rollback.save_frame(frame, &mut env, &host)
+        })
+}
+
+fn load(test: &mut WasmTestApp, frame: i32) -> Result<(), anyhow::Error> {
+    let mut state = SystemState::<(WasmScriptEnv, ScriptHostState)>::new(test.world());
+    test.world()
+        .resource_scope(|world, rollback: Mut<WasmRollback>| {
+            let (mut env, host) = state.get_mut(world);
+            rollback.load_frame(frame, &mut env, &host)
+        })
+}
+
+fn rollback_app() -> Result<(WasmTestApp, bevy::prelude::Handle<WasmScript>), anyhow::Error> {
+    let mut test = WasmTestApp::new();
+    let script = test.add_wat("rollback", MEMORY)?;
+    test.spawn(WasmTestScript(script.clone()));
+    test.run_until_instantiated(&script)?;
+    Ok((test, script))
+}
+
+#[test]
+fn load_frame_restores_memory_and_globals() -> Result<(), anyhow::Error> {
+    let (mut test, script) = rollback_app()?;
+    for frame in 0..3 {
+        save(&mut test, frame)?;
+        test.call(&script, "increment", ())?;
+    }
+    load(&mut test, 1)?;
+    test.assert_returns(&script, "get_count", (), &[Value::I32(1)]);
+    test.assert_returns(&script, "stored", (), &[Value::I32(1)]);
+    // Saving a frame again drops the frames after it.
+    save(&mut test, 1)?;
+    assert_eq!(
+        test.world()
+            .resource::<WasmRollback>()
+            .frames()
+            .collect::<Vec<_>>(),
+        [0, 1]
+    );
+    Ok(())
+}
+
+#[test]
+fn grown_memory_is_zeroed() -> Result<(), anyhow::Error> {
+    let (mut test, script) = rollback_app()?;
+    save(&mut test, 0)?;
+    test.assert_returns(&script, "grow", (), &[Value::I32(2)]);
+    load(&mut test, 0)?;
+    test.assert_returns(&script, "grown", (), &[Value::I32(0)]);
+    Ok(())
+}
+
+#[test]
+fn timers_are_rolled_back() -> Result<(), anyhow::Error> {
+    let (mut test, script) = rollback_app()?;
+    save(&mut test, 0)?;
+    let entity = test.spawn(());
+    let timers = test.world().resource::<WasmScriptTimers>().clone();
+    assert!(timers.is_empty());
+    // Timers can only be set from a script call, so set one through a script.
+    let setter = test.add_wat(
+        "set_timer",
+        r#"(module
+          (import "timers" "set_timeout" (func $set (param f64 i32 i32) (result i32)))
+          (func (export "set") (param f64) (result i32)
+            (call $set (local.get 0) (i32.const 1000) (i32.const 0))))"#,
+    )?;
+    test.spawn(WasmTestScript(setter.clone()));
+    test.run_until_instantiated(&setter)?;
+    test.call(&setter, "set", (EntityId::from_entity(entity),))?;
+    assert_eq!(timers.count(entity), 1);
+    test.call(&script, "increment", ())?;
+    save(&mut test, 1)?;
+    load(&mut test, 0)?;
+    assert!(timers.is_empty());
+    test.assert_returns(&script, "get_count", (), &[Value::I32(0)]);
+    load(&mut test, 1)?;
+    assert_eq!(timers.count(entity), 1);
+    test.assert_returns(&script, "get_count", (), &[Value::I32(1)]);
+    Ok(())
+}
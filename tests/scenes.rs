@@ -0,0 +1,64 @@
+use bevy::{
+    ecs::entity::EntityMap,
+    prelude::{AppTypeRegistry, World},
+    scene::{serde::SceneDeserializer, DynamicScene},
+};
+use bevy_wasm_scripting::{scene_scripts::WasmScriptPath, *};
+use serde::de::DeserializeSeed;
+
+fn load_scene(test: &mut WasmTestApp, ron: &str) -> Result<DynamicScene, anyhow::Error> {
+    let registry = test.world().resource::<AppTypeRegistry>().clone();
+    let registry = registry.read();
+    let mut deserializer = ron::de::Deserializer::from_str(ron)?;
+    Ok(SceneDeserializer {
+        type_registry: &registry,
+    }
+    .deserialize(&mut deserializer)?)
+}
+
+fn script_paths(world: &mut World) -> Vec<(String, Option<f64>)> {
+    let mut paths: Vec<_> = world
+        .query::<&WasmScriptPath>()
+        .iter(world)
+        .map(|script| (script.path.clone(), script.config.get("speed").copied()))
+        .collect();
+    paths.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    paths
+}
+
+#[test]
+fn script_paths_round_trip_through_scenes() -> Result<(), anyhow::Error> {
+    let mut test = WasmTestApp::new();
+    test.spawn(WasmScriptPath::new("configured_speed.wat").with_config("speed", 2.5));
+    test.spawn(WasmScriptPath::new("patrol.wat"));
+    let registry = test.world().resource::<AppTypeRegistry>().clone();
+    let saved = DynamicScene::from_world(test.world(), &registry).serialize_ron(&registry)?;
+    assert!(saved.contains("\"bevy_wasm_scripting::scene_scripts::WasmScriptPath\""));
+
+    let scene = load_scene(&mut test, &saved)?;
+    let mut loaded = WasmTestApp::new();
+    scene.write_to_world(loaded.world(), &mut EntityMap::default())?;
+    assert_eq!(
+        script_paths(loaded.world()),
+        [
+            ("configured_speed.wat".to_string(), Some(2.5)),
+            ("patrol.wat".to_string(), None),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn example_scene_loads() -> Result<(), anyhow::Error> {
+    let mut test = WasmTestApp::new();
+    let scene = load_scene(&mut test, include_str!("../assets/scenes/scripted.scn.ron"))?;
+    scene.write_to_world(test.world(), &mut EntityMap::default())?;
+    assert_eq!(
+        script_paths(test.world()),
+        [
+            ("configured_speed.wat".to_string(), None),
+            ("configured_speed.wat".to_string(), Some(2.5)),
+        ]
+    );
+    Ok(())
+}
@@ -0,0 +1,102 @@
+use bevy_wasm_scripting::*;
+use wasmer::Value;
+
+const COUNTER_SYSTEM: &str = r#"
+(module
+  (import "systems" "add_system" (func $add_system (param i32 i32 i32 i32) (result i32)))
+  (import "systems" "add_run_condition" (func $add_run_condition (param i32 i32 i32) (result i32)))
+  (memory 1)
+  (data (i32.const 0) "count_frame")
+  (data (i32.const 16) "even")
+  (global $frames (mut i32) (i32.const 0))
+  (global $checks (mut i32) (i32.const 0))
+  (func (export "register")
+    (drop (call $add_run_condition
+      (call $add_system (i32.const 0) (i32.const 11) (i32.const 0) (i32.const 0))
+      (i32.const 16) (i32.const 4))))
+  (func (export "even") (result i32)
+    (global.set $checks (i32.add (global.get $checks) (i32.const 1)))
+    (i32.eqz (i32.rem_u (global.get $checks) (i32.const 2))))
+  (func (export "count_frame")
+    (global.set $frames (i32.add (global.get $frames) (i32.const 1))))
+  (func (export "frames") (result i32)
+    global.get $frames)
+  (func (export "add_late_system") (result i32)
+    (call $add_system (i32.const 0) (i32.const 11) (i32.const 0) (i32.const 0)))
+  (func (export "add_late_condition") (result i32)
+    (call $add_run_condition (i32.const 0) (i32.const 16) (i32.const 4)))
+  (export "memory" (memory 0)))
+"#;
+
+#[test]
+fn registered_systems_run_in_their_phase() -> Result<(), anyhow::Error> {
+    let mut test = WasmTestApp::new();
+    let script = test.add_wat("counter_system", COUNTER_SYSTEM)?;
+    test.spawn(WasmTestScript(script.clone()));
+    test.run_until_instantiated(&script)?;
+    // Registration happens at the end of the update the script was instantiated in.
+    let systems = test.world().resource::<WasmScriptSystems>();
+    assert_eq!(systems.in_phase(DEFAULT_SCRIPT_PHASE).count(), 1);
+
+    let before = match &*test.call(&script, "frames", ())? {
+        [Value::I32(frames)] => *frames,
+        results => panic!("frames returned {:?}", results),
+    };
+    for _ in 0..4 {
+        test.update();
+    }
+    // The run condition lets the system run every other update.
+    test.assert_returns(&script, "frames", (), &[Value::I32(before + 2)]);
+    Ok(())
+}
+
+#[test]
+fn systems_are_only_declared_while_registering() -> Result<(), anyhow::Error> {
+    let mut test = WasmTestApp::new();
+    let script = test.add_wat("counter_system", COUNTER_SYSTEM)?;
+    test.spawn(WasmTestScript(script.clone()));
+    test.run_until_instantiated(&script)?;
+    test.update();
+    test.assert_returns(&script, "add_late_system", (), &[Value::I32(-1)]);
+    test.assert_returns(&script, "add_late_condition", (), &[Value::I32(-1)]);
+    Ok(())
+}
+
+#[test]
+fn systems_run_in_label_order() -> Result<(), anyhow::Error> {
+    let mut test = WasmTestApp::new();
+    // Systems are declared as c, b, a but ordered a, b, c: b after the "early" label on a, and c
+    // after b through its export.
+    let script = test.add_wat(
+        "ordered_systems",
+        r#"(module
+          (import "systems" "add_system" (func $add_system (param i32 i32 i32 i32) (result i32)))
+          (import "systems" "add_system_label" (func $label (param i32 i32 i32) (result i32)))
+          (import "systems" "add_system_after" (func $after (param i32 i32 i32) (result i32)))
+          (memory 1)
+          (data (i32.const 0) "abc")
+          (data (i32.const 8) "early")
+          (global $log (mut i32) (i32.const 0))
+          (func $add (param i32) (result i32)
+            (call $add_system (local.get 0) (i32.const 1) (i32.const 0) (i32.const 0)))
+          (func (export "register")
+            (local $c i32) (local $b i32)
+            (local.set $c (call $add (i32.const 2)))
+            (local.set $b (call $add (i32.const 1)))
+            (drop (call $label (call $add (i32.const 0)) (i32.const 8) (i32.const 5)))
+            (drop (call $after (local.get $b) (i32.const 8) (i32.const 5)))
+            (drop (call $after (local.get $c) (i32.const 1) (i32.const 1))))
+          (func $push (param i32)
+            (global.set $log (i32.add (i32.mul (global.get $log) (i32.const 10)) (local.get 0))))
+          (func (export "a") (call $push (i32.const 1)))
+          (func (export "b") (call $push (i32.const 2)))
+          (func (export "c") (call $push (i32.const 3)))
+          (func (export "log") (result i32) (global.get $log))
+          (export "memory" (memory 0)))"#,
+    )?;
+    test.spawn(WasmTestScript(script.clone()));
+    test.run_until_instantiated(&script)?;
+    test.update();
+    test.assert_returns(&script, "log", (), &[Value::I32(123)]);
+    Ok(())
+}
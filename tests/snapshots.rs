@@ -0,0 +1,65 @@
+use bevy::{ecs::system::SystemState, prelude::Handle};
+use bevy_wasm_scripting::*;
+use wasmer::Value;
+
+const COUNTER: &str = r#"
+(module
+  (memory (export "memory") 1)
+  (global $count (export "count") (mut i32) (i32.const 0))
+  (func (export "tick") (result i32)
+    (global.set $count (i32.add (global.get $count) (i32.const 1)))
+    (i32.store (i32.const 0) (global.get $count))
+    (global.get $count)))
+"#;
+
+const SERIALIZE_TOO_MUCH: &str = r#"
+(module
+  (memory (export "memory") 1)
+  (func (export "serialize") (result i32 i32)
+    (i32.const 16)
+    (i32.const 2147483647)))
+"#;
+
+fn take(
+    test: &mut WasmTestApp,
+    script: &Handle<WasmScript>,
+) -> Result<ScriptSnapshot, anyhow::Error> {
+    let mut state = SystemState::<WasmScriptEnv>::new(test.world());
+    let mut env = state.get_mut(test.world());
+    ScriptSnapshot::take(&mut env, script)
+}
+
+fn restore(
+    test: &mut WasmTestApp,
+    script: &Handle<WasmScript>,
+    snapshot: &ScriptSnapshot,
+) -> Result<(), anyhow::Error> {
+    let mut state = SystemState::<WasmScriptEnv>::new(test.world());
+    let mut env = state.get_mut(test.world());
+    snapshot.restore(&mut env, script)
+}
+
+#[test]
+fn raw_snapshots_restore_memory_and_globals() -> Result<(), anyhow::Error> {
+    let mut test = WasmTestApp::new();
+    let script = test.add_wat("counter", COUNTER)?;
+    test.spawn(WasmTestScript(script.clone()));
+    test.run_until_instantiated(&script)?;
+    test.assert_returns(&script, "tick", (), &[Value::I32(1)]);
+    let snapshot = take(&mut test, &script)?;
+    test.assert_returns(&script, "tick", (), &[Value::I32(2)]);
+    test.assert_returns(&script, "tick", (), &[Value::I32(3)]);
+    restore(&mut test, &script, &snapshot)?;
+    test.assert_returns(&script, "tick", (), &[Value::I32(2)]);
+    Ok(())
+}
+
+#[test]
+fn serialized_state_past_the_end_of_memory_is_refused() -> Result<(), anyhow::Error> {
+    let mut test = WasmTestApp::new();
+    let script = test.add_wat("serialize_too_much", SERIALIZE_TOO_MUCH)?;
+    test.spawn(WasmTestScript(script.clone()));
+    test.run_until_instantiated(&script)?;
+    assert!(take(&mut test, &script).is_err());
+    Ok(())
+}
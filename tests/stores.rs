@@ -0,0 +1,40 @@
+use bevy::{
+    ecs::system::SystemState,
+    prelude::{Assets, Mut},
+};
+use bevy_wasm_scripting::*;
+use wasmer::{imports, Value};
+
+#[test]
+fn directly_instantiated_scripts_are_called_through_wasm_script_env() -> Result<(), anyhow::Error> {
+    let mut test = WasmTestApp::new();
+    let script = test.add_wat("add_one", include_str!("../assets/add_one.wat"))?;
+    for _ in 0..10 {
+        if matches!(test.script(&script), Some(WasmScript::Compiled(_))) {
+            break;
+        }
+        test.update();
+    }
+    test.world()
+        .resource_scope(|world, mut assets: Mut<Assets<WasmScript>>| {
+            let mut wasmer_store = world.resource_mut::<WasmerStore>();
+            assets
+                .get_mut(&script)
+                .unwrap()
+                .instantiate_if_compiled(&mut wasmer_store, &imports! {})
+        })
+        .then_some(())
+        .ok_or_else(|| anyhow::Error::msg("script was not instantiated"))?;
+    assert!(!test
+        .world()
+        .resource::<WasmScriptStores>()
+        .contains(&script));
+
+    let mut state = SystemState::<WasmScriptComponentEnv<WasmTestScript>>::new(test.world());
+    let mut env = state.get_mut(test.world());
+    assert!(env
+        .call_if_instantiated_1::<i32, i32>(&script, "add_one", 1)
+        .is_err());
+    test.assert_returns(&script, "add_one", (1,), &[Value::I32(2)]);
+    Ok(())
+}
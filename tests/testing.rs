@@ -0,0 +1,27 @@
+use bevy_wasm_scripting::*;
+use wasmer::Value;
+
+#[test]
+fn test_apps_check_exports_results_and_traps() -> Result<(), anyhow::Error> {
+    let mut test = WasmTestApp::new();
+    let add_one = test.add_wat("add_one", include_str!("../assets/add_one.wat"))?;
+    let traps = test.add_wat("traps", r#"(module (func (export "fail") unreachable))"#)?;
+    test.spawn(WasmTestScript(add_one.clone()));
+    test.spawn(WasmTestScript(traps.clone()));
+    test.run_until_instantiated(&add_one)?;
+    test.run_until_instantiated(&traps)?;
+
+    test.assert_exports(&add_one, "add_one");
+    test.assert_returns(&add_one, "add_one", (41,), &[Value::I32(42)]);
+    let err = test.assert_traps(&traps, "fail", ());
+    assert!(err.to_string().contains("unreachable"), "{}", err);
+    Ok(())
+}
+
+#[test]
+fn invalid_scripts_are_reported() {
+    let mut test = WasmTestApp::new();
+    let script = test.add_wasm("not_wasm", b"not a wasm module");
+    test.spawn(WasmTestScript(script.clone()));
+    assert!(test.run_until_instantiated(&script).is_err());
+}
@@ -0,0 +1,30 @@
+use std::time::Duration;
+
+use bevy_wasm_scripting::*;
+use wasmer::Value;
+
+#[test]
+fn long_calls_time_out() -> Result<(), anyhow::Error> {
+    let mut test = WasmTestApp::new();
+    test.world()
+        .resource_mut::<WasmWatchdog>()
+        .set_timeout(Some(Duration::from_millis(50)));
+    let script = test.add_wat("spin", include_str!("../assets/spin_forever.wat"))?;
+    test.spawn(WasmTestScript(script.clone()));
+    test.run_until_instantiated(&script)?;
+    let err = test.assert_traps(&script, "main", (-1,));
+    assert!(err.is::<ScriptTimeout>(), "{}", err);
+    // The script is left instantiated, and later calls run normally.
+    test.assert_returns(&script, "main", (3,), &[Value::I32(3)]);
+    Ok(())
+}
+
+#[test]
+fn calls_run_unguarded_without_a_timeout() -> Result<(), anyhow::Error> {
+    let mut test = WasmTestApp::new();
+    let script = test.add_wat("spin", include_str!("../assets/spin_forever.wat"))?;
+    test.spawn(WasmTestScript(script.clone()));
+    test.run_until_instantiated(&script)?;
+    test.assert_returns(&script, "main", (1_000_000,), &[Value::I32(1_000_000)]);
+    Ok(())
+}